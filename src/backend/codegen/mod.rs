@@ -1,27 +1,63 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
 
 use indexmap::IndexMap;
 
 use crate::frontend::ast::{
-    Ast, Expr, Item, LValue, Line, LinkAttr, Operation, Val, is_builtin_func,
+    Ast, ConstValue, Expr, Extend, FieldRef, Function, GlobalKind, Item, LValue, Line, LinkAttr,
+    Operation, Type, Val, Width,
+    error::Spanned,
+    is_builtin_func,
+    sema::{ConstRef, eval_const, eval_const_with},
+    store_width,
+    typeck::TypeEnv,
 };
 pub mod x86_64;
 
 #[derive(Debug)]
 pub struct ProgramIR {
     pub functions: indexmap::IndexMap<String, FunctionIR>,
+    pub globals: indexmap::IndexMap<String, GlobalIR>,
+    /// every distinct literal of the program and its label, labeled `__lit_<n>` in order of first use
+    pub literals: IndexMap<DataUnit, String>,
 }
 
 impl ProgramIR {
     pub fn build(ast: &Ast) -> Self {
         let mut functions = IndexMap::new();
-        let mut builder = CodeBuilder::new();
+        let mut globals = IndexMap::new();
+        for global in ast.globals() {
+            let value = global
+                .value
+                .as_ref()
+                .and_then(|value| eval_const(&value.inner, ast).ok());
+            globals.insert(
+                global.name.clone(),
+                GlobalIR {
+                    name: global.name.clone(),
+                    kind: global.kind,
+                    value,
+                    link_attr: global.link_attr.clone(),
+                },
+            );
+        }
+
+        let mut builder = CodeBuilder::new().with_globals(&globals).with_structs(ast);
+        for global in globals.values() {
+            if let Some(ConstValue::Str(lit)) = &global.value {
+                builder.literal(lit);
+            }
+        }
         for func in ast.funcs() {
             let Item::Function(func) = func else {
                 continue;
             };
+            builder.shadowed = func.args.iter().cloned().collect();
+            builder.types = TypeEnv::of(func).0;
             let code = if let Some(func_body) = func.body() {
-                builder.build(func_body, func.name.clone())
+                builder.build_function(func, func_body)
             } else {
                 CodeTree::default()
             };
@@ -44,10 +80,24 @@ impl ProgramIR {
                 },
             );
         }
-        Self { functions }
+        Self {
+            functions,
+            globals,
+            literals: builder.literals,
+        }
     }
 }
 
+/// A module level variable or constant
+#[derive(Debug)]
+pub struct GlobalIR {
+    pub name: String,
+    pub kind: GlobalKind,
+    /// initial value. None for zero initialized or external globals
+    pub value: Option<ConstValue>,
+    pub link_attr: LinkAttr,
+}
+
 #[derive(Debug)]
 pub struct FunctionIR {
     pub name: String,
@@ -58,28 +108,38 @@ pub struct FunctionIR {
 
 #[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub struct CodeTree {
-    data: HashMap<DataUnit, String>,
     units: Vec<CodeUnit>,
+    arrays: Vec<ArrayIR>,
 }
 
 impl CodeTree {
     fn new() -> Self {
         Self {
-            data: HashMap::new(),
+            arrays: Vec::new(),
             units: Vec::new(),
         }
     }
 }
 
+/// A buffer of len qwords, which is allocated once for each array expression
+/// and filled with init every time the expression is evaluated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArrayIR {
+    pub label: String,
+    pub init: i64,
+    pub len: usize,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum DataUnit {
-    StrLit(String),
+    /// the raw bytes of a string literal, without the terminating nul
+    StrLit(Vec<u8>),
 }
 
 impl DataUnit {
-    pub fn write_data(&self) -> &str {
+    pub fn write_data(&self) -> &[u8] {
         match self {
-            Self::StrLit(lit) => lit,
+            Self::StrLit(bytes) => bytes,
         }
     }
 }
@@ -101,11 +161,30 @@ pub enum CodeUnit {
         name: LValue,
         value: Operand,
     },
+    /// loads a value of width from addr and widens it to a qword
+    Load {
+        addr: Operand,
+        width: Width,
+        extend: Extend,
+        dest: Operand,
+    },
+    /// writes the lowest width bits of value to addr
+    Store {
+        addr: Operand,
+        value: Operand,
+        width: Width,
+    },
     Condition {
         eval: Operand,
         then: Vec<CodeUnit>,
         label: String,
     },
+    /// sets each of the len qwords of the array at label to init
+    Fill {
+        label: String,
+        init: i64,
+        len: usize,
+    },
     Cleanup,
 }
 
@@ -130,6 +209,16 @@ pub struct CodeBuilder {
     inner: CodeTree,
     temp_name: usize,
     context_name: String,
+    /// globals visible in the current function. Constants map to their value
+    globals: HashMap<String, Option<ConstValue>>,
+    /// names which shadow globals in the current function, i.e. its arguments
+    shadowed: HashSet<String>,
+    /// fields of every visible struct
+    structs: IndexMap<String, Vec<String>>,
+    /// static types of the variables of the current function
+    types: TypeEnv,
+    /// literals of all functions built so far
+    literals: IndexMap<DataUnit, String>,
 }
 
 impl CodeBuilder {
@@ -138,6 +227,206 @@ impl CodeBuilder {
             context_name: String::default(),
             inner: CodeTree::new(),
             temp_name: 0,
+            globals: HashMap::new(),
+            shadowed: HashSet::new(),
+            structs: IndexMap::new(),
+            types: TypeEnv::default(),
+            literals: IndexMap::new(),
+        }
+    }
+
+    /// the label of the string literal lit, which is added to the literal pool on first use
+    fn literal(&mut self, lit: &[u8]) -> String {
+        let label = format!("__lit_{}", self.literals.len());
+        self.literals
+            .entry(DataUnit::StrLit(lit.to_vec()))
+            .or_insert(label)
+            .clone()
+    }
+
+    fn with_globals(mut self, globals: &IndexMap<String, GlobalIR>) -> Self {
+        self.globals = globals
+            .values()
+            .map(|global| {
+                let value = match global.kind {
+                    GlobalKind::Static => None,
+                    GlobalKind::Const => global.value.clone(),
+                };
+                (global.name.clone(), value)
+            })
+            .collect();
+        self
+    }
+
+    fn with_structs(mut self, ast: &Ast) -> Self {
+        self.structs = ast
+            .structs()
+            .map(|strukt| (strukt.name.clone(), strukt.fields.clone()))
+            .collect();
+        self
+    }
+
+    fn field_offset(&self, field: &FieldRef) -> i64 {
+        field
+            .resolve(
+                self.structs
+                    .iter()
+                    .map(|(name, fields)| (name.as_str(), fields.as_slice())),
+            )
+            .expect("fields are checked during semantic analysis")
+    }
+
+    /// the address of field in the struct ptr points to
+    fn field_addr(&mut self, ptr: &Expr, field: &FieldRef) -> Operand {
+        let ptr = self.lower_unit(ptr);
+        let offset = self.field_offset(field);
+        if offset == 0 {
+            return ptr;
+        }
+        let addr = self.new_temp();
+        self.inner.units.push(CodeUnit::Operation {
+            op: Operation::Add,
+            lhs: ptr,
+            rhs: Operand::Immediate(offset),
+            dest: Operand::Temp(addr.clone()),
+        });
+        Operand::Temp(addr)
+    }
+
+    /// truncates value to its lowest byte
+    fn truncate_u8(&mut self, value: Operand) -> Operand {
+        if let Operand::Immediate(value) = value {
+            return Operand::Immediate(value & 0xff);
+        }
+        let res = self.new_temp();
+        self.inner.units.push(CodeUnit::Operation {
+            op: Operation::BitAND,
+            lhs: value,
+            rhs: Operand::Immediate(0xff),
+            dest: Operand::Temp(res.clone()),
+        });
+        Operand::Temp(res)
+    }
+
+    /// assigns value of type ty to lvalue. Lvalues containing a field are written through their address,
+    /// values assigned to a `u8` variable are truncated
+    fn lower_assignment(&mut self, lvalue: &LValue, value: Operand, ty: Option<Type>) {
+        fn has_field(lvalue: &LValue) -> bool {
+            match lvalue {
+                LValue::Field(..) => true,
+                LValue::Deref(inner) => has_field(inner),
+                LValue::Variable(_) | LValue::Malformed => false,
+            }
+        }
+
+        let (addr, width) = match lvalue {
+            LValue::Field(inner, field) => (self.field_addr(&inner.as_expr(), field), Width::Qword),
+            LValue::Deref(inner) if self.pointee(&inner.as_expr()) == Some(Type::U8) => {
+                (self.lower_unit(&inner.as_expr()), Width::Byte)
+            }
+            LValue::Deref(inner) if has_field(inner) => {
+                (self.lower_unit(&inner.as_expr()), Width::Qword)
+            }
+            LValue::Variable(var)
+                if self.types.get(var) == Some(&Type::U8) && ty != Some(Type::U8) =>
+            {
+                let value = self.truncate_u8(value);
+                return self.lower_assignment(lvalue, value, Some(Type::U8));
+            }
+            _ => {
+                let mut name = lvalue.clone();
+                self.rename_lvalue(&mut name);
+                self.inner.units.push(CodeUnit::Assignment { name, value });
+                return;
+            }
+        };
+        self.inner
+            .units
+            .push(CodeUnit::Store { addr, value, width });
+    }
+
+    /// the static type of expr, None if it is unchecked
+    fn type_of(&self, expr: &Expr) -> Option<Type> {
+        self.types.type_of(expr).ok().flatten()
+    }
+
+    /// the type expr points to, if it is a typed pointer
+    fn pointee(&self, expr: &Expr) -> Option<Type> {
+        self.type_of(expr)?.pointee().cloned()
+    }
+
+    /// multiplies operand by the size of the pointee
+    fn scale(&mut self, operand: Operand, size: i64) -> Operand {
+        match operand {
+            _ if size == 1 => operand,
+            Operand::Immediate(value) => Operand::Immediate(value.wrapping_mul(size)),
+            operand => {
+                let res = self.new_temp();
+                self.inner.units.push(CodeUnit::Operation {
+                    op: Operation::Mul,
+                    lhs: operand,
+                    rhs: Operand::Immediate(size),
+                    dest: Operand::Temp(res.clone()),
+                });
+                Operand::Temp(res)
+            }
+        }
+    }
+
+    /// lowers lhs op rhs, scaling integer offsets of typed pointers by the size of the pointee.
+    /// Comparisons and divisions with a `u64` or pointer operand are unsigned
+    fn lower_binary(&mut self, lhs_expr: &Expr, op: Operation, rhs_expr: &Expr) -> Operand {
+        let unsigned = [lhs_expr, rhs_expr]
+            .into_iter()
+            .any(|expr| self.type_of(expr).is_some_and(|ty| ty.is_unsigned()));
+        let op = match op {
+            Operation::Gt if unsigned => Operation::UGt,
+            Operation::Lt if unsigned => Operation::ULt,
+            Operation::Ge if unsigned => Operation::UGe,
+            Operation::Le if unsigned => Operation::ULe,
+            Operation::Div if unsigned => Operation::UDiv,
+            Operation::Mod if unsigned => Operation::UMod,
+            op => op,
+        };
+        let lhs_size = self.pointee(lhs_expr).map(|ty| ty.size());
+        let rhs_size = self.pointee(rhs_expr).map(|ty| ty.size());
+        let mut lhs = self.lower_unit(lhs_expr);
+        let mut rhs = self.lower_unit(rhs_expr);
+        let mut elements = None;
+        match (op, lhs_size, rhs_size) {
+            (Operation::Add | Operation::Sub, Some(size), None) => rhs = self.scale(rhs, size),
+            (Operation::Add, None, Some(size)) => lhs = self.scale(lhs, size),
+            // the difference of two pointers is the number of elements between them
+            (Operation::Sub, Some(size), Some(_)) if size > 1 => elements = Some(size),
+            _ => {}
+        }
+
+        let res = self.new_temp();
+        self.inner.units.push(CodeUnit::Operation {
+            op,
+            lhs,
+            rhs,
+            dest: Operand::Temp(res.clone()),
+        });
+        let Some(size) = elements else {
+            return Operand::Temp(res);
+        };
+        let count = self.new_temp();
+        self.inner.units.push(CodeUnit::Operation {
+            op: Operation::Div,
+            lhs: Operand::Temp(res),
+            rhs: Operand::Immediate(size),
+            dest: Operand::Temp(count.clone()),
+        });
+        Operand::Temp(count)
+    }
+
+    /// the global referenced by ident, if it is not shadowed
+    fn global(&self, ident: &str) -> Option<&Option<ConstValue>> {
+        if self.shadowed.contains(ident) {
+            None
+        } else {
+            self.globals.get(ident)
         }
     }
 
@@ -160,9 +449,38 @@ impl CodeBuilder {
         core::mem::take(&mut self.inner)
     }
 
+    /// lowers the body of func. `u8` arguments are truncated on entry, as callers pass a full qword
+    fn build_function<'a>(
+        &mut self,
+        func: &Function,
+        lines: impl Iterator<Item = &'a Line>,
+    ) -> CodeTree {
+        self.context_name = func.name.clone();
+        for (arg, ty) in func.args.iter().zip(&func.arg_types) {
+            if *ty == Some(Type::U8) {
+                let mut name = arg.clone();
+                self.rename_ident(&mut name);
+                self.lower_assignment(
+                    &LValue::Variable(arg.clone()),
+                    Operand::Variable(name),
+                    None,
+                );
+                self.inner.units.push(CodeUnit::Cleanup);
+            }
+        }
+        self.build(lines, func.name.clone())
+    }
+
     fn lower_line(&mut self, line: &Line) {
         match line {
             Line::Expr(e) => _ = self.lower_unit(e),
+            Line::Call(f, e, _) if let Some(width) = store_width(f) => {
+                let addr = self.lower_unit(&e[0]);
+                let value = self.lower_unit(&e[1]);
+                self.inner
+                    .units
+                    .push(CodeUnit::Store { addr, value, width });
+            }
             Line::Call(f, e, ret) => {
                 let args = if is_builtin_func(f) {
                     self.lower_builtin(f, e)
@@ -173,7 +491,7 @@ impl CodeBuilder {
                 let dest = ret.as_ref().map(|_| Operand::Temp(self.new_temp()));
 
                 self.inner.units.push(CodeUnit::FuncCall {
-                    name: f.clone(),
+                    name: f.inner.clone(),
                     args,
                     dest: dest.clone(),
                 });
@@ -181,51 +499,93 @@ impl CodeBuilder {
                 if let Some(dest) = dest
                     && let Some(ret) = ret
                 {
-                    let mut name = ret.clone();
-                    self.rename_lvalue(&mut name);
-                    self.inner
-                        .units
-                        .push(CodeUnit::Assignment { name, value: dest });
+                    self.lower_assignment(ret, dest, None);
                 }
             }
-            Line::Decl(v, e) => {
+            Line::Decl(v, _, e) => {
                 let val = self.lower_unit(e);
-                let mut name = v.clone();
-                self.rename_lvalue(&mut name);
-                self.inner
-                    .units
-                    .push(CodeUnit::Assignment { name, value: val });
+                self.lower_assignment(v, val, self.type_of(e));
             }
             Line::Cond(cond, then) => {
                 let cond = self.lower_unit(cond);
-                let label = self.new_temp();
-                let mut builder = CodeBuilder::new();
-                builder.temp_name = self.temp_name;
-                builder.context_name = self.context_name.clone();
-                builder.lower_line(then);
-                self.temp_name = builder.temp_name;
-                self.inner.data.extend(builder.inner.data.drain());
-                self.inner.units.push(CodeUnit::Condition {
-                    eval: cond,
-                    then: builder.inner.units,
-                    label,
-                });
+                self.lower_condition(cond, |builder| builder.lower_line(then));
             }
             Line::Malformed => {}
         }
     }
 
-    fn lower_builtin(&mut self, name: &str, exprs: &[Expr]) -> Vec<Operand> {
+    /// lowers the units emitted by then into a block, which is skipped if eval is zero
+    fn lower_condition(&mut self, eval: Operand, then: impl FnOnce(&mut CodeBuilder)) {
+        let label = self.new_temp();
+        let mut builder = CodeBuilder::new();
+        builder.temp_name = self.temp_name;
+        builder.context_name = self.context_name.clone();
+        builder.globals = self.globals.clone();
+        builder.shadowed = self.shadowed.clone();
+        builder.structs = self.structs.clone();
+        builder.types = self.types.clone();
+        builder.literals = std::mem::take(&mut self.literals);
+        then(&mut builder);
+        self.temp_name = builder.temp_name;
+        self.literals = builder.literals;
+        self.inner.arrays.append(&mut builder.inner.arrays);
+        self.inner.units.push(CodeUnit::Condition {
+            eval,
+            then: builder.inner.units,
+            label,
+        });
+    }
+
+    /// lowers `lhs && rhs` and `lhs || rhs` to 0 or 1. rhs is only evaluated if lhs does not decide the result
+    fn lower_logical(&mut self, lhs: &Expr, op: Operation, rhs: &Expr) -> Operand {
+        let lhs = self.lower_unit(lhs);
+        let res = self.new_temp();
+        self.inner.units.push(CodeUnit::Operation {
+            op: Operation::NEq,
+            lhs,
+            rhs: Operand::Immediate(0),
+            dest: Operand::Temp(res.clone()),
+        });
+        let eval = if op == Operation::LogicalOr {
+            // rhs is only evaluated if lhs is false
+            let negated = self.new_temp();
+            self.inner.units.push(CodeUnit::Operation {
+                op: Operation::EqEq,
+                lhs: Operand::Temp(res.clone()),
+                rhs: Operand::Immediate(0),
+                dest: Operand::Temp(negated.clone()),
+            });
+            Operand::Temp(negated)
+        } else {
+            Operand::Temp(res.clone())
+        };
+        self.lower_condition(eval, |builder| {
+            let rhs = builder.lower_unit(rhs);
+            builder.inner.units.push(CodeUnit::Operation {
+                op: Operation::NEq,
+                lhs: rhs,
+                rhs: Operand::Immediate(0),
+                dest: Operand::Temp(res.clone()),
+            });
+        });
+        Operand::Temp(res)
+    }
+
+    fn lower_builtin(&mut self, name: &str, exprs: &[Spanned<Expr>]) -> Vec<Operand> {
         match name {
             "addr_of" => {
                 // here we expect one args: [Variable(Ident)]
                 // We search the current function for variables of matching names, if one is found we return its addr, else we assume this to be an external symbol
                 debug_assert_eq!(exprs.len(), 1);
                 let var = &exprs[0];
-                let Expr::Val(Val::Var(ident)) = var else {
+                let Expr::Val(Val::Var(ident)) = &var.inner else {
                     panic!("currently only idents may be passed to addr_of");
                 };
 
+                if self.global(ident).is_some() {
+                    return vec![Operand::Variable(ident.clone())];
+                }
+
                 let mut ident_as_var = ident.to_string();
                 self.rename_ident(&mut ident_as_var);
 
@@ -249,10 +609,12 @@ impl CodeBuilder {
             "asm" => {
                 // we expect on argument, which is a string literal (or an ident?). we will emit this again as Variable/Ident.
                 debug_assert_eq!(exprs.len(), 1);
-                let (Expr::Val(Val::Var(lit)) | Expr::Val(Val::Lit(lit))) = &exprs[0] else {
-                    panic!("cannot interpret non string literals/idents as assembly");
+                let asm = match &exprs[0].inner {
+                    Expr::Val(Val::Var(ident)) => ident.clone(),
+                    Expr::Val(Val::Lit(lit)) => String::from_utf8_lossy(lit).into_owned(),
+                    _ => panic!("cannot interpret non string literals/idents as assembly"),
                 };
-                vec![Operand::Variable(lit.to_string())]
+                vec![Operand::Variable(asm)]
             }
             _ => exprs.iter().map(|e| self.lower_unit(e)).collect(),
         }
@@ -261,53 +623,123 @@ impl CodeBuilder {
     fn lower_unit(&mut self, expr: &Expr) -> Operand {
         match expr {
             Expr::Val(v) => match v {
-                Val::Var(name) => {
-                    let mut name = name.clone();
-                    self.rename_ident(&mut name);
-                    Operand::Variable(name)
-                }
+                Val::Var(name) => match self.global(name) {
+                    Some(None | Some(ConstValue::Array { .. })) => Operand::Variable(name.clone()),
+                    Some(Some(ConstValue::Int(value))) => Operand::Immediate(*value),
+                    Some(Some(ConstValue::Str(lit))) => {
+                        let lit = Expr::Val(Val::Lit(lit.clone()));
+                        self.lower_unit(&lit)
+                    }
+                    None => {
+                        let mut name = name.clone();
+                        self.rename_ident(&mut name);
+                        Operand::Variable(name)
+                    }
+                },
                 Val::V(val) => Operand::Immediate(*val),
                 Val::Lit(lit) => {
-                    let unit = DataUnit::StrLit(lit.clone());
-                    let temp_var = if let Some(temp_var) = self.inner.data.get(&unit) {
-                        temp_var.clone()
-                    } else {
-                        let new_temp = self.new_temp();
-                        self.inner.data.insert(unit, new_temp.clone());
-                        new_temp
-                    };
+                    let label = self.literal(lit);
                     let in_scope_temp = self.new_temp();
                     self.inner.units.push(CodeUnit::Operation {
                         op: Operation::AsRef,
                         lhs: Operand::Immediate(0), // random value. will be overwitten by load
-                        rhs: Operand::Variable(temp_var),
+                        rhs: Operand::Variable(label),
                         dest: Operand::Temp(in_scope_temp.clone()),
                     });
                     Operand::Temp(in_scope_temp)
                 }
                 Val::Malformed => panic!(),
             },
-            Expr::Op(lhs, op, rhs) => {
-                let lhs = self.lower_unit(lhs.as_ref());
-                let rhs = self.lower_unit(rhs.as_ref());
+            Expr::Array(..) => {
+                let value = eval_const_with(expr, &mut |name| match name {
+                    ConstRef::Global(name) => match self.global(name) {
+                        Some(Some(value)) => Ok(value.clone()),
+                        _ => Err(format!("{} is not a constant", name)),
+                    },
+                    ConstRef::SizeOf(name) => match self.structs.get(name) {
+                        Some(fields) => Ok(ConstValue::Int(fields.len() as i64 * 8)),
+                        None => Err(format!("no struct named {}", name)),
+                    },
+                });
+                let Ok(ConstValue::Array { init, len }) = value else {
+                    panic!("array lengths are checked to be constant");
+                };
+
+                let temp = self.new_temp();
+                let label = format!("__{}_arr{}", self.context_name, temp);
+                self.inner.arrays.push(ArrayIR {
+                    label: label.clone(),
+                    init,
+                    len,
+                });
+                self.inner.units.push(CodeUnit::Fill {
+                    label: label.clone(),
+                    init,
+                    len,
+                });
+
+                let ptr = self.new_temp();
+                self.inner.units.push(CodeUnit::Operation {
+                    op: Operation::AsRef,
+                    lhs: Operand::Immediate(0),
+                    rhs: Operand::Variable(label),
+                    dest: Operand::Temp(ptr.clone()),
+                });
+                Operand::Temp(ptr)
+            }
+            Expr::Field(ptr, field) => {
+                let addr = self.field_addr(ptr, field);
                 let res = self.new_temp();
                 self.inner.units.push(CodeUnit::Operation {
-                    op: *op,
-                    lhs,
-                    rhs,
+                    op: Operation::Load,
+                    lhs: Operand::Immediate(0),
+                    rhs: addr,
+                    dest: Operand::Temp(res.clone()),
+                });
+                Operand::Temp(res)
+            }
+            Expr::Load(ptr, width, extend) => {
+                let addr = self.lower_unit(ptr);
+                let res = self.new_temp();
+                self.inner.units.push(CodeUnit::Load {
+                    addr,
+                    width: *width,
+                    extend: *extend,
                     dest: Operand::Temp(res.clone()),
                 });
                 Operand::Temp(res)
             }
+            Expr::SizeOf(name) => Operand::Immediate(self.structs[name].len() as i64 * 8),
+            // `&p->field` is the address of the field
+            Expr::Op(_, Operation::AsRef, rhs) if let Expr::Field(ptr, field) = &rhs.inner => {
+                self.field_addr(ptr, field)
+            }
+            // bytes are loaded through `ptr<u8>`
+            Expr::Op(_, Operation::Load, ptr) if self.pointee(ptr) == Some(Type::U8) => {
+                let addr = self.lower_unit(ptr);
+                let res = self.new_temp();
+                self.inner.units.push(CodeUnit::Load {
+                    addr,
+                    width: Width::Byte,
+                    extend: Extend::Zero,
+                    dest: Operand::Temp(res.clone()),
+                });
+                Operand::Temp(res)
+            }
+            Expr::Op(lhs, op @ (Operation::LogicalAnd | Operation::LogicalOr), rhs) => {
+                self.lower_logical(lhs, *op, rhs)
+            }
+            Expr::Op(lhs, op, rhs) => self.lower_binary(lhs, *op, rhs),
             Expr::Malformed => panic!(),
         }
     }
 
     fn rename_lvalue(&mut self, lvalue: &mut LValue) {
         match lvalue {
+            LValue::Variable(var) if self.global(var).is_some() => {}
             LValue::Variable(var) => self.rename_ident(var),
             LValue::Deref(lvalue) => self.rename_lvalue(lvalue.as_mut()),
-            LValue::Malformed => panic!(),
+            LValue::Field(..) | LValue::Malformed => panic!(),
         }
     }
 
@@ -318,8 +750,13 @@ impl CodeBuilder {
 
 #[cfg(test)]
 mod tests {
+    use std::{fs, path::PathBuf};
+
     use super::*;
-    use crate::frontend::{ast::cfg::CfgEnv, get_ast};
+    use crate::{
+        backend::{CodegenOpts, asm_gen},
+        frontend::{ast::cfg::CfgEnv, get_ast},
+    };
 
     #[test]
     fn code() {
@@ -337,7 +774,7 @@ mod tests {
                 .next()
                 .map(|f| match f {
                     Item::Function(f) => Some(f),
-                    Item::Malformed => None,
+                    Item::Global(_) | Item::Struct(_) | Item::Malformed => None,
                 })
                 .unwrap()
                 .unwrap()
@@ -346,7 +783,7 @@ mod tests {
             "main".into(),
         );
         let code_true = CodeTree {
-            data: HashMap::new(),
+            arrays: Vec::new(),
             units: vec![
                 CodeUnit::Operation {
                     op: Operation::Mul,
@@ -410,4 +847,317 @@ mod tests {
 
         assert_eq!(code, code_true)
     }
+
+    #[test]
+    fn arrays() {
+        let s = "
+        const LEN = 4;
+        static table = [7; LEN * 2];
+        begin_def main;
+            buf = [0; LEN];
+            if buf; other = [1; 2];
+        end_def
+        ";
+        let ast = get_ast(s, &CfgEnv::default()).0;
+        let code = ProgramIR::build(&ast);
+
+        assert_eq!(
+            code.globals["table"].value,
+            Some(ConstValue::Array { init: 7, len: 8 })
+        );
+        let main = &code.functions["main"].body;
+        assert_eq!(
+            main.arrays
+                .iter()
+                .map(|array| (array.init, array.len))
+                .collect::<Vec<_>>(),
+            vec![(0, 4), (1, 2)]
+        );
+        assert!(main.units.iter().any(|unit| matches!(
+            unit,
+            CodeUnit::Operation { op: Operation::AsRef, rhs: Operand::Variable(label), .. }
+                if *label == main.arrays[0].label
+        )));
+    }
+
+    #[test]
+    fn array_refill() {
+        let s = "
+        begin_def main;
+            i = 0;
+            label again;
+            buf = [5; 4];
+            store8 buf, i;
+            i = i + 1;
+            if i < 2; goto again;
+        end_def
+        ";
+        let ast = get_ast(s, &CfgEnv::default()).0;
+        let code = ProgramIR::build(&ast);
+        let label = code.functions["main"].body.arrays[0].label.clone();
+        let path = std::env::temp_dir().join("mini_compiler_array_refill.asm");
+        asm_gen(code, &path, &CodegenOpts::default()).unwrap();
+        let asm = fs::read_to_string(&path).unwrap();
+
+        // both evaluations in the loop fill the buffer before taking its address
+        let start = asm.find("__main_var_again:").unwrap();
+        let body = &asm[start..asm.find("jmp __main_var_again").unwrap()];
+        let fill = body.find("rep stosq").unwrap();
+        assert!(body[..fill].contains(&format!("lea rdi, [{}]", label)));
+        assert!(body[fill..].contains(&format!("[{}]", label)));
+    }
+
+    #[test]
+    fn sized_memory() {
+        let s = "
+        begin_def main p;
+            c = loadi8(p + 1);
+            store16 p, load32(p);
+        end_def
+        ";
+        let ast = get_ast(s, &CfgEnv::default()).0;
+        let code = ProgramIR::build(&ast);
+        let units = &code.functions["main"].body.units;
+
+        assert!(matches!(
+            units[1],
+            CodeUnit::Load {
+                width: Width::Byte,
+                extend: Extend::Sign,
+                ..
+            }
+        ));
+        assert_eq!(
+            units[4..6],
+            [
+                CodeUnit::Load {
+                    addr: Operand::Variable("__main_var_p".into()),
+                    width: Width::Dword,
+                    extend: Extend::Zero,
+                    dest: Operand::Temp("_temp_2".into()),
+                },
+                CodeUnit::Store {
+                    addr: Operand::Variable("__main_var_p".into()),
+                    value: Operand::Temp("_temp_2".into()),
+                    width: Width::Word,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unsigned() {
+        let s = "
+        begin_def main a: u64, b: i64;
+            c = a > b;
+            c = b < 1;
+            c = a % 3;
+            c = b >>> 1;
+        end_def
+        ";
+        let ast = get_ast(s, &CfgEnv::default()).0;
+        let code = ProgramIR::build(&ast);
+        let ops = code.functions["main"]
+            .body
+            .units
+            .iter()
+            .filter_map(|unit| match unit {
+                CodeUnit::Operation { op, .. } => Some(*op),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            [
+                Operation::UGt,
+                Operation::Lt,
+                Operation::UMod,
+                Operation::Sar
+            ]
+        );
+    }
+
+    #[test]
+    fn short_circuit() {
+        let s = "
+        begin_def main a, b, c, d, e;
+            x = a > 0 && b > 0 && c > 0 && d > 0 && e > 0 && a < 9 && b < 9;
+            y = b == 0 || a / b > 1;
+        end_def
+        ";
+        let code = ProgramIR::build(&get_ast(s, &CfgEnv::default()).0);
+        // the division is only evaluated if b is not zero
+        assert!(
+            code.functions["main"]
+                .body
+                .units
+                .iter()
+                .any(|unit| matches!(
+                    unit,
+                    CodeUnit::Condition { then, .. } if matches!(
+                        &then[..],
+                        [CodeUnit::Operation { op: Operation::Div, .. }, ..]
+                    )
+                ))
+        );
+
+        let path = std::env::temp_dir().join("mini_compiler_short_circuit.asm");
+        asm_gen(code, &path, &CodegenOpts::default()).unwrap();
+        let asm = fs::read_to_string(&path).unwrap();
+        // the stack is balanced within every conditional block
+        for (start, _) in asm.match_indices("jz ") {
+            let label = asm[start + 3..].lines().next().unwrap();
+            let block = &asm[start..asm.find(&format!("\n{}:", label)).unwrap()];
+            let offset = block
+                .lines()
+                .map(|line| match line.trim().split_once(' ') {
+                    Some(("sub", args)) if args.starts_with("rsp, ") => args[5..].parse().unwrap(),
+                    Some(("add", args)) if args.starts_with("rsp, ") => {
+                        -args[5..].parse::<i64>().unwrap()
+                    }
+                    Some(("push", _)) => 8,
+                    Some(("pop", _)) => -8,
+                    _ => 0,
+                })
+                .sum::<i64>();
+            assert_eq!(offset, 0, "{}", block);
+        }
+    }
+
+    #[test]
+    fn u8_stores() {
+        let s = "
+        begin_def main a: u8;
+            c: u8 = 1;
+            f a : c;
+            c = a + 300;
+        end_def
+        ";
+        let ast = get_ast(s, &CfgEnv::default()).0;
+        let code = ProgramIR::build(&ast);
+        let masks = code.functions["main"]
+            .body
+            .units
+            .iter()
+            .filter(|unit| {
+                matches!(
+                    unit,
+                    CodeUnit::Operation {
+                        op: Operation::BitAND,
+                        rhs: Operand::Immediate(0xff),
+                        ..
+                    }
+                )
+            })
+            .count();
+        // the argument on entry, the call result and the sum. Literals are truncated when lowered
+        assert_eq!(masks, 3);
+    }
+
+    /// labels of the literals referenced by units
+    fn literal_uses<'a>(units: &'a [CodeUnit], uses: &mut Vec<&'a str>) {
+        for unit in units {
+            match unit {
+                CodeUnit::Operation {
+                    op: Operation::AsRef,
+                    rhs: Operand::Variable(label),
+                    ..
+                } if label.starts_with("__lit_") => uses.push(label),
+                CodeUnit::Condition { then, .. } => literal_uses(then, uses),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn literal_pool() {
+        let std_root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("lib/std");
+        let mut files = vec![std_root.clone()];
+        let mut sources = Vec::new();
+        while let Some(path) = files.pop() {
+            if path.is_dir() {
+                files.extend(
+                    fs::read_dir(path)
+                        .unwrap()
+                        .map(|entry| entry.unwrap().path()),
+                );
+            } else if path.extension().is_some_and(|ext| ext == "lang") {
+                sources.push(fs::read_to_string(path).unwrap());
+            }
+        }
+
+        let cfg_env = CfgEnv::default().populate(&["test".into()]);
+        let path = std::env::temp_dir().join("mini_compiler_literal_pool.asm");
+        let (mut pooled, mut per_function) = (0, 0);
+        for source in &sources {
+            let code = ProgramIR::build(&get_ast(source, &cfg_env).0);
+            let sizes = code
+                .literals
+                .iter()
+                .map(|(literal, label)| (label.clone(), literal.write_data().len() + 1))
+                .collect::<HashMap<_, _>>();
+
+            // before pooling, each function emitted the distinct literals it used,
+            // and each string constant its own copy
+            for func in code.functions.values() {
+                let mut uses = Vec::new();
+                literal_uses(&func.body.units, &mut uses);
+                uses.sort();
+                uses.dedup();
+                per_function += uses.into_iter().map(|label| sizes[label]).sum::<usize>();
+            }
+            per_function += code
+                .globals
+                .values()
+                .filter_map(|global| match &global.value {
+                    Some(ConstValue::Str(lit)) => Some(lit.len() + 1),
+                    _ => None,
+                })
+                .sum::<usize>();
+
+            asm_gen(code, &path, &CodegenOpts::default()).unwrap();
+            let emitted = fs::read_to_string(&path)
+                .unwrap()
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("__lit_")?.split_once(": db "))
+                .map(|(_, bytes)| bytes.split(", ").count())
+                .sum::<usize>();
+            assert_eq!(emitted, sizes.values().sum::<usize>());
+            pooled += emitted;
+        }
+        assert!(
+            pooled < per_function,
+            "pooled literals take {} bytes of .rodata, copies per function {} bytes",
+            pooled,
+            per_function
+        );
+    }
+
+    #[test]
+    fn pic() {
+        let s = "
+        extern_def puts;
+        extern_static counter;
+        public static total = 1;
+
+        link_attr section tests;
+        begin_def check;
+            counter = counter + 1;
+            puts counter;
+            total = 2;
+        end_def
+        ";
+        let code = ProgramIR::build(&get_ast(s, &CfgEnv::default()).0);
+        let path = std::env::temp_dir().join("mini_compiler_pic.asm");
+        asm_gen(code, &path, &CodegenOpts::default().with_pic(true)).unwrap();
+        let asm = fs::read_to_string(&path).unwrap();
+
+        assert!(asm.contains("call puts wrt ..plt"));
+        assert!(asm.contains("mov rax, [rel counter wrt ..gotpcrel]"));
+        assert!(!asm.contains("[rel counter]"));
+        assert!(asm.contains("global total:data 8\n"));
+        assert!(asm.contains("mov rdx, [rel total wrt ..gotpcrel]"));
+        assert!(!asm.contains("[rel total]"));
+        assert!(asm.contains("section .data.rel.ro"));
+    }
 }
@@ -1,5 +1,6 @@
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{HashMap, HashSet},
     fmt::{Arguments, Display},
     fs::File,
     io::Write,
@@ -8,19 +9,35 @@ use std::{
 };
 
 use crate::{
-    backend::codegen::{LValue, ProgramIR},
-    frontend::ast::{LinkAttr, LinkMeta, Operation, is_builtin_func},
+    backend::{
+        CodegenOpts,
+        codegen::{LValue, ProgramIR},
+        mangle::{demangle, mangle},
+    },
+    frontend::ast::{
+        Abi, ConstValue, Extend, GlobalKind, LinkAttr, LinkMeta, Operation, Width, is_builtin_func,
+    },
     print_if,
 };
 
-use super::{CodeUnit, Operand};
+use super::{CodeUnit, DataUnit, Operand};
 
 pub struct AsmWriter {
     fh: File,
+    pic: bool,
+    /// symbols which may be resolved outside of this object, i.e. extern or public functions and statics
+    non_local: HashSet<String>,
+    /// calling conventions of all known functions
+    abis: HashMap<String, Abi>,
+    /// mangled symbols of private functions
+    symbols: HashMap<String, String>,
+    demangle: bool,
+    /// names of all globals, which are not allocated as function locals
+    globals: HashSet<String>,
 }
 
 impl AsmWriter {
-    pub fn new(path: &Path, _code: &ProgramIR) -> Self {
+    pub fn new(path: &Path, code: &ProgramIR, opts: &CodegenOpts) -> Self {
         let mut file = File::create(path).unwrap();
         print_if!(1, "writing asm code to {}", path.display());
 
@@ -28,20 +45,57 @@ impl AsmWriter {
 
         writeln!(file, "\nsection .text\n\textern printf\n\textern exit\n").unwrap();
 
-        Self { fh: file }
+        let non_local = code
+            .functions
+            .values()
+            .filter(|func| func.link_attr.external || func.link_attr.is_public)
+            .map(|func| func.name.clone())
+            .chain(
+                code.globals
+                    .iter()
+                    .filter(|(_, global)| global.link_attr.external || global.link_attr.is_public)
+                    .map(|(name, _)| name.clone()),
+            )
+            .collect();
+
+        let abis = code
+            .functions
+            .values()
+            .map(|func| (func.name.clone(), func.link_attr.abi))
+            .collect();
+
+        let symbols = code
+            .functions
+            .values()
+            .filter(|func| {
+                !func.link_attr.external
+                    && !func.link_attr.is_public
+                    && !is_builtin_func(&func.name)
+            })
+            .map(|func| (func.name.clone(), mangle(&opts.module, &func.name)))
+            .collect();
+
+        Self {
+            fh: file,
+            pic: opts.pic,
+            non_local,
+            abis,
+            symbols,
+            demangle: opts.demangle,
+            globals: code.globals.keys().cloned().collect(),
+        }
     }
 
     pub fn write(mut self, code: &ProgramIR) {
+        self.write_literals(code);
+        self.write_globals(code);
+
         let mut all_vars = Vars::default();
         let mut temps = TempVarStack::default();
         for (name, func) in code.functions.iter() {
             let stack_size_at_last_cleanup = temps.stack_pushes; // 0
-            // TODO deduplicate the emitted section data
-            if !func.body.data.is_empty() {
-                writeln!(self.fh, "section .data").unwrap();
-                for (payload, ident) in func.body.data.iter() {
-                    writeln!(self.fh, "\t{}: db `{}`, 0", ident, payload.write_data()).unwrap();
-                }
+            for array in &func.body.arrays {
+                self.write_array(&array.label, array.init, array.len);
             }
 
             let LinkAttr {
@@ -49,6 +103,7 @@ impl AsmWriter {
                 external,
                 is_public,
                 meta,
+                abi: _,
             } = &func.link_attr;
 
             match meta {
@@ -56,12 +111,17 @@ impl AsmWriter {
                     if section != ".text" {
                         writeln!(self.fh, "section {} progbits alloc write", section).unwrap();
                         writeln!(self.fh, "{}_ptr:", name).unwrap();
-                        writeln!(self.fh, "\tdq {}", name).unwrap();
+                        writeln!(self.fh, "\tdq {}", self.symbol(name)).unwrap();
                     }
                 }
                 LinkMeta::WithMeta => {
                     if section != ".text" {
-                        writeln!(self.fh, "section .rodata").unwrap();
+                        // the metadata contains absolute addresses, which need to be relocated at load time in pic mode
+                        if self.pic {
+                            writeln!(self.fh, "section .data.rel.ro progbits alloc write").unwrap();
+                        } else {
+                            writeln!(self.fh, "section .rodata").unwrap();
+                        }
 
                         // emit a block of metadata. this contains the len (in bytes)
                         // currently it is always of length sizeof(len) == 8 + 8 + 8 = 24 bytes
@@ -72,7 +132,7 @@ impl AsmWriter {
 
                         writeln!(self.fh, "__meta_{}:", name).unwrap();
                         writeln!(self.fh, "\tdq {}", len).unwrap();
-                        writeln!(self.fh, "\tdq {}", name).unwrap();
+                        writeln!(self.fh, "\tdq {}", self.symbol(name)).unwrap();
                         writeln!(self.fh, "\tdq __meta_str_{}", name).unwrap();
 
                         // ptr to meta block in section <section>
@@ -92,10 +152,14 @@ impl AsmWriter {
                 continue;
             }
 
-            writeln!(self.fh, "{}:", name).unwrap();
+            if self.demangle && self.symbols.contains_key(name) {
+                writeln!(self.fh, "; {}", self.qualified(name)).unwrap();
+            }
+            writeln!(self.fh, "{}:", self.symbol(name)).unwrap();
             for (arg, reg) in func.args.iter().zip(CALL_ORDER) {
                 all_vars.add(arg.clone());
-                self.write_in_fn(format_args!("mov qword [{}], {}", arg, reg));
+                let loc = self.mem(arg, Reg::RAX);
+                self.write_in_fn(format_args!("mov qword {}, {}", loc, reg));
             }
 
             // restore satck alignment, which is currently off due to call of function
@@ -141,6 +205,91 @@ impl AsmWriter {
         .unwrap();
     }
 
+    /// the literal pool is emitted once, read only
+    fn write_literals(&mut self, code: &ProgramIR) {
+        if code.literals.is_empty() {
+            return;
+        }
+        writeln!(self.fh, "section .rodata").unwrap();
+        for (literal, label) in &code.literals {
+            writeln!(
+                self.fh,
+                "\t{}: db {}",
+                label,
+                byte_list(literal.write_data())
+            )
+            .unwrap();
+        }
+    }
+
+    /// statics are emitted to .data or .bss, constants to .rodata, unless `link_attr` sets a section.
+    /// Every global is a qword, string initializers are stored as a pointer to the literal
+    fn write_globals(&mut self, code: &ProgramIR) {
+        for (name, global) in &code.globals {
+            let LinkAttr {
+                external,
+                is_public,
+                section: link_section,
+                ..
+            } = &global.link_attr;
+
+            if *external {
+                writeln!(self.fh, "extern {}", name).unwrap();
+                continue;
+            }
+            if *is_public {
+                writeln!(self.fh, "global {}:data 8", name).unwrap();
+            }
+
+            let value = match &global.value {
+                Some(ConstValue::Array { init, len }) => {
+                    let label = format!("__arr_{}", name);
+                    self.write_array(&label, *init, *len);
+                    label
+                }
+                Some(ConstValue::Str(lit)) => code.literals[&DataUnit::StrLit(lit.clone())].clone(),
+                Some(ConstValue::Int(value)) => value.to_string(),
+                None => "0".into(),
+            };
+
+            if link_section != ".text" {
+                writeln!(
+                    self.fh,
+                    "section {} progbits alloc write\n{}: dq {}",
+                    link_section, name, value
+                )
+                .unwrap();
+                continue;
+            }
+            let section = match (global.kind, &global.value) {
+                (GlobalKind::Const, Some(ConstValue::Str(_))) if self.pic => {
+                    ".data.rel.ro progbits alloc write"
+                }
+                (GlobalKind::Const, _) => ".rodata",
+                (GlobalKind::Static, Some(ConstValue::Int(0)) | None) => {
+                    writeln!(self.fh, "section .bss\n{}: resq 1", name).unwrap();
+                    continue;
+                }
+                (GlobalKind::Static, _) => ".data",
+            };
+            writeln!(self.fh, "section {}\n{}: dq {}", section, name, value).unwrap();
+        }
+    }
+
+    /// arrays are 16 byte aligned. Zero initialized ones are placed in .bss
+    fn write_array(&mut self, label: &str, init: i64, len: usize) {
+        if init == 0 {
+            writeln!(self.fh, "section .bss\nalignb 16\n{}: resq {}", label, len).unwrap();
+        } else {
+            writeln!(
+                self.fh,
+                "section .data\nalign 16\n{}: times {} dq {}",
+                label, len, init
+            )
+            .unwrap();
+        }
+    }
+
     fn write_unit(&mut self, unit: &CodeUnit, all_vars: &mut Vars, temps: &mut TempVarStack) {
         match unit {
             CodeUnit::FuncCall { name, args, dest } => {
@@ -161,14 +310,41 @@ impl AsmWriter {
                             saved_regs.push(reg);
                             temps.inc_stack(8);
                         }
+                        self.load(reg, op, all_vars, temps);
+                    }
+
+                    // C functions expect a 16 byte aligned stack. The stack is aligned at the start of each function
+                    let abi = self.abis.get(name).copied().unwrap_or_default();
+                    let realign =
+                        matches!(abi, Abi::C { .. }) && !temps.stack_pushes.is_multiple_of(16);
+                    if realign {
+                        self.write_in_fn(format_args!("sub rsp, 8"));
+                    }
+                    if let Abi::C { variadic: true, .. } = abi {
+                        // no vector registers are used for arguments
+                        self.write_in_fn(format_args!("xor eax, eax"));
+                    }
+
+                    if self.demangle && self.symbols.contains_key(name) {
                         self.write_in_fn(format_args!(
-                            "mov {}, {}",
-                            reg,
-                            self.get_var_str(op, all_vars, temps)
+                            "call {} ; {}",
+                            func_name,
+                            self.qualified(name)
                         ));
+                    } else {
+                        self.write_in_fn(format_args!("call {}", func_name));
                     }
 
-                    self.write_in_fn(format_args!("call {}", func_name));
+                    if realign {
+                        self.write_in_fn(format_args!("add rsp, 8"));
+                    }
+                    if let Abi::C {
+                        ret: Some((width, extend)),
+                        ..
+                    } = abi
+                    {
+                        self.extend_ret(width, extend);
+                    }
 
                     temps.dec_stack(8 * saved_regs.len());
                     for reg in saved_regs.into_iter().rev() {
@@ -184,19 +360,14 @@ impl AsmWriter {
             CodeUnit::Operation { op, lhs, rhs, dest } => {
                 match dest {
                     Operand::Variable(name) => {
-                        self.write_in_fn(format_args!(
-                            "mov rax, {}",
-                            self.get_var_str(lhs, all_vars, temps)
-                        ));
+                        self.load(Reg::RAX, lhs, all_vars, temps);
                         self.write_op(op, rhs, temps, all_vars);
 
-                        self.write_in_fn(format_args!("mov qword [{}], rax", name));
+                        let loc = self.mem(name, Reg::RDX);
+                        self.write_in_fn(format_args!("mov qword {}, rax", loc));
                     }
                     Operand::Temp(name) => {
-                        self.write_in_fn(format_args!(
-                            "mov rax, {}",
-                            self.get_var_str(lhs, all_vars, temps)
-                        ));
+                        self.load(Reg::RAX, lhs, all_vars, temps);
                         self.write_op(op, rhs, temps, all_vars);
 
                         let addr = self.get_or_init_temp(name, temps);
@@ -207,7 +378,9 @@ impl AsmWriter {
             }
             CodeUnit::Assignment { name, value } => {
                 let save_rcx = if let LValue::Variable(var_name) = name {
-                    all_vars.add(var_name.clone());
+                    if !self.globals.contains(var_name) {
+                        all_vars.add(var_name.clone());
+                    }
                     true
                 } else {
                     false
@@ -222,41 +395,160 @@ impl AsmWriter {
                 }
 
                 let resolved = self.resolve_lvalue(name);
-                self.write_in_fn(format_args!("mov qword [{}], rax", resolved));
+                self.write_in_fn(format_args!("mov qword {}, rax", resolved));
 
                 if save_rcx {
                     self.write_in_fn(format_args!("pop rcx"));
                     temps.dec_stack(8);
                 }
             }
+            CodeUnit::Load {
+                addr,
+                width,
+                extend,
+                dest,
+            } => {
+                self.load(Reg::RAX, addr, all_vars, temps);
+                match (width, extend) {
+                    (Width::Qword, _) => self.write_in_fn(format_args!("mov rax, [rax]")),
+                    // writing eax zero extends into rax
+                    (Width::Dword, Extend::Zero) => {
+                        self.write_in_fn(format_args!("mov eax, dword [rax]"))
+                    }
+                    (Width::Dword, Extend::Sign) => {
+                        self.write_in_fn(format_args!("movsxd rax, dword [rax]"))
+                    }
+                    (width, Extend::Zero) => {
+                        self.write_in_fn(format_args!("movzx rax, {} [rax]", mem_size(*width)))
+                    }
+                    (width, Extend::Sign) => {
+                        self.write_in_fn(format_args!("movsx rax, {} [rax]", mem_size(*width)))
+                    }
+                }
+                let Operand::Temp(dest) = dest else {
+                    panic!("loads are always assigned to temps");
+                };
+                let dest = self.get_or_init_temp(dest, temps);
+                self.write_in_fn(format_args!("mov {}, rax", dest));
+            }
+            CodeUnit::Store { addr, value, width } => {
+                let rhs = self.get_var_from_reg(value, all_vars, temps);
+                self.write_in_fn(format_args!("mov rax, {}", rhs));
+                // rdx is not used for temps
+                self.load(Reg::RDX, addr, all_vars, temps);
+                let src = match width {
+                    Width::Byte => "al",
+                    Width::Word => "ax",
+                    Width::Dword => "eax",
+                    Width::Qword => "rax",
+                };
+                self.write_in_fn(format_args!("mov {} [rdx], {}", mem_size(*width), src));
+            }
             CodeUnit::Condition { eval, then, label } => {
                 let eval_position = self.get_var_from_reg(eval, all_vars, temps);
                 self.write_in_fn(format_args!("test {}, {}", eval_position, eval_position));
                 self.write_in_fn(format_args!("jz {}", label));
+                let stack_size_before = temps.stack_pushes;
                 for unit in then {
                     self.write_unit(unit, all_vars, temps);
                 }
+                // temps spilled within the block are released at its end, as the block may be skipped
+                let spilled = temps.stack_pushes - stack_size_before;
+                if spilled > 0 {
+                    self.write_in_fn(format_args!("add rsp, {}", spilled));
+                    temps.dec_stack(spilled);
+                }
                 writeln!(self.fh, "{}:", label).unwrap();
             }
+            CodeUnit::Fill { label, init, len } => {
+                // rdi and rcx may hold temps, rax is free
+                let saved = [Reg::RDI, Reg::RCX]
+                    .into_iter()
+                    .filter(|reg| USAGE[*reg as usize].load(Ordering::Relaxed))
+                    .collect::<Vec<_>>();
+                for reg in &saved {
+                    self.write_in_fn(format_args!("push {}", reg));
+                    temps.inc_stack(8);
+                }
+                self.write_in_fn(format_args!("lea rdi, [{}]", label));
+                self.write_in_fn(format_args!("mov rcx, {}", len));
+                self.write_in_fn(format_args!("mov rax, {}", init));
+                self.write_in_fn(format_args!("rep stosq"));
+                for reg in saved.iter().rev() {
+                    self.write_in_fn(format_args!("pop {}", reg));
+                    temps.dec_stack(8);
+                }
+            }
             CodeUnit::Cleanup => {}
         }
     }
 
-    /// assume rcx unused
+    /// assume rcx and rdx unused. Returns the memory operand of the lvalue
     fn resolve_lvalue(&mut self, value: &LValue) -> String {
         match value {
-            LValue::Variable(var) => var.clone(),
+            LValue::Variable(var) => self.mem(var, Reg::RDX),
             LValue::Deref(lvalue) => {
                 let inner = self.resolve_lvalue(lvalue);
-                self.write_in_fn(format_args!("mov rcx, [{}]", inner));
-                "rcx".into()
+                self.write_in_fn(format_args!("mov rcx, {}", inner));
+                "[rcx]".into()
             }
-            LValue::Malformed => panic!(),
+            LValue::Field(..) | LValue::Malformed => panic!(),
+        }
+    }
+
+    /// memory operand of a symbol. Data which may live in another object is addressed
+    /// through its GOT entry, which is loaded into scratch
+    fn mem(&mut self, symbol: &str, scratch: Reg) -> String {
+        if self.pic && self.non_local.contains(symbol) {
+            self.write_in_fn(format_args!(
+                "mov {}, [rel {} wrt ..gotpcrel]",
+                scratch, symbol
+            ));
+            format!("[{}]", scratch)
+        } else if self.pic {
+            format!("[rel {}]", symbol)
+        } else {
+            format!("[{}]", symbol)
+        }
+    }
+
+    /// widens the return value of a C function in rax to a qword
+    fn extend_ret(&mut self, width: Width, extend: Extend) {
+        match (width, extend) {
+            (Width::Qword, _) => {}
+            // writing eax zero extends into rax
+            (Width::Dword, Extend::Zero) => self.write_in_fn(format_args!("mov eax, eax")),
+            (Width::Dword, Extend::Sign) => self.write_in_fn(format_args!("movsxd rax, eax")),
+            (Width::Word, Extend::Zero) => self.write_in_fn(format_args!("movzx eax, ax")),
+            (Width::Word, Extend::Sign) => self.write_in_fn(format_args!("movsx rax, ax")),
+            (Width::Byte, Extend::Zero) => self.write_in_fn(format_args!("movzx eax, al")),
+            (Width::Byte, Extend::Sign) => self.write_in_fn(format_args!("movsx rax, al")),
+        }
+    }
+
+    fn get_func_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        // functions which may live in another object need to be called through the PLT
+        if self.pic && self.non_local.contains(name) {
+            format!("{} wrt ..plt", name).into()
+        } else {
+            self.symbol(name)
+        }
+    }
+
+    /// the emitted symbol of a function
+    fn symbol<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self.symbols.get(name) {
+            Some(symbol) => symbol.clone().into(),
+            None => name.into(),
         }
     }
 
-    fn get_func_name<'a>(&self, name: &'a str) -> &'a str {
-        name
+    /// the module qualified path of a private function
+    fn qualified(&self, name: &str) -> String {
+        self.symbols
+            .get(name)
+            .and_then(|symbol| demangle(symbol))
+            .unwrap_or_else(|| name.to_string())
     }
 
     // TODO use _ret for return values in addr_of, ...
@@ -271,10 +563,7 @@ impl AsmWriter {
         match name {
             "return" => {
                 if let Some(ret) = args.first() {
-                    self.write_in_fn(format_args!(
-                        "mov rax, {}",
-                        self.get_var_str(ret, vars, temps)
-                    ));
+                    self.load(Reg::RAX, ret, vars, temps);
                 }
                 // add back the previously substracted 8 bytes for return
                 self.write_in_fn(format_args!("add rsp, 8"));
@@ -296,7 +585,12 @@ impl AsmWriter {
         temps: &mut TempVarStack,
     ) {
         if let Some(Operand::Variable(ident)) = args.first() {
-            self.write_in_fn(format_args!("lea rax, [rel {}]", ident));
+            if self.pic && self.non_local.contains(ident) {
+                // the address of a symbol in another object must be loaded from the GOT
+                self.write_in_fn(format_args!("mov rax, [rel {} wrt ..gotpcrel]", ident));
+            } else {
+                self.write_in_fn(format_args!("lea rax, [rel {}]", self.symbol(ident)));
+            }
         }
 
         if let Some(Operand::Temp(dest)) = ret {
@@ -326,11 +620,23 @@ impl AsmWriter {
         }
     }
 
-    /// returns the location of the operand
-    fn get_var_str(&self, v: &Operand, _vars: &Vars, temps: &TempVarStack) -> String {
+    /// moves the operand to reg
+    fn load(&mut self, reg: Reg, v: &Operand, vars: &Vars, temps: &TempVarStack) {
+        let src = self.get_var_str(v, vars, temps, reg);
+        self.write_in_fn(format_args!("mov {}, {}", reg, src));
+    }
+
+    /// returns the location of the operand. scratch may be clobbered to address it
+    fn get_var_str(
+        &mut self,
+        v: &Operand,
+        _vars: &Vars,
+        temps: &TempVarStack,
+        scratch: Reg,
+    ) -> String {
         match v {
             Operand::Immediate(val) => format!("{}", val),
-            Operand::Variable(name) => format!("[{}]", name),
+            Operand::Variable(name) => self.mem(name, scratch),
             Operand::Temp(name) => {
                 let Some(loc) = temps.get(name) else {
                     panic!("temp referenced but not initialized: {}", name)
@@ -343,6 +649,19 @@ impl AsmWriter {
         }
     }
 
+    /// returns the location of the operand for use as the source of an arithmetic instruction.
+    /// Immediates, which do not fit a sign extended dword, are moved to rdx, which may also be
+    /// clobbered to address a variable
+    fn get_alu_operand(&mut self, v: &Operand, vars: &Vars, temps: &TempVarStack) -> String {
+        match v {
+            Operand::Immediate(val) if i32::try_from(*val).is_err() => {
+                self.write_in_fn(format_args!("mov rdx, {}", val));
+                "rdx".to_string()
+            }
+            v => self.get_var_str(v, vars, temps, Reg::RDX),
+        }
+    }
+
     /// returns the location of the operand. This location will be a register
     fn get_var_from_reg(&mut self, v: &Operand, _vars: &Vars, temps: &TempVarStack) -> String {
         // assuming rax is usable
@@ -352,7 +671,8 @@ impl AsmWriter {
                 "rax".to_string()
             }
             Operand::Variable(name) => {
-                self.write_in_fn(format_args!("mov rax, qword [{}]", name));
+                let loc = self.mem(name, Reg::RAX);
+                self.write_in_fn(format_args!("mov rax, qword {}", loc));
                 "rax".to_string()
             }
             Operand::Temp(name) => {
@@ -379,73 +699,73 @@ impl AsmWriter {
             Operation::BitAND => "and",
             Operation::BitOR => "or",
             Operation::BitXOR => "xor",
+            Operation::Neg | Operation::BitNot => {
+                // ignore lhs
+                self.load(Reg::RAX, rhs, vars, temps);
+                let op = if *op == Operation::Neg { "neg" } else { "not" };
+                self.write_in_fn(format_args!("{} rax", op));
+                return;
+            }
             Operation::Not => {
                 // ignore lhs
-                self.write_in_fn(format_args!(
-                    "mov rax, {}",
-                    self.get_var_str(rhs, vars, temps)
-                ));
+                self.load(Reg::RAX, rhs, vars, temps);
                 self.write_in_fn(format_args!("test rax, rax"));
                 self.write_in_fn(format_args!("sete al"));
                 self.write_in_fn(format_args!("movzx rax, al"));
                 return;
             }
-            Operation::Gt => {
-                self.write_in_fn(format_args!(
-                    "cmp rax, {}",
-                    self.get_var_str(rhs, vars, temps)
-                ));
-                self.write_in_fn(format_args!("setg al"));
-                self.write_in_fn(format_args!("movzx rax, al"));
-                return;
-            }
-            Operation::Lt => {
-                self.write_in_fn(format_args!(
-                    "cmp rax, {}",
-                    self.get_var_str(rhs, vars, temps)
-                ));
-                self.write_in_fn(format_args!("setl al"));
-                self.write_in_fn(format_args!("movzx rax, al"));
-                return;
-            }
-            Operation::EqEq => {
-                self.write_in_fn(format_args!(
-                    "cmp rax, {}",
-                    self.get_var_str(rhs, vars, temps)
-                ));
-                self.write_in_fn(format_args!("sete al"));
+            Operation::Gt
+            | Operation::Lt
+            | Operation::Ge
+            | Operation::Le
+            | Operation::UGt
+            | Operation::ULt
+            | Operation::UGe
+            | Operation::ULe
+            | Operation::EqEq
+            | Operation::NEq => {
+                let set = match op {
+                    Operation::Gt => "setg",
+                    Operation::Lt => "setl",
+                    Operation::Ge => "setge",
+                    Operation::Le => "setle",
+                    Operation::UGt => "seta",
+                    Operation::ULt => "setb",
+                    Operation::UGe => "setae",
+                    Operation::ULe => "setbe",
+                    Operation::EqEq => "sete",
+                    _ => "setne",
+                };
+                let rhs = self.get_alu_operand(rhs, vars, temps);
+                self.write_in_fn(format_args!("cmp rax, {}", rhs));
+                self.write_in_fn(format_args!("{} al", set));
                 self.write_in_fn(format_args!("movzx rax, al"));
                 return;
             }
-            Operation::NEq => {
-                self.write_in_fn(format_args!(
-                    "cmp rax, {}",
-                    self.get_var_str(rhs, vars, temps)
-                ));
-                self.write_in_fn(format_args!("setne al"));
-                self.write_in_fn(format_args!("movzx rax, al"));
-                return;
+            Operation::LogicalAnd | Operation::LogicalOr => {
+                unreachable!("logical operators are lowered to conditions")
             }
             Operation::Load => {
-                let var_location = self.get_var_str(rhs, vars, temps);
-                // double deref, as var_location may be a ptr
-                self.write_in_fn(format_args!("mov rax, {}", var_location));
+                // double deref, as the operand may be a ptr
+                self.load(Reg::RAX, rhs, vars, temps);
                 self.write_in_fn(format_args!("mov rax, [rax]"));
                 return;
             }
-            Operation::Div | Operation::Mod => {
-                // sign extend RDX:RAX
-                self.write_in_fn(format_args!("cqo"));
+            Operation::Div | Operation::Mod | Operation::UDiv | Operation::UMod => {
+                let signed = matches!(op, Operation::Div | Operation::Mod);
+                if signed {
+                    // sign extend RDX:RAX
+                    self.write_in_fn(format_args!("cqo"));
+                } else {
+                    self.write_in_fn(format_args!("xor rdx, rdx"));
+                }
                 if USAGE[Reg::RCX as usize].load(Ordering::Relaxed) {
                     self.write_in_fn(format_args!("push rcx"));
                     temps.inc_stack(8);
                 }
-                self.write_in_fn(format_args!(
-                    "mov rcx, {}",
-                    self.get_var_str(rhs, vars, temps)
-                ));
-                self.write_in_fn(format_args!("idiv rcx"));
-                if *op == Operation::Mod {
+                self.load(Reg::RCX, rhs, vars, temps);
+                self.write_in_fn(format_args!("{} rcx", if signed { "idiv" } else { "div" }));
+                if matches!(op, Operation::Mod | Operation::UMod) {
                     self.write_in_fn(format_args!("mov rax, rdx"));
                 }
                 if USAGE[Reg::RCX as usize].load(Ordering::Relaxed) {
@@ -454,18 +774,16 @@ impl AsmWriter {
                 }
                 return;
             }
-            Operation::Shr => {
+            Operation::Shr | Operation::Sar => {
                 let rcx_usage = USAGE[Reg::RCX as usize].load(Ordering::Relaxed);
                 if rcx_usage {
                     self.write_in_fn(format_args!("push rcx"));
                     temps.inc_stack(8);
                 }
 
-                self.write_in_fn(format_args!(
-                    "mov rcx, {}",
-                    self.get_var_str(rhs, vars, temps)
-                ));
-                self.write_in_fn(format_args!("shr rax, cl"));
+                self.load(Reg::RCX, rhs, vars, temps);
+                let shift = if *op == Operation::Sar { "sar" } else { "shr" };
+                self.write_in_fn(format_args!("{} rax, cl", shift));
 
                 if rcx_usage {
                     self.write_in_fn(format_args!("pop rcx"));
@@ -480,10 +798,7 @@ impl AsmWriter {
                     temps.inc_stack(8);
                 }
 
-                self.write_in_fn(format_args!(
-                    "mov rcx, {}",
-                    self.get_var_str(rhs, vars, temps)
-                ));
+                self.load(Reg::RCX, rhs, vars, temps);
                 self.write_in_fn(format_args!("shl rax, cl"));
 
                 if rcx_usage {
@@ -494,13 +809,14 @@ impl AsmWriter {
             }
             Operation::AsRef => {
                 let addr = match rhs {
-                    Operand::Immediate(val) => {
+                    Operand::Immediate(_) => {
+                        let val = self.get_alu_operand(rhs, vars, temps);
                         self.write_in_fn(format_args!("sub rsp, 8"));
                         self.write_in_fn(format_args!("mov qword [rsp], {}", val));
                         temps.inc_stack(8);
-                        "rsp"
+                        "[rsp]".to_string()
                     }
-                    Operand::Variable(var) => var,
+                    Operand::Variable(var) => self.mem(var, Reg::RAX),
                     Operand::Temp(name) => {
                         let Some(loc) = temps.get(name) else {
                             panic!("temp referenced but not initialized: {}", name)
@@ -509,22 +825,19 @@ impl AsmWriter {
                             Location::Reg(reg) => {
                                 self.write_in_fn(format_args!("push {}", reg));
                                 temps.inc_stack(8);
-                                "rsp"
+                                "[rsp]".to_string()
                             }
-                            Location::Stack(s) => &format!("rsp + {}", s),
+                            Location::Stack(s) => format!("[rsp + {}]", s),
                         }
                     }
                 };
-                self.write_in_fn(format_args!("lea rax, [{}]", addr));
+                self.write_in_fn(format_args!("lea rax, {}", addr));
                 return;
             }
             Operation::Malformed => return,
         };
-        self.write_in_fn(format_args!(
-            "{} rax, {}",
-            op_str,
-            self.get_var_str(rhs, vars, temps)
-        ));
+        let rhs = self.get_alu_operand(rhs, vars, temps);
+        self.write_in_fn(format_args!("{} rax, {}", op_str, rhs));
     }
 
     fn write_in_fn(&mut self, line: Arguments) {
@@ -532,6 +845,16 @@ impl AsmWriter {
     }
 }
 
+/// the operands of a `db` emitting a nul terminated string of bytes
+fn byte_list(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .chain([&0])
+        .map(u8::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[derive(Default, Debug)]
 struct TempVarStack {
     inner: HashMap<String, Location>,
@@ -601,6 +924,16 @@ impl TempVarStack {
     }
 }
 
+/// nasm size specifier of a memory operand
+fn mem_size(width: Width) -> &'static str {
+    match width {
+        Width::Byte => "byte",
+        Width::Word => "word",
+        Width::Dword => "dword",
+        Width::Qword => "qword",
+    }
+}
+
 // rax + rdx are used for calculations
 #[allow(clippy::upper_case_acronyms, unused)]
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -0,0 +1,377 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::frontend::ast::{Abi, Ast, Expr, Function, Item, LinkMeta, Operation, Val};
+
+/// Output format of the generated documentation
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    Html,
+    Markdown,
+}
+
+impl DocFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+/// The documented functions of a single module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleDoc {
+    pub module: Vec<String>,
+    /// public functions defined in the module
+    pub funcs: Vec<FuncDoc>,
+    /// functions declared with `extern_def`
+    pub externs: Vec<FuncDoc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuncDoc {
+    pub name: String,
+    /// `begin_def` or `extern_def` line of the function
+    pub signature: String,
+    /// non default `link_attr` lines
+    pub link_attrs: Vec<String>,
+    pub cfg: Option<String>,
+    pub doc: Option<String>,
+}
+
+impl ModuleDoc {
+    /// collects the public and external functions of ast. Imported declarations are skipped
+    pub fn collect(module: Vec<String>, ast: &Ast) -> Self {
+        let mut zelf = Self {
+            module,
+            funcs: Vec::new(),
+            externs: Vec::new(),
+        };
+        for item in ast.funcs() {
+            let Item::Function(func) = item else {
+                continue;
+            };
+            if func.body.is_some() && func.link_attr.is_public {
+                zelf.funcs.push(FuncDoc::of(func));
+            } else if func.link_attr.external {
+                zelf.externs.push(FuncDoc::of(func));
+            }
+        }
+        zelf
+    }
+
+    /// name of the page of this module
+    pub fn page(&self, format: DocFormat) -> String {
+        format!("{}.{}", self.module.join("."), format.extension())
+    }
+
+    fn title(&self) -> String {
+        self.module.join("::")
+    }
+}
+
+impl FuncDoc {
+    pub fn of(func: &Function) -> Self {
+        let args = func
+            .args
+            .iter()
+            .zip(&func.arg_types)
+            .map(|(arg, ty)| match ty {
+                Some(ty) => format!("{}: {}", arg, ty),
+                None => arg.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut signature = match (func.body.is_some(), func.link_attr.is_public) {
+            (false, _) => "extern_def ".to_string(),
+            (true, true) => "public begin_def ".to_string(),
+            (true, false) => "begin_def ".to_string(),
+        };
+        signature.push_str(&func.name);
+        if !args.is_empty() {
+            signature.push(' ');
+            signature.push_str(&args);
+        }
+        if let Some(ret) = &func.ret {
+            write!(signature, " -> {}", ret).unwrap();
+        }
+        signature.push(';');
+
+        let attr = &func.link_attr;
+        let mut link_attrs = Vec::new();
+        match attr.meta {
+            LinkMeta::Raw => link_attrs.push(format!("link_attr raw section {};", attr.section)),
+            LinkMeta::WithMeta if attr.section != ".text" => {
+                link_attrs.push(format!("link_attr section {};", attr.section))
+            }
+            LinkMeta::WithMeta => {}
+        }
+        if let Abi::C { variadic, .. } = attr.abi {
+            link_attrs.push(format!(
+                "link_attr abi c;{}",
+                if variadic { " (variadic)" } else { "" }
+            ));
+        }
+
+        Self {
+            name: func.name.clone(),
+            signature,
+            link_attrs,
+            cfg: func.cfg.as_ref().map(|cfg| cfg_string(cfg)),
+            doc: func.doc.clone(),
+        }
+    }
+}
+
+/// renders a cfg expression the way it is written
+fn cfg_string(expr: &Expr) -> String {
+    let operand = |expr: &Expr| match expr {
+        Expr::Op(_, op, _) if *op != Operation::Not => format!("({})", cfg_string(expr)),
+        expr => cfg_string(expr),
+    };
+    match expr {
+        Expr::Val(Val::Var(flag)) => flag.clone(),
+        Expr::Val(Val::Lit(spec)) => format!("{:?}", String::from_utf8_lossy(spec)),
+        Expr::Val(Val::V(v)) => v.to_string(),
+        Expr::Op(_, Operation::Not, rhs) => format!("!{}", operand(rhs)),
+        Expr::Op(lhs, op, rhs) => format!("{} {} {}", operand(lhs), op, operand(rhs)),
+        _ => "malformed".into(),
+    }
+}
+
+/// Documentation of several modules, in which external declarations link to their definition
+pub struct Docs {
+    modules: Vec<ModuleDoc>,
+    /// maps each public function to the index of its defining module
+    definitions: HashMap<String, usize>,
+}
+
+impl Docs {
+    pub fn new(mut modules: Vec<ModuleDoc>) -> Self {
+        modules.sort_by(|a, b| a.module.cmp(&b.module));
+        let definitions = modules
+            .iter()
+            .enumerate()
+            .flat_map(|(i, module)| module.funcs.iter().map(move |func| (func.name.clone(), i)))
+            .collect();
+        Self {
+            modules,
+            definitions,
+        }
+    }
+
+    /// the file name and content of the index page and the page of every module
+    pub fn pages(&self, format: DocFormat) -> Vec<(String, String)> {
+        let mut pages = vec![(format!("index.{}", format.extension()), self.index(format))];
+        for module in &self.modules {
+            pages.push((module.page(format), self.module_page(module, format)));
+        }
+        pages
+    }
+
+    fn index(&self, format: DocFormat) -> String {
+        let mut out = String::new();
+        match format {
+            DocFormat::Html => {
+                html_head(&mut out, "Modules");
+                out.push_str("<h1>Modules</h1>\n<ul>\n");
+                for module in &self.modules {
+                    writeln!(
+                        out,
+                        "<li><a href=\"{}\">{}</a> ({} functions)</li>",
+                        module.page(format),
+                        escape(&module.title()),
+                        module.funcs.len()
+                    )
+                    .unwrap();
+                }
+                out.push_str("</ul>\n</body>\n</html>\n");
+            }
+            DocFormat::Markdown => {
+                out.push_str("# Modules\n\n");
+                for module in &self.modules {
+                    writeln!(
+                        out,
+                        "- [`{}`]({}) ({} functions)",
+                        module.title(),
+                        module.page(format),
+                        module.funcs.len()
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        out
+    }
+
+    /// the defining module of an external function and a link to its definition,
+    /// None if it is not documented
+    fn definition(&self, name: &str, format: DocFormat) -> Option<(&ModuleDoc, String)> {
+        self.definitions.get(name).map(|&i| {
+            let module = &self.modules[i];
+            (module, format!("{}#fn.{}", module.page(format), name))
+        })
+    }
+
+    fn module_page(&self, module: &ModuleDoc, format: DocFormat) -> String {
+        let mut out = String::new();
+        let title = module.title();
+        // external declarations link to their definition
+        let sections = [
+            ("Functions", &module.funcs, false),
+            ("External functions", &module.externs, true),
+        ];
+        match format {
+            DocFormat::Html => {
+                html_head(&mut out, &title);
+                writeln!(
+                    out,
+                    "<p><a href=\"index.html\">Modules</a></p>\n<h1>Module <code>{}</code></h1>",
+                    escape(&title)
+                )
+                .unwrap();
+                for (heading, funcs, external) in
+                    sections.into_iter().filter(|(_, f, _)| !f.is_empty())
+                {
+                    writeln!(out, "<h2>{}</h2>", heading).unwrap();
+                    for func in funcs {
+                        writeln!(
+                            out,
+                            "<h3 id=\"fn.{0}\"><code>{0}</code></h3>",
+                            escape(&func.name)
+                        )
+                        .unwrap();
+                        out.push_str("<pre>");
+                        for attr in &func.link_attrs {
+                            writeln!(out, "{}", escape(attr)).unwrap();
+                        }
+                        writeln!(out, "{}</pre>", escape(&func.signature)).unwrap();
+                        if let Some(cfg) = &func.cfg {
+                            writeln!(out, "<p>Available on <code>cfg {}</code></p>", escape(cfg))
+                                .unwrap();
+                        }
+                        if external
+                            && let Some((module, link)) = self.definition(&func.name, format)
+                        {
+                            writeln!(
+                                out,
+                                "<p>Defined in <a href=\"{}\"><code>{}</code></a></p>",
+                                link,
+                                escape(&module.title())
+                            )
+                            .unwrap();
+                        }
+                        for paragraph in func.doc.iter().flat_map(|doc| doc.split("\n\n")) {
+                            writeln!(out, "<p>{}</p>", escape(paragraph.trim())).unwrap();
+                        }
+                    }
+                }
+                out.push_str("</body>\n</html>\n");
+            }
+            DocFormat::Markdown => {
+                writeln!(out, "[Modules](index.md)\n\n# Module `{}`", title).unwrap();
+                for (heading, funcs, external) in
+                    sections.into_iter().filter(|(_, f, _)| !f.is_empty())
+                {
+                    writeln!(out, "\n## {}", heading).unwrap();
+                    for func in funcs {
+                        writeln!(out, "\n<a id=\"fn.{0}\"></a>\n### `{0}`\n", func.name).unwrap();
+                        out.push_str("```\n");
+                        for attr in &func.link_attrs {
+                            writeln!(out, "{}", attr).unwrap();
+                        }
+                        writeln!(out, "{}\n```", func.signature).unwrap();
+                        if let Some(cfg) = &func.cfg {
+                            writeln!(out, "\nAvailable on `cfg {}`", cfg).unwrap();
+                        }
+                        if external
+                            && let Some((module, link)) = self.definition(&func.name, format)
+                        {
+                            writeln!(out, "\nDefined in [`{}`]({})", module.title(), link).unwrap();
+                        }
+                        if let Some(doc) = &func.doc {
+                            writeln!(out, "\n{}", doc).unwrap();
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn html_head(out: &mut String, title: &str) {
+    writeln!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>",
+        escape(title)
+    )
+    .unwrap();
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::{ast::cfg::CfgEnv, get_ast};
+
+    #[test]
+    fn cross_links() {
+        let vec = "
+            ## Appends value to *v.
+            cfg test || !\"os=windows\";
+            public begin_def vec_push v: ptr<ptr<i64>>, value;
+            end_def
+            begin_def private;
+            end_def
+        ";
+        let main = "
+            link_attr abi c;
+            extern_def vec_push v, value;
+            extern_def missing;
+        ";
+        let (vec, _) = get_ast(vec, &CfgEnv::default());
+        let (main, _) = get_ast(main, &CfgEnv::default());
+        let docs = Docs::new(vec![
+            ModuleDoc::collect(vec!["main".into()], &main),
+            ModuleDoc::collect(vec!["std".into(), "vec".into()], &vec),
+        ]);
+
+        let vec = &docs.modules[1];
+        assert_eq!(
+            vec.funcs,
+            vec![FuncDoc {
+                name: "vec_push".into(),
+                signature: "public begin_def vec_push v: ptr<ptr<i64>>, value;".into(),
+                link_attrs: Vec::new(),
+                cfg: Some("test || !\"os=windows\"".into()),
+                doc: Some("Appends value to *v.".into()),
+            }]
+        );
+        assert_eq!(
+            docs.modules[0].externs[0].link_attrs,
+            vec!["link_attr abi c;"]
+        );
+
+        let pages = docs.pages(DocFormat::Markdown);
+        assert_eq!(
+            pages
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["index.md", "main.md", "std.vec.md"]
+        );
+        assert!(
+            pages[1]
+                .1
+                .contains("Defined in [`std::vec`](std.vec.md#fn.vec_push)")
+        );
+        assert_eq!(pages[1].1.matches("Defined in").count(), 1);
+    }
+}
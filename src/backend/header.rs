@@ -0,0 +1,149 @@
+use std::io::{self, Write};
+
+use crate::frontend::ast::{Ast, Item, Type};
+
+static C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile", "while", "bool", "true", "false",
+];
+
+/// A function, which is callable from C.
+/// Parameters and the return value are declared with their annotated type, unannotated ones are `int64_t`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CExport {
+    pub name: String,
+    pub args: Vec<String>,
+    pub arg_types: Vec<Option<Type>>,
+    pub ret: Option<Type>,
+}
+
+impl CExport {
+    /// collects all functions defined and exported in ast
+    pub fn collect(ast: &Ast) -> impl Iterator<Item = Self> {
+        ast.funcs().filter_map(|item| match item {
+            Item::Function(func) if func.link_attr.is_public && !func.link_attr.external => {
+                Some(Self {
+                    name: func.name.clone(),
+                    args: func.args.clone(),
+                    arg_types: func.arg_types.clone(),
+                    ret: func.ret.clone(),
+                })
+            }
+            _ => None,
+        })
+    }
+
+    fn write_prototype(&self, out: &mut impl Write) -> io::Result<()> {
+        let args = if self.args.is_empty() {
+            "void".to_string()
+        } else {
+            self.args
+                .iter()
+                .zip(&self.arg_types)
+                .enumerate()
+                .map(|(i, (arg, ty))| format!("{} {}", c_type(ty.as_ref()), c_ident(arg, i)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        writeln!(
+            out,
+            "{} {}({});",
+            c_type(self.ret.as_ref()),
+            self.name,
+            args
+        )
+    }
+}
+
+/// Writes a C header declaring all exports, guarded by include guard `guard`
+pub fn write_c_header(exports: &[CExport], guard: &str, out: &mut impl Write) -> io::Result<()> {
+    let guard = guard
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    writeln!(out, "/* generated by mini_compiler. Do not edit. */")?;
+    writeln!(out, "#ifndef {}", guard)?;
+    writeln!(out, "#define {}\n", guard)?;
+    writeln!(out, "#include <stdint.h>\n")?;
+    writeln!(out, "#ifdef __cplusplus\nextern \"C\" {{\n#endif\n")?;
+
+    for export in exports {
+        writeln!(
+            out,
+            "/* {} takes {} argument(s) */",
+            export.name,
+            export.args.len()
+        )?;
+        export.write_prototype(out)?;
+    }
+
+    writeln!(out, "\n#ifdef __cplusplus\n}}\n#endif\n")?;
+    writeln!(out, "#endif /* {} */", guard)
+}
+
+/// the C type of a value of type ty, unannotated values are `int64_t`
+fn c_type(ty: Option<&Type>) -> String {
+    match ty {
+        None | Some(Type::I64) => "int64_t".into(),
+        Some(Type::U64) => "uint64_t".into(),
+        Some(Type::U8) => "uint8_t".into(),
+        Some(Type::Ptr(inner)) => format!("{}*", c_type(Some(inner))),
+    }
+}
+
+/// maps a parameter name to a valid C identifier
+fn c_ident(ident: &str, position: usize) -> String {
+    if !ident.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        format!("arg{}", position)
+    } else if C_KEYWORDS.contains(&ident) {
+        format!("{}_", ident)
+    } else {
+        ident.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::{ast::cfg::CfgEnv, get_ast};
+
+    #[test]
+    fn header() {
+        let s = "
+            extern_def print_str;
+            begin_def private_helper x;
+            end_def
+            public begin_def add lhs, int;
+                return lhs + int;
+            end_def
+            public begin_def nothing;
+            end_def
+            public begin_def first s: ptr<u8>, v: ptr<ptr<i64>>, n: u64 -> u8;
+                return *s;
+            end_def
+        ";
+        let ast = get_ast(s, &CfgEnv::default()).0;
+        let exports = CExport::collect(&ast).collect::<Vec<_>>();
+        assert_eq!(exports.len(), 3);
+
+        let mut out = Vec::new();
+        write_c_header(&exports, "my-lib.h", &mut out).unwrap();
+        let header = String::from_utf8(out).unwrap();
+
+        assert!(header.contains("#ifndef MY_LIB_H\n#define MY_LIB_H"));
+        assert!(header.contains("int64_t add(int64_t lhs, int64_t int_);"));
+        assert!(header.contains("int64_t nothing(void);"));
+        assert!(header.contains("uint8_t first(uint8_t* s, int64_t** v, uint64_t n);"));
+        assert!(!header.contains("private_helper"));
+        assert!(!header.contains("print_str"));
+    }
+}
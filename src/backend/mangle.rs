@@ -0,0 +1,123 @@
+//! Symbol names of module private functions.
+//! A private function `f` in module `std::collections::vec` is emitted as `_MN3std11collections3vec1fE`,
+//! so private functions of different modules never clash, even if they end up global.
+//! Public functions keep their plain name, as they are part of the C visible interface.
+//! Bytes of a segment, which are not valid in an identifier, are escaped as `$` followed by two hex digits,
+//! e.g. the module of `my-mod.lang` becomes `my$2dmod`.
+
+const PREFIX: &str = "_MN";
+const ESCAPE: char = '$';
+
+/// mangles name, qualified by the module path
+pub fn mangle(module: &[String], name: &str) -> String {
+    let mut symbol = PREFIX.to_string();
+    for segment in module.iter().map(String::as_str).chain([name]) {
+        let segment = escape(segment);
+        symbol.push_str(&segment.len().to_string());
+        symbol.push_str(&segment);
+    }
+    symbol.push('E');
+    symbol
+}
+
+/// demangles a single symbol into its qualified path, e.g. `std::collections::vec::f`
+pub fn demangle(symbol: &str) -> Option<String> {
+    let (path, rest) = demangle_prefix(symbol)?;
+    rest.is_empty().then_some(path)
+}
+
+/// replaces every mangled symbol in text with its qualified path
+pub fn demangle_all(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(PREFIX) {
+        out.push_str(&rest[..start]);
+        match demangle_prefix(&rest[start..]) {
+            Some((path, tail)) => {
+                out.push_str(&path);
+                rest = tail;
+            }
+            None => {
+                out.push_str(PREFIX);
+                rest = &rest[start + PREFIX.len()..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// demangles the symbol at the start of s, returning the path and the remaining text
+fn demangle_prefix(s: &str) -> Option<(String, &str)> {
+    let mut rest = s.strip_prefix(PREFIX)?;
+    let mut segments = Vec::new();
+    while !rest.starts_with('E') {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let len: usize = rest[..digits].parse().ok()?;
+        let segment = rest.get(digits..digits + len)?;
+        if segment.is_empty()
+            || !segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ESCAPE)
+        {
+            return None;
+        }
+        segments.push(unescape(segment)?);
+        rest = &rest[digits + len..];
+    }
+    (!segments.is_empty()).then(|| (segments.join("::"), &rest[1..]))
+}
+
+fn escape(segment: &str) -> String {
+    let mut escaped = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'_' {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("{}{:02x}", ESCAPE, byte));
+        }
+    }
+    escaped
+}
+
+fn unescape(segment: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(segment.len());
+    let mut rest = segment;
+    while let Some(start) = rest.find(ESCAPE) {
+        bytes.extend_from_slice(&rest.as_bytes()[..start]);
+        let hex = rest.get(start + 1..start + 3)?;
+        bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        rest = &rest[start + 3..];
+    }
+    bytes.extend_from_slice(rest.as_bytes());
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let module = ["std".to_string(), "collections".into(), "vec".into()];
+        let symbol = mangle(&module, "__bound_violation");
+        assert_eq!(symbol, "_MN3std11collections3vec17__bound_violationE");
+        assert_eq!(
+            demangle(&symbol).as_deref(),
+            Some("std::collections::vec::__bound_violation")
+        );
+        assert_eq!(demangle("_MN3stdE_"), None);
+        assert_eq!(demangle("printf"), None);
+
+        let escaped = mangle(&["my-mod".to_string(), "café".into()], "helper");
+        assert_eq!(escaped, "_MN8my$2dmod9caf$c3$a96helperE");
+        assert_eq!(demangle(&escaped).as_deref(), Some("my-mod::café::helper"));
+        assert_eq!(demangle("_MN3a$zE"), None);
+
+        let err = format!("undefined reference to `{}'; _MN is not a symbol", symbol);
+        assert_eq!(
+            demangle_all(&err),
+            "undefined reference to `std::collections::vec::__bound_violation'; _MN is not a symbol"
+        );
+    }
+}
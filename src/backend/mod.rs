@@ -1,10 +1,20 @@
-use std::path::Path;
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
 use codegen::x86_64::AsmWriter;
+use serde_json::json;
 
-use crate::{backend::codegen::ProgramIR, frontend::ast::Ast};
+use crate::{
+    backend::codegen::ProgramIR,
+    frontend::ast::{Ast, error::MessageFormat},
+};
 
 mod codegen;
+pub mod doc;
+pub mod header;
+pub mod mangle;
 
 // declarartion ->
 // push value to stack and store relative position
@@ -17,12 +27,88 @@ pub fn generate(ast: &Ast) -> Result<ProgramIR, BackendErr> {
     Ok(ProgramIR::build(ast))
 }
 
-pub fn asm_gen(code: ProgramIR, name: &Path) -> Result<(), BackendErr> {
-    AsmWriter::new(name, &code).write(&code);
+pub fn asm_gen(code: ProgramIR, name: &Path, opts: &CodegenOpts) -> Result<(), BackendErr> {
+    AsmWriter::new(name, &code, opts).write(&code);
     Ok(())
 }
 
+/// Target specific options for the emitted assembly.
+#[derive(Debug, Clone, Default)]
+pub struct CodegenOpts {
+    /// emit position independent code, i.e. rip relative addressing for all symbols,
+    /// calls to non local functions through the PLT and relocatable metadata
+    pub pic: bool,
+    /// path of the compiled module, which qualifies the symbols of private functions
+    pub module: Vec<String>,
+    /// annotate mangled symbols with their demangled path
+    pub demangle: bool,
+}
+
+impl CodegenOpts {
+    pub fn with_pic(mut self, pic: bool) -> Self {
+        self.pic = pic;
+        self
+    }
+
+    pub fn with_module(mut self, module: Vec<String>) -> Self {
+        self.module = module;
+        self
+    }
+
+    pub fn with_demangle(mut self, demangle: bool) -> Self {
+        self.demangle = demangle;
+        self
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BackendErr {
-    General,
+    /// nasm rejected the assembly at the path
+    Assemble(PathBuf),
+    /// gcc could not link the artifact at the path
+    Link(PathBuf),
+    /// ar could not create the archive at the path
+    Archive(PathBuf),
+}
+
+impl BackendErr {
+    /// the stable code identifying the kind of error
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Assemble(_) => "E0030",
+            Self::Link(_) => "E0031",
+            Self::Archive(_) => "E0032",
+        }
+    }
+
+    /// prints the error in format. JSON lines are written to stdout
+    pub fn emit(&self, format: MessageFormat) {
+        match format {
+            MessageFormat::Human => {
+                eprintln!("\x1b[31m[{}] Error:\x1b[0m {}", self.code(), self)
+            }
+            MessageFormat::Json => {
+                let (Self::Assemble(path) | Self::Link(path) | Self::Archive(path)) = self;
+                let diagnostic = json!({
+                    "severity": "error",
+                    "code": self.code(),
+                    "message": self.to_string(),
+                    "file": path.display().to_string(),
+                    "span": null,
+                    "labels": [],
+                });
+                println!("{}", diagnostic);
+            }
+        }
+    }
+}
+
+impl Display for BackendErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Assemble(path) => write!(f, "nasm failed for {}", path.display()),
+            Self::Link(path) => write!(f, "gcc failed to link {}", path.display()),
+            Self::Archive(path) => write!(f, "ar failed for {}", path.display()),
+        }
+    }
 }
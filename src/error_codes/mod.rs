@@ -0,0 +1,76 @@
+//! Long form explanations of the error codes, as printed by `--explain`.
+//!
+//! Codes are assigned in order and never reused. Retired codes are left out of the list,
+//! so `--explain` reports them as unknown: E0002 (formerly any lexer error, now E0020 to
+//! E0028), E0005 and E0029.
+
+macro_rules! explanations {
+    ($($code:ident),* $(,)?) => {
+        /// every error code with its explanation, in ascending order
+        pub const EXPLANATIONS: &[(&str, &str)] = &[
+            $((stringify!($code), include_str!(concat!(stringify!($code), ".md"))),)*
+        ];
+    };
+}
+
+explanations!(
+    E0001, E0003, E0004, E0006, E0007, E0008, E0009, E0010, E0011, E0012, E0013, E0014, E0015,
+    E0016, E0017, E0018, E0019, E0020, E0021, E0022, E0023, E0024, E0025, E0026, E0027, E0028,
+    E0030, E0031, E0032, E0033, E0034, E0035,
+);
+
+/// the explanation of code, which may omit the leading `E` and zeros, e.g. `3` for `E0003`
+pub fn explain(code: &str) -> Option<&'static str> {
+    let digits = code.strip_prefix(['E', 'e']).unwrap_or(code);
+    let code = format!("E{:04}", digits.parse::<u16>().ok()?);
+    EXPLANATIONS
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map(|(_, explanation)| *explanation)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        backend::BackendErr,
+        frontend::{ast::cfg::CfgEnv, get_checked_ast, import::Resolver},
+    };
+
+    #[test]
+    fn codes_are_explained() {
+        assert!(EXPLANATIONS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(explain("e3"), explain("E0003"));
+        assert!(explain("E9999").is_none());
+        assert!(explain("E0002").is_none());
+
+        // the erroneous example of each explanation raises its error
+        let lib_root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("lib");
+        let resolver = Resolver::new(&lib_root.join("example.lang"))
+            .with_include_dirs([PathBuf::from("/usr/include")])
+            .with_lib_roots([lib_root.clone()]);
+        // backend errors are raised by nasm, gcc and ar, so their examples are not compiled
+        let backend = [
+            BackendErr::Assemble("util.asm".into()),
+            BackendErr::Link("a.out".into()),
+            BackendErr::Archive("libout.a".into()),
+        ]
+        .map(|err| err.code());
+        for (code, explanation) in EXPLANATIONS {
+            if !explanation.contains("Erroneous code example") || backend.contains(code) {
+                continue;
+            }
+            let example = explanation.split("```\n").nth(1).unwrap();
+            let (_, diagnostics) = get_checked_ast(example, &CfgEnv::default(), &resolver);
+            let codes = diagnostics
+                .errs
+                .iter()
+                .map(|e| e.inner.code())
+                .collect::<Vec<_>>();
+            assert_eq!(codes.first(), Some(code), "{:?}", codes);
+        }
+        assert!(explain(BackendErr::Link("a.out".into()).code()).is_some());
+    }
+}
@@ -25,18 +25,19 @@ impl CfgEnv {
     pub fn eval_cfg_expr(&self, expr: &Expr) -> bool {
         match expr {
             Expr::Val(v) => match v {
-                Val::Lit(str) => {
-                    if let Some((key, value)) = str.split_once('=') {
+                Val::Lit(lit) => {
+                    let spec = String::from_utf8_lossy(lit);
+                    if let Some((key, value)) = spec.split_once('=') {
                         self.mappings.get(key).is_some_and(|v| v == value)
                     } else {
-                        self.flags.contains(str)
+                        self.flags.contains(spec.as_ref())
                     }
                 }
                 crate::frontend::ast::Val::Var(str) => self.flags.contains(str),
                 _ => false,
             },
             Expr::Op(lhs, op, rhs) => match op {
-                Operation::BitAND => {
+                Operation::BitAND | Operation::LogicalAnd => {
                     let lhs = self.eval_cfg_expr(lhs.as_ref());
                     if !lhs {
                         return false;
@@ -44,7 +45,7 @@ impl CfgEnv {
                     self.eval_cfg_expr(rhs.as_ref())
                 }
                 Operation::Not => !self.eval_cfg_expr(rhs.as_ref()),
-                Operation::BitOR => {
+                Operation::BitOR | Operation::LogicalOr => {
                     let lhs = self.eval_cfg_expr(lhs.as_ref());
                     if lhs {
                         return true;
@@ -58,7 +59,11 @@ impl CfgEnv {
                 }
                 _ => false,
             },
-            Expr::Malformed => false,
+            Expr::Array(..)
+            | Expr::Field(..)
+            | Expr::SizeOf(_)
+            | Expr::Load(..)
+            | Expr::Malformed => false,
         }
     }
 
@@ -1,8 +1,19 @@
-use std::ops::Range;
+use std::{
+    fmt::Display,
+    io::{self, Write},
+    ops::{Deref, DerefMut, Range},
+};
 
 use ariadne::{Label, Report, ReportBuilder, ReportKind, Source};
+use serde_json::{Value, json};
 
-use crate::frontend::lexer::{Span, Token};
+use crate::frontend::{
+    ast::{
+        Arity, Ast, Type,
+        lint::{Level, Lint, LintLevels},
+    },
+    lexer::{LexErr, Span, Token},
+};
 
 #[macro_export]
 macro_rules! unexpected {
@@ -40,10 +51,22 @@ pub trait BuildReport {
         'a: 'b;
 }
 
+/// How diagnostics are printed
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// annotated source snippets
+    #[default]
+    Human,
+    /// one JSON object per line
+    Json,
+}
+
 #[derive(Debug, Default)]
 pub struct Diagnostics<'a> {
     pub errs: Vec<Spanned<AstErr<'a>>>,
     pub warns: Vec<Spanned<AstWarn<'a>>>,
+    /// warnings of denied lints, which fail the compilation like errors
+    pub denied: Vec<Spanned<AstWarn<'a>>>,
 }
 
 impl<'a> Diagnostics<'a> {
@@ -51,9 +74,26 @@ impl<'a> Diagnostics<'a> {
         Self::default()
     }
 
+    /// number of errors and denied warnings
+    pub fn error_count(&self) -> usize {
+        self.errs.len() + self.denied.len()
+    }
+
+    /// drops allowed warnings and moves denied ones to denied, according to the `lint_attr` attributes of ast
+    pub fn apply_lints(&mut self, ast: &Ast, levels: &LintLevels) {
+        for warn in std::mem::take(&mut self.warns) {
+            match levels.level(warn.inner.lint(), &warn.span, ast.lint_attrs()) {
+                Level::Allow => {}
+                Level::Warn => self.warns.push(warn),
+                Level::Deny => self.denied.push(warn),
+            }
+        }
+    }
+
     pub fn report(&self, file: &str, source: &str) {
         for e in &self.errs {
-            let mut report = Report::build(ReportKind::Error, (file, e.span.start..e.span.end));
+            let mut report = Report::build(ReportKind::Error, (file, e.span.start..e.span.end))
+                .with_code(e.inner.code());
             report = e.inner.report(report, file);
             report = report.with_label(
                 Label::new((file, e.span.start..e.span.end))
@@ -62,16 +102,106 @@ impl<'a> Diagnostics<'a> {
             report.finish().print((file, Source::from(source))).unwrap();
         }
 
-        for w in &self.warns {
-            let mut report = Report::build(ReportKind::Warning, (file, w.span.start..w.span.end));
+        let warns = self.warns.iter().map(|w| (ReportKind::Warning, w));
+        let denied = self.denied.iter().map(|w| (ReportKind::Error, w));
+        for (kind, w) in warns.chain(denied) {
+            let lint = w.inner.lint();
+            let mut report =
+                Report::build(kind, (file, w.span.start..w.span.end)).with_code(w.inner.code());
             report = w.inner.report(report, file);
             report = report.with_label(
                 Label::new((file, w.span.start..w.span.end))
                     .with_message("while parsing this block"),
             );
+            report = match kind {
+                ReportKind::Error => report.with_note(format!("lint {} is denied", lint)),
+                _ => report.with_note(format!(
+                    "silence lint {} with `-A {}` or `lint_attr allow {};`",
+                    lint, lint, lint
+                )),
+            };
             report.finish().print((file, Source::from(source))).unwrap();
         }
     }
+
+    /// prints the diagnostics in format. JSON lines are written to stdout
+    pub fn emit(&self, file: &str, source: &str, format: MessageFormat) {
+        match format {
+            MessageFormat::Human => self.report(file, source),
+            MessageFormat::Json => self
+                .write_json(file, source, &mut io::stdout().lock())
+                .unwrap(),
+        }
+    }
+
+    /// writes each error and warning as a JSON object on its own line.
+    /// Warnings and denied warnings name their lint
+    pub fn write_json(&self, file: &str, source: &str, out: &mut impl Write) -> io::Result<()> {
+        let errs = self.errs.iter().map(|e| {
+            (
+                "error",
+                e.inner.code(),
+                e.inner.to_string(),
+                &e.inner,
+                &e.span,
+                None,
+            )
+        });
+        let warns = self.warns.iter().map(|w| ("warning", w));
+        let denied = self.denied.iter().map(|w| ("error", w));
+        let warns = warns.chain(denied).map(|(severity, w)| {
+            let AstWarn::DeadCodeError { err, .. } = &w.inner;
+            (
+                severity,
+                w.inner.code(),
+                w.inner.to_string(),
+                err,
+                &w.span,
+                Some(w.inner.lint().name()),
+            )
+        });
+        for (severity, code, message, err, block, lint) in errs.chain(warns) {
+            let labels = err
+                .labels()
+                .into_iter()
+                .chain([(block.clone(), "while parsing this block".to_string())])
+                .map(|(span, message)| json!({ "span": json_span(source, &span), "message": message }))
+                .collect::<Vec<_>>();
+            let diagnostic = json!({
+                "severity": severity,
+                "code": code,
+                "lint": lint,
+                "message": message,
+                "file": file,
+                "span": json_span(source, block),
+                "labels": labels,
+            });
+            writeln!(out, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+/// byte offsets together with 1-based lines and columns, which count chars
+fn json_span(source: &str, span: &Span) -> Value {
+    let line_col = |offset: usize| {
+        let before = &source[..source.floor_char_boundary(offset.min(source.len()))];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    };
+    let (line_start, column_start) = line_col(span.start);
+    let (line_end, column_end) = line_col(span.end);
+    json!({
+        "byte_start": span.start,
+        "byte_end": span.end,
+        "line_start": line_start,
+        "column_start": column_start,
+        "line_end": line_end,
+        "column_end": column_end,
+    })
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
@@ -80,9 +210,35 @@ pub struct Spanned<T> {
     pub span: Span,
 }
 
+impl<T> Spanned<T> {
+    pub fn new(inner: T, span: Span) -> Self {
+        Self { inner, span }
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<T> AsRef<T> for Spanned<T> {
+    fn as_ref(&self) -> &T {
+        &self.inner
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AstErr<'a> {
     UnexecpectedEOF,
+    Lex(LexErr),
     UnclosedBlock {
         at: Spanned<Token<'a>>,
         expected: Vec<Token<'a>>,
@@ -91,8 +247,64 @@ pub enum AstErr<'a> {
         expected: Vec<Token<'a>>,
         found: Spanned<Token<'a>>,
     },
-    UndefinedFunctionCall {
+    ArityMismatch {
+        name: String,
+        expected: Arity,
+        found: usize,
+    },
+    UnresolvedImport {
+        path: String,
+    },
+    UnresolvedPath {
+        path: String,
+    },
+    PrivateFunction {
+        name: String,
+        module: String,
+    },
+    NonConstInitializer {
         name: String,
+        reason: String,
+    },
+    AssignToConst {
+        name: String,
+    },
+    NonConstArray {
+        reason: String,
+    },
+    UnknownStruct {
+        name: String,
+    },
+    UnknownField {
+        field: String,
+    },
+    AmbiguousField {
+        field: String,
+        structs: Vec<String>,
+    },
+    TypeMismatch {
+        expected: Type,
+        found: Type,
+        context: String,
+    },
+    InvalidOperand {
+        op: String,
+        ty: Type,
+    },
+    ConflictingType {
+        name: String,
+        first: Type,
+        second: Type,
+    },
+    IntegerOverflow,
+    UnknownLint {
+        name: String,
+    },
+    /// a struct declaring a field twice, spanning the second declaration
+    DuplicateField {
+        field: String,
+        strukt: String,
+        first: Span,
     },
 }
 
@@ -101,6 +313,49 @@ impl<'a> AstErr<'a> {
         Spanned { inner: self, span }
     }
 
+    /// the stable code identifying the kind of error
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnexecpectedEOF => "E0001",
+            Self::Lex(err) => err.code(),
+            Self::UnclosedBlock { .. } => "E0003",
+            Self::UnexpectedToken { .. } => "E0004",
+            Self::ArityMismatch { .. } => "E0006",
+            Self::UnresolvedImport { .. } => "E0007",
+            Self::UnresolvedPath { .. } => "E0008",
+            Self::PrivateFunction { .. } => "E0009",
+            Self::NonConstInitializer { .. } => "E0010",
+            Self::AssignToConst { .. } => "E0011",
+            Self::NonConstArray { .. } => "E0012",
+            Self::UnknownStruct { .. } => "E0013",
+            Self::UnknownField { .. } => "E0014",
+            Self::AmbiguousField { .. } => "E0015",
+            Self::TypeMismatch { .. } => "E0016",
+            Self::InvalidOperand { .. } => "E0017",
+            Self::ConflictingType { .. } => "E0018",
+            Self::IntegerOverflow => "E0019",
+            Self::UnknownLint { .. } => "E0033",
+            Self::DuplicateField { .. } => "E0034",
+        }
+    }
+
+    /// messages attached to spans other than the block of the error
+    pub fn labels(&self) -> Vec<(Span, String)> {
+        match self {
+            Self::UnclosedBlock { at, expected } => {
+                vec![(at.span.clone(), format!("expected one of {:?}", expected))]
+            }
+            Self::UnexpectedToken { expected, found } => vec![(
+                found.span.clone(),
+                format!("expected one of {:?}, found {:?}", expected, found.inner),
+            )],
+            Self::DuplicateField { field, first, .. } => {
+                vec![(first.clone(), format!("{} first declared here", field))]
+            }
+            _ => Vec::new(),
+        }
+    }
+
     pub fn into_warn(self, msg: String) -> AstWarn<'a> {
         AstWarn::DeadCodeError {
             err: self,
@@ -109,6 +364,62 @@ impl<'a> AstErr<'a> {
     }
 }
 
+impl Display for AstErr<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AstErr::UnexecpectedEOF => write!(f, "unexpected EOF"),
+            AstErr::Lex(err) => write!(f, "{}", err),
+            AstErr::UnclosedBlock { .. } => write!(f, "unclosed code block"),
+            AstErr::UnexpectedToken { .. } => write!(f, "unexpected token"),
+            AstErr::ArityMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} takes {} argument(s), but {} were supplied",
+                name, expected, found
+            ),
+            AstErr::UnresolvedImport { path } => write!(f, "could not find import {}", path),
+            AstErr::UnresolvedPath { path } => write!(f, "could not resolve {}", path),
+            AstErr::PrivateFunction { name, module } => {
+                write!(f, "function {} is private to module {}", name, module)
+            }
+            AstErr::NonConstInitializer { name, .. } => {
+                write!(f, "initializer of {} is not a constant expression", name)
+            }
+            AstErr::AssignToConst { name } => write!(f, "cannot assign to constant {}", name),
+            AstErr::NonConstArray { .. } => write!(
+                f,
+                "the initializer and length of an array must be constant expressions"
+            ),
+            AstErr::UnknownStruct { name } => write!(f, "no struct named {}", name),
+            AstErr::UnknownField { field } => write!(f, "no struct has a field {}", field),
+            AstErr::AmbiguousField { field, .. } => write!(f, "field {} is ambiguous", field),
+            AstErr::TypeMismatch {
+                expected,
+                found,
+                context,
+            } => write!(
+                f,
+                "mismatched types in {}: expected {}, found {}",
+                context, expected, found
+            ),
+            AstErr::InvalidOperand { op, ty } => write!(f, "cannot apply {} to {}", op, ty),
+            AstErr::ConflictingType {
+                name,
+                first,
+                second,
+            } => write!(f, "{} is declared as {} and as {}", name, first, second),
+            AstErr::IntegerOverflow => write!(f, "integer literal is out of range for i64"),
+            AstErr::UnknownLint { name } => write!(f, "unknown lint {}", name),
+            AstErr::DuplicateField { field, strukt, .. } => {
+                write!(f, "field {} is declared twice in struct {}", field, strukt)
+            }
+        }
+    }
+}
+
 impl<'a> BuildReport for AstErr<'a> {
     fn report<'b, 'c>(
         &self,
@@ -118,25 +429,64 @@ impl<'a> BuildReport for AstErr<'a> {
     where
         'b: 'c,
     {
+        let builder = self.labels().into_iter().fold(
+            builder.with_message(self),
+            |builder, (span, message)| {
+                builder.with_label(Label::new((file, span.start..span.end)).with_message(message))
+            },
+        );
         match self {
-            AstErr::UnexecpectedEOF => builder.with_message("unexpected EOF"),
-            AstErr::UnclosedBlock { at, expected } => {
-                builder.with_message("unclosed code block").with_label(
-                    Label::new((file, at.span.start..at.span.end))
-                        .with_message(format!("expected one of {:?}", expected)),
-                )
+            AstErr::Lex(LexErr::UnknownEscape(_)) => builder.with_help(
+                "valid escapes are \\n, \\t, \\r, \\0, \\\\, \\', \\\", \\x.. and \\u{..}",
+            ),
+            AstErr::Lex(LexErr::CharLength(_)) => {
+                builder.with_help("use double quotes for string literals")
+            }
+            AstErr::UnresolvedImport { .. } => builder.with_help(
+                "imports are resolved relative to the importing file and the include directories",
+            ),
+            AstErr::UnresolvedPath { .. } => {
+                builder.with_help("the qualifying module must be imported with `use` or `import`")
+            }
+            AstErr::PrivateFunction { .. } => {
+                builder.with_help("mark the function as `public` to use it from other modules")
+            }
+            AstErr::NonConstInitializer { reason, .. } | AstErr::NonConstArray { reason } => {
+                builder.with_note(reason)
+            }
+            AstErr::AssignToConst { .. } => {
+                builder.with_help("use a `static` for mutable global state")
+            }
+            AstErr::UnknownField { .. } => {
+                builder.with_help("structs must be declared in or imported into this module")
             }
-            AstErr::UnexpectedToken { expected, found } => {
-                builder.with_message("unexpected token").with_label(
-                    Label::new((file, found.span.start..found.span.end)).with_message(format!(
-                        "expected one of {:?}, found {:?}",
-                        expected, found.inner
-                    )),
-                )
+            AstErr::AmbiguousField { field, structs } => builder
+                .with_note(format!("declared by {}", structs.join(", ")))
+                .with_help(format!(
+                    "qualify the field, i.e. `->{}::{}`",
+                    structs[0], field
+                )),
+            AstErr::InvalidOperand {
+                ty: Type::Ptr(_), ..
+            } => builder
+                .with_note("pointers may only be offset by integers or subtracted from each other"),
+            AstErr::ConflictingType { .. } => {
+                builder.with_help("a variable has a single type in the whole function")
             }
-            AstErr::UndefinedFunctionCall { name } => builder
-                .with_message(format!("tried to call undefined function {}", name))
-                .with_help("functions must be defined above the call site"),
+            AstErr::IntegerOverflow => builder.with_note(format!(
+                "integer literals range from {} to {}",
+                i64::MIN,
+                i64::MAX
+            )),
+            AstErr::UnknownLint { .. } => builder.with_help(format!(
+                "known lints are {}",
+                Lint::ALL
+                    .iter()
+                    .map(Lint::name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            _ => builder,
         }
     }
 }
@@ -150,11 +500,35 @@ pub enum AstWarn<'a> {
 }
 
 impl<'a> AstWarn<'a> {
+    pub fn lint(&self) -> Lint {
+        match self {
+            Self::DeadCodeError { .. } => Lint::DeadCodeError,
+        }
+    }
+
+    /// the code of the error the warning was demoted from
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DeadCodeError { err, .. } => err.code(),
+        }
+    }
+
     pub fn at(self, span: Span) -> Spanned<AstWarn<'a>> {
         Spanned { inner: self, span }
     }
 }
 
+impl Display for AstWarn<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DeadCodeError {
+                err,
+                reason_for_dead,
+            } => write!(f, "ignored due to {}: {}", reason_for_dead, err),
+        }
+    }
+}
+
 impl<'c> BuildReport for AstWarn<'c> {
     fn report<'a, 'b>(
         &self,
@@ -165,12 +539,34 @@ impl<'c> BuildReport for AstWarn<'c> {
         'a: 'b,
     {
         match self {
-            Self::DeadCodeError {
-                err,
-                reason_for_dead,
-            } => err
-                .report(builder, file)
-                .with_message(format!("ignored due to {}", reason_for_dead)),
+            Self::DeadCodeError { err, .. } => err.report(builder, file).with_message(self),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::{ast::cfg::CfgEnv, get_ast};
+
+    #[test]
+    fn json_lines() {
+        let s = "begin_def main;\n\tä = 1 +;\nend_def\n";
+        let (_, diagnostics) = get_ast(s, &CfgEnv::default());
+        let mut out = Vec::new();
+        diagnostics.write_json("main.lang", s, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 1);
+
+        let diagnostic: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(diagnostic["severity"], "error");
+        assert_eq!(diagnostic["code"], "E0004");
+        assert_eq!(diagnostic["file"], "main.lang");
+        let found = &diagnostic["labels"][0]["span"];
+        assert_eq!(found["byte_start"], s.find("+;").unwrap() + 1);
+        assert_eq!(found["line_start"], 2);
+        // columns count chars, not bytes
+        assert_eq!(found["column_start"], 9);
+    }
+}
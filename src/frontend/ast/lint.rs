@@ -0,0 +1,188 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::frontend::lexer::Span;
+
+/// A named class of warnings, whose level may be set on the command line or by `lint_attr`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// errors in code excluded by `cfg`
+    DeadCodeError,
+}
+
+impl Lint {
+    pub const ALL: &[Lint] = &[Self::DeadCodeError];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DeadCodeError => "dead_code_error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|lint| lint.name() == name)
+    }
+
+    pub fn default_level(&self) -> Level {
+        match self {
+            Self::DeadCodeError => Level::Warn,
+        }
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "allow" => Some(Self::Allow),
+            "warn" => Some(Self::Warn),
+            "deny" => Some(Self::Deny),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Allow => write!(f, "allow"),
+            Self::Warn => write!(f, "warn"),
+            Self::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// `lint_attr <level> <lint>;` above an item, which sets the level of the lint within scope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintAttr {
+    pub lint: Lint,
+    pub level: Level,
+    /// the item including its attributes
+    pub scope: Span,
+}
+
+/// The levels of all lints outside of `lint_attr` scopes
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    levels: HashMap<Lint, Level>,
+    /// the level of all lints which would warn, as set by the `warnings` group
+    warnings: Option<Level>,
+}
+
+impl LintLevels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// sets the level of the lint, overriding any level set before
+    pub fn with_level(mut self, lint: Lint, level: Level) -> Self {
+        self.levels.insert(lint, level);
+        self
+    }
+
+    /// sets the level of the lint or lint group called name
+    pub fn with_named_level(self, name: &str, level: Level) -> Result<Self, String> {
+        if name == "warnings" {
+            return Ok(self.with_warnings(level));
+        }
+        match Lint::from_name(name) {
+            Some(lint) => Ok(self.with_level(lint, level)),
+            None => Err(format!("unknown lint {}", name)),
+        }
+    }
+
+    /// sets the level of every lint which would warn otherwise
+    pub fn with_warnings(mut self, level: Level) -> Self {
+        self.warnings = Some(level);
+        self
+    }
+
+    /// the level of lint at span. `lint_attr` takes precedence over the command line,
+    /// except for the `warnings` group applying to anything that still warns
+    pub fn level(&self, lint: Lint, span: &Span, attrs: &[LintAttr]) -> Level {
+        let level = attrs
+            .iter()
+            .rev()
+            .find(|attr| attr.lint == lint && attr.scope.contains(span.start))
+            .map(|attr| attr.level)
+            .or_else(|| self.levels.get(&lint).copied())
+            .unwrap_or(lint.default_level());
+        match (level, self.warnings) {
+            (Level::Warn, Some(warnings)) => warnings,
+            (level, _) => level,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::{ast::cfg::CfgEnv, get_ast};
+
+    #[test]
+    fn levels() {
+        let lint = Lint::DeadCodeError;
+        let span = Span { start: 10, end: 12 };
+        let attrs = [LintAttr {
+            lint,
+            level: Level::Allow,
+            scope: Span { start: 0, end: 20 },
+        }];
+
+        assert_eq!(LintLevels::new().level(lint, &span, &[]), Level::Warn);
+        let deny_warnings = LintLevels::new().with_warnings(Level::Deny);
+        assert_eq!(deny_warnings.level(lint, &span, &[]), Level::Deny);
+        assert_eq!(deny_warnings.level(lint, &span, &attrs), Level::Allow);
+
+        let levels = LintLevels::new()
+            .with_named_level("dead_code_error", Level::Deny)
+            .unwrap()
+            .with_named_level("dead_code_error", Level::Allow)
+            .unwrap();
+        assert_eq!(levels.level(lint, &span, &[]), Level::Allow);
+        let levels = LintLevels::new()
+            .with_warnings(Level::Deny)
+            .with_warnings(Level::Warn);
+        assert_eq!(levels.level(lint, &span, &[]), Level::Warn);
+        assert!(
+            LintLevels::new()
+                .with_named_level("unused", Level::Deny)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn lint_attr() {
+        let s = "
+            lint_attr allow dead_code_error;
+            cfg false;
+            begin_def skipped;
+            x = 1 +;
+            end_def
+
+            begin_def main;
+            cfg false;
+            y = 2 *;
+            end_def
+        ";
+        let (ast, mut diagnostics) = get_ast(s, &CfgEnv::default());
+        assert_eq!(ast.lint_attrs().len(), 1);
+        assert_eq!(diagnostics.warns.len(), 2);
+
+        diagnostics.apply_lints(&ast, &LintLevels::new().with_warnings(Level::Deny));
+        assert!(diagnostics.warns.is_empty());
+        assert_eq!(diagnostics.denied.len(), 1);
+        assert!(diagnostics.denied[0].span.start > s.find("main").unwrap());
+    }
+}
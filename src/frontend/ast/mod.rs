@@ -2,22 +2,161 @@ use std::fmt::{Debug, Display};
 
 use indexmap::IndexMap;
 
+use crate::frontend::{
+    ast::{
+        error::{AstErr, Spanned},
+        lint::LintAttr,
+    },
+    lexer::Span,
+};
+
 pub mod cfg;
 pub mod error;
+pub mod lint;
+pub mod node;
 pub mod parser;
+pub mod sema;
+pub mod typeck;
 
 pub(crate) fn is_builtin_func(ident: &str) -> bool {
-    matches!(ident, "goto" | "label" | "addr_of" | "asm" | "return")
+    matches!(ident, "goto" | "label" | "addr_of" | "asm" | "return") || store_width(ident).is_some()
+}
+
+/// width of builtin `store8 <ptr>, <value>;` and its siblings
+pub(crate) fn store_width(ident: &str) -> Option<Width> {
+    Some(match ident {
+        "store8" => Width::Byte,
+        "store16" => Width::Word,
+        "store32" => Width::Dword,
+        _ => return None,
+    })
+}
+
+/// width and extension of the load expression `load8(<ptr>)` and its siblings
+pub(crate) fn load_kind(ident: &str) -> Option<(Width, Extend)> {
+    Some(match ident {
+        "load8" => (Width::Byte, Extend::Zero),
+        "load16" => (Width::Word, Extend::Zero),
+        "load32" => (Width::Dword, Extend::Zero),
+        "loadi8" => (Width::Byte, Extend::Sign),
+        "loadi16" => (Width::Word, Extend::Sign),
+        "loadi32" => (Width::Dword, Extend::Sign),
+        _ => return None,
+    })
 }
 
 pub struct Ast {
     functions: IndexMap<String, Item>,
+    imports: Vec<Spanned<Import>>,
+    inactive: Vec<Span>,
+    lint_attrs: Vec<LintAttr>,
 }
 
 impl Ast {
     pub fn funcs(&self) -> impl Iterator<Item = &Item> {
         self.functions.values()
     }
+
+    pub(crate) fn funcs_mut(&mut self) -> impl Iterator<Item = &mut Item> {
+        self.functions.values_mut()
+    }
+
+    pub fn imports(&self) -> impl Iterator<Item = &Spanned<Import>> {
+        self.imports.iter()
+    }
+
+    /// items and lines excluded by `cfg`
+    pub fn inactive(&self) -> impl Iterator<Item = &Span> {
+        self.inactive.iter()
+    }
+
+    /// the `lint_attr` attributes of all items, including those excluded by `cfg`
+    pub fn lint_attrs(&self) -> &[LintAttr] {
+        &self.lint_attrs
+    }
+
+    pub fn get_func(&self, name: &str) -> Option<&Function> {
+        match self.functions.get(name) {
+            Some(Item::Function(func)) => Some(func),
+            _ => None,
+        }
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<&Global> {
+        match self.functions.get(name) {
+            Some(Item::Global(global)) => Some(global),
+            _ => None,
+        }
+    }
+
+    pub fn get_struct(&self, name: &str) -> Option<&Struct> {
+        match self.functions.get(name) {
+            Some(Item::Struct(strukt)) => Some(strukt),
+            _ => None,
+        }
+    }
+
+    pub fn structs(&self) -> impl Iterator<Item = &Struct> {
+        self.functions.values().filter_map(|item| match item {
+            Item::Struct(strukt) => Some(strukt),
+            _ => None,
+        })
+    }
+
+    /// resolves field to its byte offset. Unqualified fields must be unique among all structs
+    pub fn field_offset(&self, field: &FieldRef) -> Result<i64, AstErr<'static>> {
+        field.resolve(
+            self.structs()
+                .map(|strukt| (strukt.name.as_str(), strukt.fields.as_slice())),
+        )
+    }
+
+    pub fn globals(&self) -> impl Iterator<Item = &Global> {
+        self.functions.values().filter_map(|item| match item {
+            Item::Global(global) => Some(global),
+            _ => None,
+        })
+    }
+
+    /// declares f, if no item of the same name exists yet
+    pub fn declare(&mut self, f: Function) {
+        if !self.functions.contains_key(&f.name) {
+            self.functions.insert(f.name.clone(), Item::Function(f));
+        }
+    }
+
+    /// declares global, if no item of the same name exists yet
+    pub fn declare_global(&mut self, global: Global) {
+        if !self.functions.contains_key(&global.name) {
+            self.functions
+                .insert(global.name.clone(), Item::Global(global));
+        }
+    }
+
+    /// declares strukt, if no item of the same name exists yet
+    pub fn declare_struct(&mut self, strukt: Struct) {
+        if !self.functions.contains_key(&strukt.name) {
+            self.functions
+                .insert(strukt.name.clone(), Item::Struct(strukt));
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Import {
+    /// `import "<path>";`
+    File(String),
+    /// `use <ident>(::<ident>)*;`
+    Module(Vec<String>),
+}
+
+impl Display for Import {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "\"{}\"", path),
+            Self::Module(path) => write!(f, "{}", path.join("::")),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -26,10 +165,11 @@ pub struct LinkAttr {
     pub external: bool,
     pub is_public: bool,
     pub meta: LinkMeta,
+    pub abi: Abi,
 }
 
 impl LinkAttr {
-    fn into_external(mut self) -> Self {
+    pub(crate) fn into_external(mut self) -> Self {
         self.external = true;
         self
     }
@@ -48,6 +188,11 @@ impl LinkAttr {
         self.meta = meta;
         self
     }
+
+    pub(crate) fn with_abi(mut self, abi: Abi) -> Self {
+        self.abi = abi;
+        self
+    }
 }
 
 impl Default for LinkAttr {
@@ -57,6 +202,7 @@ impl Default for LinkAttr {
             external: false,
             is_public: false,
             meta: LinkMeta::default(),
+            abi: Abi::default(),
         }
     }
 }
@@ -68,54 +214,349 @@ pub enum LinkMeta {
     WithMeta,
 }
 
+/// Calling convention of a function
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Abi {
+    #[default]
+    Lang,
+    /// System V calls require an aligned stack and al set to the number of vector registers for variadic functions
+    C {
+        variadic: bool,
+        /// an integer return value narrower than a qword, whose upper bits are left undefined by the callee
+        ret: Option<(Width, Extend)>,
+    },
+}
+
+/// Number of arguments a function accepts
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    /// any number of arguments is accepted
+    #[default]
+    Unchecked,
+    Exact(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, n_args: usize) -> bool {
+        match self {
+            Self::Unchecked => true,
+            Self::Exact(n) => *n == n_args,
+            Self::AtLeast(n) => *n <= n_args,
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unchecked => write!(f, "any number of"),
+            Self::Exact(n) => write!(f, "{}", n),
+            Self::AtLeast(n) => write!(f, "at least {}", n),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Item {
     Function(Function),
+    Global(Global),
+    Struct(Struct),
     Malformed,
 }
 
+impl Item {
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Function(func) => Some(&func.name),
+            Self::Global(global) => Some(&global.name),
+            Self::Struct(strukt) => Some(&strukt.name),
+            Self::Malformed => None,
+        }
+    }
+
+    pub fn link_attr(&self) -> Option<&LinkAttr> {
+        match self {
+            Self::Function(func) => Some(&func.link_attr),
+            Self::Global(global) => Some(&global.link_attr),
+            Self::Struct(strukt) => Some(&strukt.link_attr),
+            Self::Malformed => None,
+        }
+    }
+}
+
+/// A module level variable or constant
+#[derive(Debug)]
+pub struct Global {
+    pub name: String,
+    pub kind: GlobalKind,
+    /// constant initializer. Statics without one are zero initialized
+    pub value: Option<Spanned<Expr>>,
+    pub link_attr: LinkAttr,
+    pub span: Span,
+    pub name_span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobalKind {
+    /// `static name = <expr>;`, a mutable qword
+    Static,
+    /// `const NAME = <expr>;`, inlined at every use
+    Const,
+}
+
+/// A record of qword sized fields, `struct Node { value, next };`
+#[derive(Debug)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub field_spans: Vec<Span>,
+    pub link_attr: LinkAttr,
+    pub span: Span,
+    pub name_span: Span,
+}
+
+impl Struct {
+    /// size in bytes
+    pub fn size(&self) -> i64 {
+        self.fields.len() as i64 * 8
+    }
+}
+
+/// Field access `p->next`, or `p->Node::next` to name the struct explicitly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldRef {
+    pub strukt: Option<String>,
+    pub name: String,
+}
+
+impl FieldRef {
+    /// resolves the byte offset of the field among structs, given as name and fields
+    pub fn resolve<'s>(
+        &self,
+        structs: impl IntoIterator<Item = (&'s str, &'s [String])>,
+    ) -> Result<i64, AstErr<'static>> {
+        let offset_of = |fields: &[String]| {
+            fields
+                .iter()
+                .position(|f| *f == self.name)
+                .map(|i| i as i64 * 8)
+        };
+        let structs = structs.into_iter().collect::<Vec<_>>();
+        if let Some(strukt) = &self.strukt
+            && !structs.iter().any(|(name, _)| name == strukt)
+        {
+            return Err(AstErr::UnknownStruct {
+                name: strukt.clone(),
+            });
+        }
+
+        let mut candidates = structs
+            .into_iter()
+            .filter(|(name, _)| self.strukt.as_ref().is_none_or(|strukt| strukt == name))
+            .filter_map(|(name, fields)| Some((name, offset_of(fields)?)));
+
+        match (candidates.next(), candidates.next()) {
+            (Some((_, offset)), None) => Ok(offset),
+            (None, _) => Err(AstErr::UnknownField {
+                field: self.to_string(),
+            }),
+            (Some((first, _)), Some((second, _))) => Err(AstErr::AmbiguousField {
+                field: self.name.clone(),
+                structs: [first, second]
+                    .into_iter()
+                    .chain(candidates.map(|(name, _)| name))
+                    .map(str::to_string)
+                    .collect(),
+            }),
+        }
+    }
+}
+
+impl Display for FieldRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.strukt {
+            Some(strukt) => write!(f, "{}::{}", strukt, self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Value of a constant expression
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConstValue {
+    Int(i64),
+    /// pointer to a string literal
+    Str(Vec<u8>),
+    /// pointer to a buffer of len qwords
+    Array {
+        init: i64,
+        len: usize,
+    },
+}
+
 #[derive(Debug)]
 pub struct Function {
     pub name: String,
-    pub body: Option<Vec<Line>>,
+    pub body: Option<Vec<Spanned<Line>>>,
     pub args: Vec<String>,
+    pub arg_spans: Vec<Span>,
+    /// annotated type of each argument
+    pub arg_types: Vec<Option<Type>>,
+    /// annotated return type
+    pub ret: Option<Type>,
+    pub arity: Arity,
     pub link_attr: LinkAttr,
+    /// the `##` doc comments directly above the function, or without them the plain `#` comments
+    /// directly above it up to the previous blank line
+    pub doc: Option<String>,
+    /// the `cfg` condition the function is compiled under
+    pub cfg: Option<Spanned<Expr>>,
+    /// from the first attribute to `end_def`. Imported declarations span their import
+    pub span: Span,
+    pub name_span: Span,
 }
 
 impl Function {
     pub fn body(&self) -> Option<impl Iterator<Item = &Line>> {
+        self.body.as_ref().map(|b| b.iter().map(|line| &line.inner))
+    }
+
+    pub fn spanned_body(&self) -> Option<impl Iterator<Item = &Spanned<Line>>> {
         self.body.as_ref().map(|b| b.iter())
     }
 }
 
+/// Static type of a value. Values without a type annotation are unchecked
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    I64,
+    U64,
+    U8,
+    Ptr(Box<Type>),
+}
+
+impl Type {
+    /// size in bytes of a value of this type in memory
+    pub fn size(&self) -> i64 {
+        match self {
+            Self::U8 => 1,
+            Self::I64 | Self::U64 | Self::Ptr(_) => 8,
+        }
+    }
+
+    pub fn is_int(&self) -> bool {
+        matches!(self, Self::I64 | Self::U64 | Self::U8)
+    }
+
+    /// whether comparisons of this type are unsigned. `u8` values always fit an i64 and compare as signed
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, Self::U64 | Self::Ptr(_))
+    }
+
+    pub fn pointee(&self) -> Option<&Type> {
+        match self {
+            Self::Ptr(inner) => Some(inner),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::I64 => write!(f, "i64"),
+            Self::U64 => write!(f, "u64"),
+            Self::U8 => write!(f, "u8"),
+            Self::Ptr(inner) => write!(f, "ptr<{}>", inner),
+        }
+    }
+}
+
 #[derive(PartialEq, Eq)]
 pub enum Line {
-    Expr(Expr),
-    Decl(LValue, Expr),
-    Call(String, Vec<Expr>, Option<LValue>),
-    Cond(Expr, Box<Line>),
+    Expr(Spanned<Expr>),
+    /// `<lvalue> [: <type>] = <expr>;`
+    Decl(Spanned<LValue>, Option<Type>, Spanned<Expr>),
+    Call(Spanned<String>, Vec<Spanned<Expr>>, Option<Spanned<LValue>>),
+    Cond(Spanned<Expr>, Box<Spanned<Line>>),
     Malformed,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Expr {
     Val(Val),
-    Op(Box<Expr>, Operation, Box<Expr>),
+    Op(Box<Spanned<Expr>>, Operation, Box<Spanned<Expr>>),
+    /// `[<init>; <len>]`, a buffer of len qwords initialized to init. Evaluates to a pointer to the buffer
+    Array(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// `<ptr>-><field>`, loads the field of the struct ptr points to
+    Field(Box<Spanned<Expr>>, FieldRef),
+    /// `sizeof(<struct>)`, the size of a struct in bytes
+    SizeOf(String),
+    /// `load8(<ptr>)`, loads a value narrower than a qword and widens it
+    Load(Box<Spanned<Expr>>, Width, Extend),
     Malformed,
 }
 
+/// Size of a memory access
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Width {
+    Byte,
+    Word,
+    Dword,
+    Qword,
+}
+
+impl Width {
+    pub fn bits(&self) -> usize {
+        match self {
+            Self::Byte => 8,
+            Self::Word => 16,
+            Self::Dword => 32,
+            Self::Qword => 64,
+        }
+    }
+}
+
+/// How a value narrower than a qword is widened when loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Extend {
+    Zero,
+    Sign,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LValue {
     Variable(String),
-    Deref(Box<LValue>),
+    Deref(Box<Spanned<LValue>>),
+    Field(Box<Spanned<LValue>>, FieldRef),
     Malformed,
 }
 
+impl LValue {
+    /// the expression evaluating to the current value of the lvalue
+    pub fn as_expr(&self) -> Expr {
+        let spanned =
+            |inner: &Spanned<Self>| Box::new(Spanned::new(inner.as_expr(), inner.span.clone()));
+        match self {
+            Self::Variable(name) => Expr::Val(Val::Var(name.clone())),
+            Self::Deref(inner) => Expr::Op(
+                Box::new(Spanned::new(Expr::Val(Val::V(0)), Span::default())),
+                Operation::Load,
+                spanned(inner),
+            ),
+            Self::Field(inner, field) => Expr::Field(spanned(inner), field.clone()),
+            Self::Malformed => Expr::Malformed,
+        }
+    }
+}
+
 impl Display for LValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Variable(v) => write!(f, "{v}"),
-            Self::Deref(val) => write!(f, "*{}", *val),
+            Self::Deref(val) => write!(f, "*{}", val.inner),
+            Self::Field(val, field) => write!(f, "{}->{}", val.inner, field),
             Self::Malformed => write!(f, "malformed"),
         }
     }
@@ -131,15 +572,34 @@ pub enum Operation {
     Load,
     AsRef,
     Not,
+    /// unary `-`
+    Neg,
+    /// unary `~`
+    BitNot,
     Gt,
     Lt,
+    Ge,
+    Le,
     EqEq,
     NEq,
+    /// short-circuiting `&&`
+    LogicalAnd,
+    /// short-circuiting `||`
+    LogicalOr,
     BitAND,
     BitOR,
     BitXOR,
     Shr,
+    /// arithmetic shift right `>>>`
+    Sar,
     Shl,
+    /// unsigned variants of the comparisons and division, only produced by codegen for `u64` and pointer operands
+    UGt,
+    ULt,
+    UGe,
+    ULe,
+    UDiv,
+    UMod,
     Malformed,
 }
 
@@ -147,7 +607,8 @@ pub enum Operation {
 pub enum Val {
     Var(String),
     V(i64),
-    Lit(String),
+    /// the bytes of a string literal
+    Lit(Vec<u8>),
     Malformed,
 }
 
@@ -160,7 +621,20 @@ impl Debug for Line {
 impl Display for Function {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "fn {}(", self.name)?;
-        writeln!(f, "{}) {{", self.args.join(","))?;
+        let args = self
+            .args
+            .iter()
+            .zip(&self.arg_types)
+            .map(|(arg, ty)| match ty {
+                Some(ty) => format!("{}: {}", arg, ty),
+                None => arg.clone(),
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{})", args.join(","))?;
+        if let Some(ret) = &self.ret {
+            write!(f, " -> {}", ret)?;
+        }
+        writeln!(f, " {{")?;
         if let Some(body) = self.body() {
             for line in body {
                 writeln!(f, "{};", line)?;
@@ -175,7 +649,7 @@ impl Display for Val {
         match self {
             Self::Var(i) => write!(f, "{}", i),
             Self::V(v) => write!(f, "{}", v),
-            Self::Lit(lit) => write!(f, "{}", lit),
+            Self::Lit(lit) => write!(f, "{}", String::from_utf8_lossy(lit)),
             Self::Malformed => write!(f, "malformed"),
         }
     }
@@ -192,13 +666,26 @@ impl Display for Operation {
             Self::Load => write!(f, "*"),
             Self::AsRef => write!(f, "&"),
             Self::Not => write!(f, "!"),
+            Self::Neg => write!(f, "-"),
+            Self::BitNot => write!(f, "~"),
             Self::Gt => write!(f, ">"),
             Self::Lt => write!(f, "<"),
+            Self::Ge => write!(f, ">="),
+            Self::Le => write!(f, "<="),
             Self::EqEq => write!(f, "=="),
+            Self::LogicalAnd => write!(f, "&&"),
+            Self::LogicalOr => write!(f, "||"),
             Self::BitAND => write!(f, "&"),
             Self::BitOR => write!(f, "|"),
             Self::BitXOR => write!(f, "^"),
             Self::Shr => write!(f, ">>"),
+            Self::Sar => write!(f, ">>>"),
+            Self::UGt => write!(f, ">"),
+            Self::ULt => write!(f, "<"),
+            Self::UGe => write!(f, ">="),
+            Self::ULe => write!(f, "<="),
+            Self::UDiv => write!(f, "/"),
+            Self::UMod => write!(f, "%"),
             Self::Shl => write!(f, "<<"),
             Self::NEq => write!(f, "!="),
             Self::Malformed => write!(f, "malformed"),
@@ -210,7 +697,14 @@ impl Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Self::Val(v) => write!(f, "{}", v),
-            Self::Op(lhs, op, rhs) => write!(f, "({} {} {})", lhs.as_ref(), op, rhs.as_ref()),
+            Self::Op(lhs, op, rhs) => write!(f, "({} {} {})", lhs.inner, op, rhs.inner),
+            Self::Array(init, len) => write!(f, "[{}; {}]", init.inner, len.inner),
+            Self::Field(ptr, field) => write!(f, "{}->{}", ptr.inner, field),
+            Self::SizeOf(name) => write!(f, "sizeof({})", name),
+            Self::Load(ptr, width, extend) => {
+                let signed = if *extend == Extend::Sign { "i" } else { "" };
+                write!(f, "load{}{}({})", signed, width.bits(), ptr.inner)
+            }
             Self::Malformed => write!(f, "malformed"),
         }
     }
@@ -219,28 +713,51 @@ impl Display for Expr {
 impl Display for Line {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Self::Expr(e) => write!(f, "{}", e),
-            Self::Decl(i, e) => write!(f, "declare {} = {}", i, e),
+            Self::Expr(e) => write!(f, "{}", e.inner),
+            Self::Decl(i, None, e) => write!(f, "declare {} = {}", i.inner, e.inner),
+            Self::Decl(i, Some(ty), e) => write!(f, "declare {}: {} = {}", i.inner, ty, e.inner),
             Self::Call(i, e, ret) => write!(
                 f,
                 "call {} {} : {:?}",
-                i,
+                i.inner,
                 e.iter()
                     .map(|ele| ele.to_string())
                     .collect::<Vec<_>>()
                     .join(","),
-                ret
+                ret.as_ref().map(|ret| &ret.inner)
             ),
-            Self::Cond(c, e) => write!(f, "if {}; {}", c, e),
+            Self::Cond(c, e) => write!(f, "if {}; {}", c.inner, e.inner),
             Self::Malformed => write!(f, "malformed"),
         }
     }
 }
 
+impl Display for Global {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            GlobalKind::Static => "static",
+            GlobalKind::Const => "const",
+        };
+        write!(f, "{} {}", kind, self.name)?;
+        if let Some(value) = &self.value {
+            write!(f, " = {}", value.inner)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Struct {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "struct {} {{ {} }}", self.name, self.fields.join(", "))
+    }
+}
+
 impl Display for Item {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Function(func) => write!(f, "{}", func),
+            Self::Global(global) => write!(f, "{}", global),
+            Self::Struct(strukt) => write!(f, "{}", strukt),
             Self::Malformed => write!(f, "malformed"),
         }
     }
@@ -278,4 +795,84 @@ mod tests {
             "fn main() {\ndeclare x = (1 + 2);\ncall print (x * (5 + 2)) : None;\ndeclare y = (x / (3 + 2));\ndeclare k = (x + ((y / 5) * 4));\n};\n"
         )
     }
+
+    #[test]
+    fn unary() {
+        let s = "
+            begin_def main;
+          x = -1 - -x * ~2;
+          y = -9223372036854775808;
+          z = 9223372036854775808;
+          a = 0xffff_ffff_ffff_ffff + -0x8000_0000_0000_0000;
+          b = 0x1_0000_0000_0000_0000;
+          end_def
+        ";
+        let mut stream = TokenStream::from_str(s).unwrap();
+        let (ast, diagnostics) = Ast::from_stream(&mut stream, &CfgEnv::default());
+        assert_eq!(
+            format!("{}", ast),
+            "fn main() {\ndeclare x = (-1 - ((0 - x) * (0 ~ 2)));\ndeclare y = -9223372036854775808;\ndeclare z = malformed;\ndeclare a = (-1 + -9223372036854775808);\ndeclare b = malformed;\n};\n"
+        );
+        let errs = diagnostics
+            .errs
+            .into_iter()
+            .map(|e| e.inner)
+            .collect::<Vec<_>>();
+        assert_eq!(errs, vec![AstErr::IntegerOverflow, AstErr::IntegerOverflow]);
+    }
+
+    #[test]
+    fn doc() {
+        let s = "
+            ## adds two numbers
+            ##   a and b
+            cfg test;
+            public begin_def add a, b;
+            end_def # not a doc comment
+
+            # subtracts b from a
+            begin_def sub a, b;
+            end_def
+            # not a doc comment
+
+            begin_def mul a, b;
+            end_def
+        ";
+        let mut stream = TokenStream::from_str(s).unwrap();
+        let (ast, _) = Ast::from_stream(&mut stream, &CfgEnv::default().populate(&["test".into()]));
+        let doc = |name| ast.get_func(name).unwrap().doc.clone();
+        assert_eq!(doc("add"), Some("adds two numbers\n  a and b".into()));
+        assert_eq!(doc("sub"), Some("subtracts b from a".into()));
+        assert_eq!(doc("mul"), None);
+    }
+
+    #[test]
+    fn attributed_global() {
+        let s = "
+            link_attr section mysec;
+            static counter = 3;
+            begin_def main;
+            return counter;
+            end_def
+        ";
+        let mut stream = TokenStream::from_str(s).unwrap();
+        let (ast, diagnostics) = Ast::from_stream(&mut stream, &CfgEnv::default());
+        assert!(diagnostics.errs.is_empty());
+        assert_eq!(
+            ast.get_global("counter").unwrap().link_attr.section,
+            "mysec"
+        );
+        assert_ne!(ast.get_func("main").unwrap().link_attr.section, "mysec");
+    }
+
+    #[test]
+    fn duplicate_field() {
+        let s = "struct Node { value, next, value };";
+        let mut stream = TokenStream::from_str(s).unwrap();
+        let (ast, diagnostics) = Ast::from_stream(&mut stream, &CfgEnv::default());
+        assert_eq!(ast.get_struct("Node").unwrap().fields, ["value", "next"]);
+        assert_eq!(diagnostics.errs.len(), 1);
+        assert_eq!(diagnostics.errs[0].inner.code(), "E0034");
+        assert_eq!(diagnostics.errs[0].span.start, s.rfind("value").unwrap());
+    }
 }
@@ -0,0 +1,230 @@
+use crate::frontend::{
+    ast::{Ast, Expr, Function, Import, Item, LValue, Line, error::Spanned},
+    lexer::Span,
+};
+
+/// A node of the ast, as found by [Ast::nodes_at]
+#[derive(Debug, Clone, Copy)]
+pub enum Node<'a> {
+    Import(&'a Spanned<Import>),
+    Item(&'a Item),
+    /// the name of a function, global or struct
+    ItemName(&'a Item),
+    /// the argument of a function at the index
+    Arg(&'a Function, usize),
+    /// the field of a struct at the index
+    StructField(&'a Item, usize),
+    Line(&'a Spanned<Line>),
+    /// the called function of a `Line::Call`
+    Callee(&'a Spanned<String>),
+    Expr(&'a Spanned<Expr>),
+    LValue(&'a Spanned<LValue>),
+}
+
+impl Node<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Import(import) => import.span.clone(),
+            Self::Item(item) => item.span().unwrap_or_default(),
+            Self::ItemName(item) => item.name_span().unwrap_or_default(),
+            Self::Arg(func, i) => func.arg_spans[*i].clone(),
+            Self::StructField(Item::Struct(strukt), i) => strukt.field_spans[*i].clone(),
+            Self::StructField(..) => Span::default(),
+            Self::Line(line) => line.span.clone(),
+            Self::Callee(name) => name.span.clone(),
+            Self::Expr(expr) => expr.span.clone(),
+            Self::LValue(lvalue) => lvalue.span.clone(),
+        }
+    }
+}
+
+impl Item {
+    /// the span of the whole item. None for malformed items
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Function(func) => Some(func.span.clone()),
+            Self::Global(global) => Some(global.span.clone()),
+            Self::Struct(strukt) => Some(strukt.span.clone()),
+            Self::Malformed => None,
+        }
+    }
+
+    pub fn name_span(&self) -> Option<Span> {
+        match self {
+            Self::Function(func) => Some(func.name_span.clone()),
+            Self::Global(global) => Some(global.name_span.clone()),
+            Self::Struct(strukt) => Some(strukt.name_span.clone()),
+            Self::Malformed => None,
+        }
+    }
+}
+
+impl Ast {
+    /// the nodes containing the byte offset, from the item down to the innermost node
+    pub fn nodes_at(&self, offset: usize) -> Vec<Node<'_>> {
+        let mut nodes = Vec::new();
+        if let Some(import) = self.imports().find(|import| import.span.contains(offset)) {
+            nodes.push(Node::Import(import));
+            return nodes;
+        }
+        let Some(item) = self
+            .funcs()
+            .find(|item| item.span().is_some_and(|span| span.contains(offset)))
+        else {
+            return nodes;
+        };
+        nodes.push(Node::Item(item));
+        if item.name_span().is_some_and(|span| span.contains(offset)) {
+            nodes.push(Node::ItemName(item));
+            return nodes;
+        }
+        match item {
+            Item::Function(func) => {
+                if let Some(i) = func.arg_spans.iter().position(|span| span.contains(offset)) {
+                    nodes.push(Node::Arg(func, i));
+                } else if let Some(cfg) = &func.cfg
+                    && cfg.span.contains(offset)
+                {
+                    expr_nodes(cfg, offset, &mut nodes);
+                } else if let Some(line) = func
+                    .spanned_body()
+                    .into_iter()
+                    .flatten()
+                    .find(|line| line.span.contains(offset))
+                {
+                    line_nodes(line, offset, &mut nodes);
+                }
+            }
+            Item::Global(global) => {
+                if let Some(value) = &global.value
+                    && value.span.contains(offset)
+                {
+                    expr_nodes(value, offset, &mut nodes);
+                }
+            }
+            Item::Struct(strukt) => {
+                if let Some(i) = strukt
+                    .field_spans
+                    .iter()
+                    .position(|span| span.contains(offset))
+                {
+                    nodes.push(Node::StructField(item, i));
+                }
+            }
+            Item::Malformed => {}
+        }
+        nodes
+    }
+
+    /// the innermost node containing the byte offset
+    pub fn node_at(&self, offset: usize) -> Option<Node<'_>> {
+        self.nodes_at(offset).pop()
+    }
+}
+
+fn line_nodes<'a>(line: &'a Spanned<Line>, offset: usize, nodes: &mut Vec<Node<'a>>) {
+    nodes.push(Node::Line(line));
+    match &line.inner {
+        Line::Expr(expr) => expr_nodes(expr, offset, nodes),
+        Line::Decl(lvalue, _, expr) => {
+            if lvalue.span.contains(offset) {
+                lvalue_nodes(lvalue, offset, nodes);
+            } else if expr.span.contains(offset) {
+                expr_nodes(expr, offset, nodes);
+            }
+        }
+        Line::Call(name, args, ret) => {
+            if name.span.contains(offset) {
+                nodes.push(Node::Callee(name));
+            } else if let Some(arg) = args.iter().find(|arg| arg.span.contains(offset)) {
+                expr_nodes(arg, offset, nodes);
+            } else if let Some(ret) = ret
+                && ret.span.contains(offset)
+            {
+                lvalue_nodes(ret, offset, nodes);
+            }
+        }
+        Line::Cond(cond, then) => {
+            if cond.span.contains(offset) {
+                expr_nodes(cond, offset, nodes);
+            } else if then.span.contains(offset) {
+                line_nodes(then, offset, nodes);
+            }
+        }
+        Line::Malformed => {}
+    }
+}
+
+fn expr_nodes<'a>(expr: &'a Spanned<Expr>, offset: usize, nodes: &mut Vec<Node<'a>>) {
+    nodes.push(Node::Expr(expr));
+    let child = match &expr.inner {
+        Expr::Op(lhs, _, rhs) | Expr::Array(lhs, rhs) => [lhs, rhs]
+            .into_iter()
+            .find(|child| child.span.contains(offset)),
+        Expr::Field(ptr, _) | Expr::Load(ptr, ..) => {
+            Some(ptr).filter(|ptr| ptr.span.contains(offset))
+        }
+        Expr::Val(_) | Expr::SizeOf(_) | Expr::Malformed => None,
+    };
+    if let Some(child) = child {
+        expr_nodes(child, offset, nodes);
+    }
+}
+
+fn lvalue_nodes<'a>(lvalue: &'a Spanned<LValue>, offset: usize, nodes: &mut Vec<Node<'a>>) {
+    nodes.push(Node::LValue(lvalue));
+    if let LValue::Deref(inner) | LValue::Field(inner, _) = &lvalue.inner
+        && inner.span.contains(offset)
+    {
+        lvalue_nodes(inner, offset, nodes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::{ast::cfg::CfgEnv, get_ast};
+
+    #[test]
+    fn nodes_at() {
+        let s = "
+            struct Node { value, next };
+            begin_def main a;
+            x = a + (p->Node::next) * 2;
+            if x; print x, 1 : *r;
+            end_def
+        ";
+        let (ast, _) = get_ast(s, &CfgEnv::default());
+        let at = |needle: &str| s.find(needle).unwrap();
+        let texts = |offset| {
+            ast.nodes_at(offset)
+                .iter()
+                .map(|node| &s[node.span().start..node.span().end])
+                .collect::<Vec<_>>()
+        };
+
+        assert!(matches!(ast.node_at(at("a;")), Some(Node::Arg(_, 0))));
+        assert!(matches!(
+            ast.node_at(at("next }")),
+            Some(Node::StructField(_, 1))
+        ));
+        assert!(matches!(ast.node_at(at("main")), Some(Node::ItemName(_))));
+        assert_eq!(
+            texts(at("::next")),
+            vec![
+                &s[at("begin_def")..at("end_def") + "end_def".len()],
+                "x = a + (p->Node::next) * 2;",
+                "a + (p->Node::next) * 2",
+                "(p->Node::next) * 2",
+                "(p->Node::next)",
+            ]
+        );
+        assert_eq!(texts(at("p->")).last(), Some(&"p"));
+        assert!(matches!(ast.node_at(at("print")), Some(Node::Callee(_))));
+        assert_eq!(
+            texts(at("r;"))[1..],
+            ["if x; print x, 1 : *r;", "print x, 1 : *r;", "*r", "r"]
+        );
+        assert!(ast.node_at(0).is_none());
+    }
+}
@@ -1,10 +1,11 @@
 use crate::{
     frontend::{
         ast::{
-            Expr, Operation, Val,
+            Expr, FieldRef, Operation, Type, Val,
             error::{AstErr, Diagnostics, Spanned},
+            load_kind,
         },
-        lexer::{Token, TokenStream},
+        lexer::{Span, Token, TokenStream},
     },
     unclosed_block, unexpected,
 };
@@ -13,10 +14,74 @@ pub fn parse_expr<'a>(
     stream: &mut TokenStream<'a>,
     min_bp: f32,
     diagnostics: &mut Diagnostics<'a>,
-) -> Expr {
+) -> Spanned<Expr> {
     let anchor = stream.peek().span.clone();
-    let mut lhs = match stream.peek().as_ref() {
-        Token::Ident(_) | Token::Lit(_) | Token::Number(_) => {
+    let malformed =
+        |stream: &TokenStream| Spanned::new(Expr::Malformed, stream.span_from(anchor.start));
+    let inner = match stream.peek().as_ref() {
+        Token::Keyword("sizeof") => {
+            stream.advance();
+            let name = match (
+                stream.peek().as_ref(),
+                stream.peekn(1).as_ref(),
+                stream.peekn(2).as_ref(),
+            ) {
+                (Token::OpenParen, Token::Ident(name), Token::CloseParen) => name.to_string(),
+                _ => {
+                    unexpected!(
+                        diagnostics,
+                        [
+                            Token::OpenParen,
+                            Token::Ident("<struct>"),
+                            Token::CloseParen
+                        ],
+                        stream.peek().clone(),
+                        anchor.clone().merge(stream.last_span.clone())
+                    );
+                    return malformed(stream);
+                }
+            };
+            stream.advance();
+            stream.advance();
+            stream.advance();
+            Expr::SizeOf(name)
+        }
+        Token::Ident(name)
+            if *stream.peekn(1).as_ref() == Token::OpenParen
+                && let Some((width, extend)) = load_kind(name) =>
+        {
+            stream.advance();
+            stream.advance();
+            let ptr = parse_expr(stream, 0., diagnostics);
+            if ptr.inner == Expr::Malformed {
+                return ptr;
+            }
+            if *stream.peek().as_ref() != Token::CloseParen {
+                unclosed_block!(
+                    diagnostics,
+                    [Token::CloseParen],
+                    stream.peek().clone(),
+                    anchor.clone().merge(stream.last_span.clone())
+                );
+                return malformed(stream);
+            }
+            stream.advance();
+            Expr::Load(Box::new(ptr), width, extend)
+        }
+        // a negated literal is a single value, so that the smallest i64 can be written
+        Token::Sub
+            if let num @ (Token::Number(_) | Token::RadixNumber(_)) = stream.peekn(1).as_ref() =>
+        {
+            let num = num.clone();
+            stream.advance();
+            stream.advance();
+            let span = Span {
+                start: anchor.start,
+                end: stream.last_span.end,
+            };
+            Expr::Val(Val::int(&num, true, span, diagnostics))
+        }
+        Token::Ident(_) | Token::Lit(_) | Token::Number(_) | Token::RadixNumber(_) => {
             Expr::Val(Val::parse(stream, diagnostics))
         }
         Token::Semi | Token::Comma | Token::EOF | Token::CloseParen => {
@@ -26,21 +91,56 @@ pub fn parse_expr<'a>(
                     Token::Star,
                     Token::Ampercent,
                     Token::Not,
+                    Token::Sub,
+                    Token::Tilde,
                     Token::OpenParen,
+                    Token::OpenBracket,
                     Token::Ident("<ident>"),
-                    Token::Lit("<strlit>"),
+                    Token::Lit("<strlit>".into()),
                     Token::Number(0),
                 ],
                 stream.peek().clone(),
                 stream.last_span.clone()
             );
 
-            return Expr::Malformed;
+            return malformed(stream);
+        }
+        Token::OpenBracket => {
+            stream.advance();
+            let init = parse_expr(stream, 0., diagnostics);
+            if init.inner == Expr::Malformed {
+                return init;
+            }
+            if *stream.peek().as_ref() != Token::Semi {
+                unexpected!(
+                    diagnostics,
+                    [Token::Semi],
+                    stream.peek().clone(),
+                    anchor.clone().merge(stream.last_span.clone())
+                );
+                return malformed(stream);
+            }
+            stream.advance();
+            let len = parse_expr(stream, 0., diagnostics);
+            if len.inner == Expr::Malformed {
+                return len;
+            }
+            if *stream.peek().as_ref() != Token::CloseBracket {
+                unclosed_block!(
+                    diagnostics,
+                    [Token::CloseBracket],
+                    stream.peek().clone(),
+                    anchor.clone().merge(stream.last_span.clone())
+                );
+                return malformed(stream);
+            }
+            stream.advance();
+            Expr::Array(Box::new(init), Box::new(len))
         }
         Token::OpenParen => {
             stream.advance();
             let lhs = parse_expr(stream, 0., diagnostics);
-            if lhs == Expr::Malformed {
+            if lhs.inner == Expr::Malformed {
                 return lhs;
             }
             if let Token::CloseParen = stream.peek().as_ref() {
@@ -50,20 +150,22 @@ pub fn parse_expr<'a>(
                     diagnostics,
                     [Token::CloseParen],
                     stream.peek().clone(),
-                    anchor.merge(stream.last_span.clone())
+                    anchor.clone().merge(stream.last_span.clone())
                 );
-                return Expr::Malformed;
+                return malformed(stream);
             }
-            lhs
+            // the parenthesized expression spans its parens
+            lhs.inner
         }
         _tok => {
             if let Some(op) = Operation::try_from_token_as_single(stream.peek()) {
                 stream.advance();
                 let rhs = parse_expr(stream, op.infix_power().0, diagnostics);
-                if rhs == Expr::Malformed {
-                    return Expr::Malformed;
+                if rhs.inner == Expr::Malformed {
+                    return rhs;
                 }
-                Expr::Op(Box::new(Expr::Val(Val::V(0))), op, Box::new(rhs))
+                let zero = Spanned::new(Expr::Val(Val::V(0)), anchor.clone());
+                Expr::Op(Box::new(zero), op, Box::new(rhs))
             } else {
                 unexpected!(
                     diagnostics,
@@ -71,18 +173,31 @@ pub fn parse_expr<'a>(
                         Token::Star,
                         Token::Ampercent,
                         Token::Not,
+                        Token::Sub,
+                        Token::Tilde,
                         Token::Ident(""),
-                        Token::Lit(""),
+                        Token::Lit("".into()),
                         Token::Number(0),
                     ],
                     stream.peek().clone(),
-                    anchor.merge(stream.last_span.clone())
+                    anchor.clone().merge(stream.last_span.clone())
                 );
 
-                return Expr::Malformed;
+                return malformed(stream);
             }
         }
     };
+    let mut lhs = Spanned::new(inner, stream.span_from(anchor.start));
+
+    while *stream.peek().as_ref() == Token::Arrow {
+        let Some(field) = FieldRef::parse(stream, diagnostics) else {
+            return malformed(stream);
+        };
+        lhs = Spanned::new(
+            Expr::Field(Box::new(lhs), field),
+            stream.span_from(anchor.start),
+        );
+    }
 
     while let Some(op) = Operation::try_from_token(stream.peek()) {
         let (l, r) = op.infix_power();
@@ -91,40 +206,56 @@ pub fn parse_expr<'a>(
         }
         stream.advance();
         let rhs = parse_expr(stream, l, diagnostics);
-        if rhs == Expr::Malformed {
-            return Expr::Malformed;
+        if rhs.inner == Expr::Malformed {
+            return rhs;
         }
-        lhs = Expr::Op(Box::new(lhs), op, Box::new(rhs));
+        lhs = Spanned::new(
+            Expr::Op(Box::new(lhs), op, Box::new(rhs)),
+            stream.span_from(anchor.start),
+        );
     }
     lhs
 }
 
 impl Operation {
-    fn infix_power(&self) -> (f32, f32) {
+    pub(crate) fn infix_power(&self) -> (f32, f32) {
         match self {
             Self::Load | Self::AsRef => (4., 4.1),
-            Self::Not => (3.5, 3.6),
-            Self::Mul | Self::Div | Self::Mod => (3.1, 3.),
+            Self::Not | Self::Neg | Self::BitNot => (3.5, 3.6),
+            Self::Mul | Self::Div | Self::Mod | Self::UDiv | Self::UMod => (3.1, 3.),
             Self::Sub | Self::Add => (2., 2.1),
-            Self::Shr | Self::Shl => (1.8, 1.9),
+            Self::Shr | Self::Sar | Self::Shl => (1.8, 1.9),
             Self::BitAND => (1.6, 1.7),
             Self::BitXOR => (1.5, 1.6),
             Self::BitOR => (1.4, 1.5),
-            Self::Gt | Self::Lt | Self::EqEq | Self::NEq => (1., 1.1),
+            Self::Gt
+            | Self::Lt
+            | Self::Ge
+            | Self::Le
+            | Self::UGt
+            | Self::ULt
+            | Self::UGe
+            | Self::ULe
+            | Self::EqEq
+            | Self::NEq => (1., 1.1),
+            Self::LogicalAnd => (0.8, 0.9),
+            Self::LogicalOr => (0.6, 0.7),
             Self::Malformed => (0., 0.1),
         }
     }
 
-    fn try_from_token_as_single<'a>(token: &Spanned<Token<'a>>) -> Option<Self> {
+    pub(crate) fn try_from_token_as_single<'a>(token: &Spanned<Token<'a>>) -> Option<Self> {
         Some(match token.as_ref() {
             Token::Star => Self::Load,
             Token::Ampercent => Self::AsRef,
             Token::Not => Self::Not,
+            Token::Sub => Self::Neg,
+            Token::Tilde => Self::BitNot,
             _tok => return None,
         })
     }
 
-    fn try_from_token<'a>(token: &Spanned<Token<'a>>) -> Option<Self> {
+    pub(crate) fn try_from_token<'a>(token: &Spanned<Token<'a>>) -> Option<Self> {
         Some(match token.as_ref() {
             Token::Star => Self::Mul,
             Token::Add => Self::Add,
@@ -135,9 +266,14 @@ impl Operation {
             Token::Or => Self::BitOR,
             Token::Hat => Self::BitXOR,
             Token::Shr => Self::Shr,
+            Token::Sar => Self::Sar,
             Token::Shl => Self::Shl,
             Token::Gt => Self::Gt,
             Token::Lt => Self::Lt,
+            Token::Ge => Self::Ge,
+            Token::Le => Self::Le,
+            Token::AndAnd => Self::LogicalAnd,
+            Token::OrOr => Self::LogicalOr,
             Token::EqEq => Self::EqEq,
             Token::NEq => Self::NEq,
             _tok => return None,
@@ -145,16 +281,154 @@ impl Operation {
     }
 }
 
+impl Type {
+    /// whether the tokens at the cursor start a type, i.e. `i64`, `u64`, `u8` or `ptr<`
+    pub(super) fn is_next(stream: &TokenStream, at: i64) -> bool {
+        match stream.peekn(at).as_ref() {
+            Token::Ident("i64" | "u64" | "u8") => true,
+            Token::Ident("ptr") => *stream.peekn(at + 1).as_ref() == Token::Lt,
+            _ => false,
+        }
+    }
+
+    /// parses `i64`, `u64`, `u8` or `ptr<type>`
+    pub(super) fn parse<'a>(
+        stream: &mut TokenStream<'a>,
+        diagnostics: &mut Diagnostics<'a>,
+    ) -> Option<Self> {
+        let mut pending_close = 0;
+        let ty = Self::parse_inner(stream, &mut pending_close, diagnostics)?;
+        if pending_close > 0 {
+            // the `>>` or `>>>` closed more `ptr<` than were opened
+            unexpected!(
+                diagnostics,
+                [Token::Gt],
+                stream.peekn(-1).clone(),
+                stream.last_span.clone()
+            );
+            return None;
+        }
+        Some(ty)
+    }
+
+    /// pending_close counts the outer pointers already closed by the `>>` or `>>>` of an inner one
+    fn parse_inner<'a>(
+        stream: &mut TokenStream<'a>,
+        pending_close: &mut usize,
+        diagnostics: &mut Diagnostics<'a>,
+    ) -> Option<Self> {
+        let anchor = stream.peek().span.clone();
+        let ty = match stream.peek().as_ref() {
+            Token::Ident("i64") => {
+                stream.advance();
+                return Some(Self::I64);
+            }
+            Token::Ident("u64") => {
+                stream.advance();
+                return Some(Self::U64);
+            }
+            Token::Ident("u8") => {
+                stream.advance();
+                return Some(Self::U8);
+            }
+            Token::Ident("ptr") if *stream.peekn(1).as_ref() == Token::Lt => {
+                stream.advance();
+                stream.advance();
+                Self::Ptr(Box::new(Self::parse_inner(
+                    stream,
+                    pending_close,
+                    diagnostics,
+                )?))
+            }
+            _ => {
+                unexpected!(
+                    diagnostics,
+                    [
+                        Token::Ident("i64"),
+                        Token::Ident("u64"),
+                        Token::Ident("u8"),
+                        Token::Ident("ptr")
+                    ],
+                    stream.peek().clone(),
+                    anchor
+                );
+                return None;
+            }
+        };
+
+        if *pending_close > 0 {
+            *pending_close -= 1;
+            return Some(ty);
+        }
+        match stream.peek().as_ref() {
+            Token::Gt => stream.advance(),
+            Token::Shr => {
+                stream.advance();
+                *pending_close = 1;
+            }
+            Token::Sar => {
+                stream.advance();
+                *pending_close = 2;
+            }
+            _ => {
+                unclosed_block!(
+                    diagnostics,
+                    [Token::Gt],
+                    stream.peek().clone(),
+                    anchor.merge(stream.last_span.clone())
+                );
+                return None;
+            }
+        }
+        Some(ty)
+    }
+}
+
+impl FieldRef {
+    /// parses `->field` or `->Struct::field`
+    pub(super) fn parse<'a>(
+        stream: &mut TokenStream<'a>,
+        diagnostics: &mut Diagnostics<'a>,
+    ) -> Option<Self> {
+        let anchor = stream.next().span.clone();
+        let mut path = Vec::new();
+        loop {
+            let Token::Ident(segment) = stream.peek().as_ref() else {
+                unexpected!(
+                    diagnostics,
+                    [Token::Ident("<field>")],
+                    stream.peek().clone(),
+                    anchor.merge(stream.last_span.clone())
+                );
+                return None;
+            };
+            path.push(segment.to_string());
+            stream.advance();
+            if path.len() == 2 || *stream.peek().as_ref() != Token::PathSep {
+                break;
+            }
+            stream.advance();
+        }
+        let name = path.pop().unwrap();
+        Some(Self {
+            strukt: path.pop(),
+            name,
+        })
+    }
+}
+
 impl Val {
     fn parse<'a>(stream: &mut TokenStream<'a>, diagnostics: &mut Diagnostics<'a>) -> Self {
         let zelf = match stream.peek().as_ref() {
             Token::Ident(t) => Self::Var(t.to_string()),
-            Token::Number(num) => Self::V(*num),
-            Token::Lit(t) => Self::Lit(t.to_string()),
+            num @ (Token::Number(_) | Token::RadixNumber(_)) => {
+                Self::int(num, false, stream.peek().span.clone(), diagnostics)
+            }
+            Token::Lit(t) => Self::Lit(t.to_vec()),
             _tok => {
                 unexpected!(
                     diagnostics,
-                    [Token::Ident("<ident>"), Token::Lit("<strlit>")],
+                    [Token::Ident("<ident>"), Token::Lit("<strlit>".into())],
                     stream.peek().clone(),
                     stream.last_span.clone()
                 );
@@ -164,4 +438,29 @@ impl Val {
         stream.advance();
         zelf
     }
+
+    /// an integer literal. Decimal literals must fit an i64 after applying the sign,
+    /// the bits of hex, octal and binary literals up to u64::MAX are reinterpreted as an i64
+    fn int(num: &Token, negative: bool, span: Span, diagnostics: &mut Diagnostics) -> Self {
+        let value = match *num {
+            Token::Number(num) if negative => 0i64.checked_sub_unsigned(num),
+            Token::Number(num) => i64::try_from(num).ok(),
+            Token::RadixNumber(bits) => u64::try_from(bits).ok().map(|bits| {
+                let value = bits as i64;
+                if negative {
+                    value.wrapping_neg()
+                } else {
+                    value
+                }
+            }),
+            _ => unreachable!("only number tokens are integer literals"),
+        };
+        match value {
+            Some(value) => Self::V(value),
+            None => {
+                diagnostics.errs.push(AstErr::IntegerOverflow.at(span));
+                Self::Malformed
+            }
+        }
+    }
 }
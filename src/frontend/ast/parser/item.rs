@@ -3,12 +3,14 @@ use indexmap::IndexMap;
 use crate::{
     frontend::{
         ast::{
-            Ast, Function, Item, Line, LinkAttr, LinkMeta,
+            Abi, Arity, Ast, Expr, Function, Global, GlobalKind, Import, Item, Line, LinkAttr,
+            LinkMeta, Struct, Type,
             cfg::CfgEnv,
-            error::{AstErr, Diagnostics},
+            error::{AstErr, Diagnostics, Spanned},
+            lint::{Level, Lint, LintAttr},
             parser::expr::parse_expr,
         },
-        lexer::{Token, TokenStream},
+        lexer::{Span, Token, TokenStream},
     },
     kw, skip_until, skip_until_or_over, unclosed_block, unexpected,
 };
@@ -16,15 +18,30 @@ use crate::{
 impl Ast {
     pub fn from_stream<'a>(s: &mut TokenStream<'a>, cfg_env: &CfgEnv) -> (Self, Diagnostics<'a>) {
         let mut diagnostics = Diagnostics::new();
+        diagnostics.errs.extend(
+            s.errs
+                .drain(..)
+                .map(|err| AstErr::Lex(err.inner).at(err.span)),
+        );
         let mut functions = IndexMap::new();
+        let mut imports = Vec::new();
+        let mut inactive = Vec::new();
         let mut skip_next = false;
+        // start of the current item including its cfg, doc comments above it belong to the item
+        let mut item_start = None;
+        let mut item_cfg = None;
+        let mut item_lints = Vec::new();
+        let mut lint_attrs = Vec::new();
         loop {
             let anchor = s.peek().span.clone();
             match s.peek().as_ref() {
                 Token::EOF => break,
                 Token::Keyword("cfg") => {
+                    item_start.get_or_insert(anchor.start);
                     s.advance();
-                    skip_next = !cfg_env.eval_cfg_expr(&parse_expr(s, 0., &mut diagnostics));
+                    let cfg = parse_expr(s, 0., &mut diagnostics);
+                    skip_next = !cfg_env.eval_cfg_expr(&cfg);
+                    item_cfg = Some(cfg);
                     if *s.peek().as_ref() != Token::Semi {
                         unexpected!(
                             diagnostics,
@@ -35,21 +52,102 @@ impl Ast {
                     }
                     skip_until_or_over!(s, kw!(Token::Semi), Token::Semi);
                 }
+                Token::Keyword("lint_attr") => {
+                    item_start.get_or_insert(anchor.start);
+                    s.advance();
+                    item_lints.extend(LintAttr::parse(s, &mut diagnostics));
+                    if *s.peek().as_ref() != Token::Semi {
+                        unexpected!(
+                            diagnostics,
+                            [Token::Semi],
+                            s.peek().clone(),
+                            anchor.clone().merge(s.last_span.clone())
+                        );
+                    }
+                    skip_until_or_over!(s, kw!(Token::Semi), Token::Semi);
+                }
+                Token::Keyword("import") | Token::Keyword("use") => {
+                    let import = Import::parse(s, &mut diagnostics);
+                    if skip_next {
+                        inactive.push(s.span_from(item_start.unwrap_or(anchor.start)));
+                    } else if let Some(import) = import {
+                        imports.push(Spanned {
+                            inner: import,
+                            span: Span {
+                                start: anchor.start,
+                                end: s.last_span.end,
+                            },
+                        });
+                    }
+                    if *s.peek().as_ref() != Token::Semi {
+                        unclosed_block!(
+                            diagnostics,
+                            [Token::Semi],
+                            s.peek().clone(),
+                            anchor.clone().merge(s.last_span.clone())
+                        );
+                    }
+                    skip_until_or_over!(s, kw!(Token::Semi), Token::Semi);
+                    let scope = s.span_from(item_start.unwrap_or(anchor.start));
+                    lint_attrs.extend(item_lints.drain(..).map(|(level, lint)| LintAttr {
+                        lint,
+                        level,
+                        scope: scope.clone(),
+                    }));
+                    skip_next = false;
+                    item_start = None;
+                    item_cfg = None;
+                }
                 Token::Keyword(kw)
-                    if matches!(*kw, "link_attr" | "begin_def" | "public" | "extern_def") =>
+                    if matches!(
+                        *kw,
+                        "link_attr"
+                            | "begin_def"
+                            | "public"
+                            | "extern_def"
+                            | "static"
+                            | "const"
+                            | "extern_static"
+                            | "struct"
+                    ) =>
                 {
+                    let start = item_start.take().unwrap_or(anchor.start);
+                    let cfg = item_cfg.take();
                     let mut item_diagnostic = Diagnostics::new();
                     let link_attr = LinkAttr::parse(s, &mut item_diagnostic);
 
-                    if let Some(f) = Function::parse(s, link_attr, cfg_env, &mut item_diagnostic)
+                    let item = if Global::is_next(s) {
+                        Global::parse(s, link_attr, &mut item_diagnostic).map(Item::Global)
+                    } else if Struct::is_next(s) {
+                        Struct::parse(s, link_attr, &mut item_diagnostic).map(Item::Struct)
+                    } else {
+                        Function::parse(s, link_attr, cfg_env, &mut inactive, &mut item_diagnostic)
+                            .map(Item::Function)
+                    };
+
+                    if let Some(mut item) = item
+                        && let Some(name) = item.name().map(str::to_string)
                         && !skip_next
                     {
-                        functions.insert(f.name.clone(), Item::Function(f));
+                        // the item spans its attributes too
+                        let span = s.span_from(start);
+                        match &mut item {
+                            Item::Function(func) => {
+                                func.doc = s.doc_before(start);
+                                func.cfg = cfg;
+                                func.span = span;
+                            }
+                            Item::Global(global) => global.span = span,
+                            Item::Struct(strukt) => strukt.span = span,
+                            Item::Malformed => {}
+                        }
+                        functions.insert(name, item);
                     }
 
                     diagnostics.warns.append(&mut item_diagnostic.warns);
 
                     if skip_next {
+                        inactive.push(s.span_from(start));
                         diagnostics.warns.extend(
                             item_diagnostic
                                 .errs
@@ -59,6 +157,12 @@ impl Ast {
                     } else {
                         diagnostics.errs.append(&mut item_diagnostic.errs);
                     }
+                    let scope = s.span_from(start);
+                    lint_attrs.extend(item_lints.drain(..).map(|(level, lint)| LintAttr {
+                        lint,
+                        level,
+                        scope: scope.clone(),
+                    }));
                     skip_next = false;
                 }
                 _invalid => {
@@ -68,13 +172,22 @@ impl Ast {
                             Token::Keyword("cfg"),
                             Token::Keyword("begin_def"),
                             Token::Keyword("public"),
-                            Token::Keyword("link_attr")
+                            Token::Keyword("link_attr"),
+                            Token::Keyword("lint_attr"),
+                            Token::Keyword("import"),
+                            Token::Keyword("use"),
+                            Token::Keyword("static"),
+                            Token::Keyword("const"),
+                            Token::Keyword("struct")
                         ],
                         s.peek().clone(),
                         anchor
                     );
 
                     skip_next = false;
+                    item_start = None;
+                    item_cfg = None;
+                    item_lints.clear();
                     skip_until!(
                         s,
                         Token::Keyword("begin_def")
@@ -82,11 +195,115 @@ impl Ast {
                             | Token::Keyword("public")
                             | Token::Keyword("cfg")
                             | Token::Keyword("link_attr")
+                            | Token::Keyword("lint_attr")
+                            | Token::Keyword("import")
+                            | Token::Keyword("use")
+                            | Token::Keyword("static")
+                            | Token::Keyword("const")
+                            | Token::Keyword("extern_static")
+                            | Token::Keyword("struct")
                     );
                 }
             }
         }
-        (Self { functions }, diagnostics)
+        (
+            Self {
+                functions,
+                imports,
+                inactive,
+                lint_attrs,
+            },
+            diagnostics,
+        )
+    }
+}
+
+impl Import {
+    /// parses `import "<path>"` or `use <module path>`. Leaves the stream before the next semi
+    fn parse<'a>(stream: &mut TokenStream<'a>, diagnostics: &mut Diagnostics<'a>) -> Option<Self> {
+        let is_file = *stream.next().as_ref() == Token::Keyword("import");
+
+        if is_file {
+            let Token::Lit(path) = stream.peek().as_ref() else {
+                unexpected!(
+                    diagnostics,
+                    [Token::Lit("<path>".into())],
+                    stream.peek().clone(),
+                    stream.last_span.clone()
+                );
+                return None;
+            };
+            let path = String::from_utf8_lossy(path).into_owned();
+            stream.advance();
+            return Some(Self::File(path));
+        }
+
+        let mut path = Vec::new();
+        loop {
+            let Token::Ident(segment) = stream.peek().as_ref() else {
+                unexpected!(
+                    diagnostics,
+                    [Token::Ident("<module>")],
+                    stream.peek().clone(),
+                    stream.last_span.clone()
+                );
+                return None;
+            };
+            path.push(segment.to_string());
+            stream.advance();
+
+            if *stream.peek().as_ref() != Token::PathSep {
+                break;
+            }
+            stream.advance();
+        }
+        Some(Self::Module(path))
+    }
+}
+
+impl LintAttr {
+    /// parses the level and lint of `lint_attr <level> <lint>`, following the keyword
+    fn parse<'a>(
+        stream: &mut TokenStream<'a>,
+        diagnostics: &mut Diagnostics<'a>,
+    ) -> Option<(Level, Lint)> {
+        let level = match stream.peek().as_ref() {
+            Token::Ident(level) => Level::from_name(level),
+            _ => None,
+        };
+        let Some(level) = level else {
+            unexpected!(
+                diagnostics,
+                [
+                    Token::Ident("allow"),
+                    Token::Ident("warn"),
+                    Token::Ident("deny")
+                ],
+                stream.peek().clone(),
+                stream.last_span.clone()
+            );
+            return None;
+        };
+        stream.advance();
+
+        let Token::Ident(name) = stream.peek().as_ref() else {
+            unexpected!(
+                diagnostics,
+                [Token::Ident("<lint>")],
+                stream.peek().clone(),
+                stream.last_span.clone()
+            );
+            return None;
+        };
+        let name = name.to_string();
+        let span = stream.next().span.clone();
+        match Lint::from_name(&name) {
+            Some(lint) => Some((level, lint)),
+            None => {
+                diagnostics.errs.push(AstErr::UnknownLint { name }.at(span));
+                None
+            }
+        }
     }
 }
 
@@ -131,6 +348,13 @@ impl LinkAttr {
                                     | Token::Keyword("public")
                                     | Token::Keyword("cfg")
                                     | Token::Keyword("link_attr")
+                                    | Token::Keyword("lint_attr")
+                                    | Token::Keyword("import")
+                                    | Token::Keyword("use")
+                                    | Token::Keyword("static")
+                                    | Token::Keyword("const")
+                                    | Token::Keyword("extern_static")
+                                    | Token::Keyword("struct")
                             );
                             continue;
                         }
@@ -142,6 +366,14 @@ impl LinkAttr {
                     zelf = zelf.into_external();
                     stream.advance();
                 }
+                (Token::Ident("abi"), Token::Ident("c"), _) => {
+                    zelf = zelf.with_abi(Abi::C {
+                        variadic: false,
+                        ret: None,
+                    });
+                    stream.advance();
+                    stream.advance();
+                }
                 (_tok, _, _) => {
                     unexpected!(
                         diagnostics,
@@ -149,7 +381,8 @@ impl LinkAttr {
                             Token::Keyword("section"),
                             Token::Keyword("raw"),
                             Token::Keyword("vis"),
-                            Token::Keyword("section")
+                            Token::Keyword("extern"),
+                            Token::Keyword("abi")
                         ],
                         stream.peek().clone(),
                         anchor
@@ -162,6 +395,13 @@ impl LinkAttr {
                             | Token::Keyword("public")
                             | Token::Keyword("cfg")
                             | Token::Keyword("link_attr")
+                            | Token::Keyword("lint_attr")
+                            | Token::Keyword("import")
+                            | Token::Keyword("use")
+                            | Token::Keyword("static")
+                            | Token::Keyword("const")
+                            | Token::Keyword("extern_static")
+                            | Token::Keyword("struct")
                     );
                     continue;
                 }
@@ -183,6 +423,13 @@ impl LinkAttr {
                     | Token::Keyword("public")
                     | Token::Keyword("cfg")
                     | Token::Keyword("link_attr")
+                    | Token::Keyword("lint_attr")
+                    | Token::Keyword("import")
+                    | Token::Keyword("use")
+                    | Token::Keyword("static")
+                    | Token::Keyword("const")
+                    | Token::Keyword("extern_static")
+                    | Token::Keyword("struct")
             );
         }
 
@@ -190,21 +437,251 @@ impl LinkAttr {
     }
 }
 
+impl Global {
+    /// whether the next item is a global, i.e. `[public] static|const|extern_static`
+    fn is_next(stream: &TokenStream) -> bool {
+        let is_global =
+            |tok: &Token| matches!(tok, Token::Keyword("static" | "const" | "extern_static"));
+        is_global(stream.peek().as_ref())
+            || (*stream.peek().as_ref() == Token::Keyword("public")
+                && is_global(stream.peekn(1).as_ref()))
+    }
+
+    fn parse<'a>(
+        stream: &mut TokenStream<'a>,
+        link_attr: LinkAttr,
+        diagnostics: &mut Diagnostics<'a>,
+    ) -> Option<Self> {
+        let global = Self::parse_inner(stream, link_attr, diagnostics);
+        if global.is_none() {
+            skip_until_or_over!(stream, kw!(Token::Semi), Token::Semi);
+        }
+        global
+    }
+
+    fn parse_inner<'a>(
+        stream: &mut TokenStream<'a>,
+        mut link_attr: LinkAttr,
+        diagnostics: &mut Diagnostics<'a>,
+    ) -> Option<Self> {
+        let anchor = stream.peek().span.clone();
+
+        if *stream.peek().as_ref() == Token::Keyword("public") {
+            stream.advance();
+            link_attr = link_attr.into_pub();
+        }
+
+        let kind = match stream.next().as_ref() {
+            Token::Keyword("const") => GlobalKind::Const,
+            Token::Keyword("extern_static") => {
+                link_attr = link_attr.into_external();
+                GlobalKind::Static
+            }
+            _ => GlobalKind::Static,
+        };
+
+        let Token::Ident(ident) = stream.peek().as_ref() else {
+            unexpected!(
+                diagnostics,
+                [Token::Ident("<ident>")],
+                stream.peek().clone(),
+                anchor.merge(stream.last_span.clone())
+            );
+            return None;
+        };
+        let name = ident.to_string();
+        let name_span = stream.peek().span.clone();
+        stream.advance();
+
+        let value = if *stream.peek().as_ref() == Token::Eq && !link_attr.external {
+            stream.advance();
+            let value = parse_expr(stream, 0., diagnostics);
+            if value.inner == Expr::Malformed {
+                return None;
+            }
+            Some(value)
+        } else if kind == GlobalKind::Const {
+            unexpected!(
+                diagnostics,
+                [Token::Eq],
+                stream.peek().clone(),
+                anchor.merge(stream.last_span.clone())
+            );
+            return None;
+        } else {
+            None
+        };
+
+        if *stream.peek().as_ref() != Token::Semi {
+            unclosed_block!(
+                diagnostics,
+                [Token::Semi],
+                stream.peek().clone(),
+                anchor.merge(stream.last_span.clone())
+            );
+            return None;
+        }
+        stream.advance();
+
+        Some(Self {
+            name,
+            kind,
+            value,
+            link_attr,
+            span: stream.span_from(anchor.start),
+            name_span,
+        })
+    }
+}
+
+impl Struct {
+    /// whether the next item is a struct, i.e. `[public] struct`
+    fn is_next(stream: &TokenStream) -> bool {
+        let is_struct = |tok: &Token| *tok == Token::Keyword("struct");
+        is_struct(stream.peek().as_ref())
+            || (*stream.peek().as_ref() == Token::Keyword("public")
+                && is_struct(stream.peekn(1).as_ref()))
+    }
+
+    fn parse<'a>(
+        stream: &mut TokenStream<'a>,
+        link_attr: LinkAttr,
+        diagnostics: &mut Diagnostics<'a>,
+    ) -> Option<Self> {
+        let strukt = Self::parse_inner(stream, link_attr, diagnostics);
+        if strukt.is_none() {
+            skip_until_or_over!(stream, kw!(Token::Semi), Token::Semi);
+        }
+        strukt
+    }
+
+    /// parses `[public] struct <ident> { <field>, ... };`
+    fn parse_inner<'a>(
+        stream: &mut TokenStream<'a>,
+        mut link_attr: LinkAttr,
+        diagnostics: &mut Diagnostics<'a>,
+    ) -> Option<Self> {
+        let anchor = stream.peek().span.clone();
+
+        if *stream.peek().as_ref() == Token::Keyword("public") {
+            stream.advance();
+            link_attr = link_attr.into_pub();
+        }
+        stream.advance();
+
+        let Token::Ident(ident) = stream.peek().as_ref() else {
+            unexpected!(
+                diagnostics,
+                [Token::Ident("<ident>")],
+                stream.peek().clone(),
+                anchor.merge(stream.last_span.clone())
+            );
+            return None;
+        };
+        let name = ident.to_string();
+        let name_span = stream.peek().span.clone();
+        stream.advance();
+
+        if *stream.peek().as_ref() != Token::OpenBrace {
+            unexpected!(
+                diagnostics,
+                [Token::OpenBrace],
+                stream.peek().clone(),
+                anchor.merge(stream.last_span.clone())
+            );
+            return None;
+        }
+        stream.advance();
+
+        let mut fields: Vec<String> = Vec::new();
+        let mut field_spans: Vec<Span> = Vec::new();
+        while *stream.peek().as_ref() != Token::CloseBrace {
+            let Token::Ident(field) = stream.peek().as_ref() else {
+                unexpected!(
+                    diagnostics,
+                    [Token::Ident("<field>"), Token::CloseBrace],
+                    stream.peek().clone(),
+                    anchor.merge(stream.last_span.clone())
+                );
+                return None;
+            };
+            match fields.iter().position(|f| f == field) {
+                Some(first) => diagnostics.errs.push(
+                    AstErr::DuplicateField {
+                        field: field.to_string(),
+                        strukt: name.clone(),
+                        first: field_spans[first].clone(),
+                    }
+                    .at(stream.peek().span.clone()),
+                ),
+                None => {
+                    fields.push(field.to_string());
+                    field_spans.push(stream.peek().span.clone());
+                }
+            }
+            stream.advance();
+            match stream.peek().as_ref() {
+                Token::Comma => stream.advance(),
+                Token::CloseBrace => {}
+                _ => {
+                    unclosed_block!(
+                        diagnostics,
+                        [Token::Comma, Token::CloseBrace],
+                        stream.peek().clone(),
+                        anchor.merge(stream.last_span.clone())
+                    );
+                    return None;
+                }
+            }
+        }
+        stream.advance();
+
+        if *stream.peek().as_ref() != Token::Semi {
+            unclosed_block!(
+                diagnostics,
+                [Token::Semi],
+                stream.peek().clone(),
+                anchor.merge(stream.last_span.clone())
+            );
+            return None;
+        }
+        stream.advance();
+
+        Some(Self {
+            name,
+            fields,
+            field_spans,
+            link_attr,
+            span: stream.span_from(anchor.start),
+            name_span,
+        })
+    }
+}
+
 impl Function {
     fn parse<'a>(
         stream: &mut TokenStream<'a>,
         link_attr: LinkAttr,
         cfg_env: &CfgEnv,
+        inactive: &mut Vec<Span>,
         diagnostics: &mut Diagnostics<'a>,
     ) -> Option<Self> {
-        let func = Self::parse_inner(stream, link_attr, cfg_env, diagnostics);
+        let func = Self::parse_inner(stream, link_attr, cfg_env, inactive, diagnostics);
         if func.is_none() {
             skip_until!(
                 stream,
                 Token::Keyword("begin_def")
                     | Token::Keyword("extern_def")
                     | Token::Keyword("link_attr")
+                    | Token::Keyword("lint_attr")
                     | Token::Keyword("cfg")
+                    | Token::Keyword("public")
+                    | Token::Keyword("import")
+                    | Token::Keyword("use")
+                    | Token::Keyword("static")
+                    | Token::Keyword("const")
+                    | Token::Keyword("extern_static")
+                    | Token::Keyword("struct")
             );
         }
         func
@@ -214,6 +691,7 @@ impl Function {
         stream: &mut TokenStream<'a>,
         mut link_attr: LinkAttr,
         cfg_env: &CfgEnv,
+        inactive: &mut Vec<Span>,
         diagnostics: &mut Diagnostics<'a>,
     ) -> Option<Self> {
         let mut kw = stream.peek();
@@ -257,11 +735,15 @@ impl Function {
             return None;
         };
         let name = ident.to_string();
+        let name_span = stream.peek().span.clone();
         stream.advance();
 
         let mut args = Vec::new();
+        let mut arg_spans = Vec::new();
+        let mut arg_types = Vec::new();
+        let mut ret = None;
 
-        while *stream.peek().as_ref() != Token::Semi {
+        while !matches!(stream.peek().as_ref(), Token::Semi | Token::Arrow) {
             let Token::Ident(ident) = stream.peek().as_ref() else {
                 unexpected!(
                     diagnostics,
@@ -273,15 +755,23 @@ impl Function {
             };
 
             args.push(ident.to_string());
+            arg_spans.push(stream.peek().span.clone());
             stream.advance();
 
+            if *stream.peek().as_ref() == Token::Colon {
+                stream.advance();
+                arg_types.push(Some(Type::parse(stream, diagnostics)?));
+            } else {
+                arg_types.push(None);
+            }
+
             let next = stream.peek();
             if *next.as_ref() == Token::Comma {
                 stream.advance();
-            } else if *next.as_ref() != Token::Semi {
+            } else if !matches!(next.as_ref(), Token::Semi | Token::Arrow) {
                 unclosed_block!(
                     diagnostics,
-                    [Token::Semi, Token::Comma],
+                    [Token::Semi, Token::Comma, Token::Colon, Token::Arrow],
                     next.clone(),
                     anchor.clone().merge(stream.last_span.clone())
                 );
@@ -289,6 +779,20 @@ impl Function {
             }
         }
 
+        if *stream.peek().as_ref() == Token::Arrow {
+            stream.advance();
+            ret = Some(Type::parse(stream, diagnostics)?);
+            if *stream.peek().as_ref() != Token::Semi {
+                unclosed_block!(
+                    diagnostics,
+                    [Token::Semi],
+                    stream.peek().clone(),
+                    anchor.clone().merge(stream.last_span.clone())
+                );
+                return None;
+            }
+        }
+
         stream.advance();
 
         let body = if is_local {
@@ -301,7 +805,14 @@ impl Function {
                         | Token::Keyword("begin_def")
                         | Token::Keyword("extern_def")
                         | Token::Keyword("link_attr")
+                        | Token::Keyword("lint_attr")
                         | Token::Keyword("public")
+                        | Token::Keyword("import")
+                        | Token::Keyword("use")
+                        | Token::Keyword("static")
+                        | Token::Keyword("const")
+                        | Token::Keyword("extern_static")
+                        | Token::Keyword("struct")
                 ) {
                     unclosed_block!(
                         diagnostics,
@@ -330,7 +841,9 @@ impl Function {
                 };
 
                 let mut line_diagnostics = Diagnostics::new();
+                let line_start = stream.peek().span.start;
                 let line = Line::parse(stream, &mut line_diagnostics);
+                let line_span = stream.span_from(line_start);
 
                 if line == Line::Malformed
                     && matches!(
@@ -339,22 +852,27 @@ impl Function {
                             | Token::Keyword("public")
                             | Token::Keyword("extern_def")
                             | Token::Keyword("link_attr")
+                            | Token::Keyword("lint_attr")
                     )
                 {
                     unclosed_block!(
                         diagnostics,
                         [Token::Keyword("end_def")],
                         stream.peekn(-1).clone(),
-                        anchor
+                        anchor.clone()
                     );
                     return None;
                 }
 
                 diagnostics.warns.append(&mut line_diagnostics.warns);
                 if cfg {
-                    body.push(line);
+                    body.push(Spanned {
+                        inner: line,
+                        span: line_span,
+                    });
                     diagnostics.errs.append(&mut line_diagnostics.errs);
                 } else {
+                    inactive.push(line_span);
                     diagnostics.warns.extend(
                         line_diagnostics
                             .errs
@@ -376,7 +894,15 @@ impl Function {
             name,
             body,
             args,
+            arg_spans,
+            arg_types,
+            ret,
+            arity: Arity::default(),
             link_attr,
+            doc: None,
+            cfg: None,
+            span: stream.span_from(anchor.start),
+            name_span,
         })
     }
 }
@@ -35,7 +35,14 @@ macro_rules! kw {
             | Token::Keyword("if")
             | Token::Keyword("cfg")
             | Token::Keyword("link_attr")
+            | Token::Keyword("lint_attr")
             | Token::Keyword("public")
+            | Token::Keyword("import")
+            | Token::Keyword("use")
+            | Token::Keyword("static")
+            | Token::Keyword("const")
+            | Token::Keyword("extern_static")
+            | Token::Keyword("struct")
     };
 }
 
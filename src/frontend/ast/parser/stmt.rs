@@ -2,8 +2,8 @@ use crate::{
     expect,
     frontend::{
         ast::{
-            LValue, Line,
-            error::{AstErr, Diagnostics},
+            FieldRef, LValue, Line, Type,
+            error::{AstErr, Diagnostics, Spanned},
             parser::expr::parse_expr,
         },
         lexer::{Token, TokenStream},
@@ -12,13 +12,25 @@ use crate::{
 };
 
 impl LValue {
-    fn from_tokens<'a>(stream: &mut TokenStream<'a>, diagnostics: &mut Diagnostics<'a>) -> Self {
+    fn from_tokens<'a>(
+        stream: &mut TokenStream<'a>,
+        diagnostics: &mut Diagnostics<'a>,
+    ) -> Spanned<Self> {
         let anchor = stream.peek().span.clone();
-        match stream.peek().as_ref() {
+        let lvalue = match stream.peek().as_ref() {
             Token::Ident(i) => {
-                let ident = i.to_string();
+                let mut lvalue = Spanned::new(Self::Variable(i.to_string()), anchor.clone());
                 stream.advance();
-                Self::Variable(ident)
+                while *stream.peek().as_ref() == Token::Arrow {
+                    let Some(field) = FieldRef::parse(stream, diagnostics) else {
+                        return Spanned::new(Self::Malformed, stream.span_from(anchor.start));
+                    };
+                    lvalue = Spanned::new(
+                        Self::Field(Box::new(lvalue), field),
+                        stream.span_from(anchor.start),
+                    );
+                }
+                return lvalue;
             }
             Token::Star => {
                 stream.advance();
@@ -30,11 +42,12 @@ impl LValue {
                     diagnostics,
                     [Token::Star, Token::Ident("<variable>")],
                     stream.peek().clone(),
-                    anchor.merge(stream.last_span.clone())
+                    anchor.clone().merge(stream.last_span.clone())
                 );
                 Self::Malformed
             }
-        }
+        };
+        Spanned::new(lvalue, stream.span_from(anchor.start))
     }
 }
 
@@ -44,17 +57,62 @@ impl Line {
         let anchor = stream.peek().span.clone();
 
         let line = match stream.peek().as_ref() {
+            // `<ident>: <type> = <expr>`. `<ident> : <lvalue>` is a call without arguments
+            Token::Ident(name)
+                if *stream.peekn(1).as_ref() == Token::Colon
+                    && Type::is_next(stream, 2)
+                    && (*stream.peekn(2).as_ref() == Token::Ident("ptr")
+                        || *stream.peekn(3).as_ref() == Token::Eq) =>
+            {
+                let var = Spanned::new(LValue::Variable(name.to_string()), anchor.clone());
+                stream.advance();
+                stream.advance();
+                match Type::parse(stream, diagnostics) {
+                    Some(ty) if *stream.peek().as_ref() == Token::Eq => {
+                        stream.advance();
+                        Self::Decl(var, Some(ty), parse_expr(stream, 0., diagnostics))
+                    }
+                    Some(_) => {
+                        unexpected!(
+                            diagnostics,
+                            [Token::Eq],
+                            stream.peek().clone(),
+                            anchor.clone().merge(stream.last_span.clone())
+                        );
+                        Self::Malformed
+                    }
+                    None => Self::Malformed,
+                }
+            }
             Token::Ident(_) | Token::Star => {
                 let var = LValue::from_tokens(stream, diagnostics);
 
                 'parse_inner: {
-                    match (stream.peek().as_ref(), var) {
+                    match (stream.peek().as_ref(), &var.inner) {
                         (_, LValue::Malformed) => Self::Malformed,
-                        (Token::Eq, var) => {
+                        (Token::Eq, _) => {
                             stream.advance();
-                            Self::Decl(var, parse_expr(stream, 0., diagnostics))
+                            Self::Decl(var, None, parse_expr(stream, 0., diagnostics))
                         }
                         (_, LValue::Variable(func)) => {
+                            let mut func = func.clone();
+                            // module qualified function, i.e. `vec::push`
+                            while *stream.peek().as_ref() == Token::PathSep {
+                                stream.advance();
+                                let Token::Ident(segment) = stream.peek().as_ref() else {
+                                    unexpected!(
+                                        diagnostics,
+                                        [Token::Ident("<ident>")],
+                                        stream.peek().clone(),
+                                        anchor.clone().merge(stream.last_span.clone())
+                                    );
+                                    break 'parse_inner Self::Malformed;
+                                };
+                                func = format!("{}::{}", func, segment);
+                                stream.advance();
+                            }
+                            let func = Spanned::new(func, stream.span_from(anchor.start));
+
                             let mut exprs = Vec::new();
 
                             while *stream.peek().as_ref() != Token::Semi
@@ -79,7 +137,7 @@ impl Line {
                             if *stream.peek().as_ref() == Token::Colon {
                                 stream.advance();
                                 let lvalue = LValue::from_tokens(stream, diagnostics);
-                                if lvalue == LValue::Malformed {
+                                if lvalue.inner == LValue::Malformed {
                                     break 'parse_inner Self::Malformed;
                                 }
                                 ret.replace(lvalue);
@@ -105,8 +163,10 @@ impl Line {
 
                 expect!(stream, diagnostics, anchor, [Token::Semi], unclosed_block);
 
+                let then_start = stream.peek().span.start;
                 let then = Self::parse(stream, diagnostics);
                 // then consumed the Semi already
+                let then = Spanned::new(then, stream.span_from(then_start));
                 return Self::Cond(cond, Box::new(then));
             }
             Token::EOF => {
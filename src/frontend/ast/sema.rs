@@ -0,0 +1,512 @@
+use crate::frontend::{
+    ast::{
+        Arity, Ast, ConstValue, Expr, GlobalKind, Item, LValue, Line, Operation, Val,
+        error::{AstErr, Diagnostics, Spanned},
+        is_builtin_func, store_width,
+    },
+    lexer::Span,
+};
+
+/// Checks every call against the arity of the called function
+pub fn check_arity(ast: &Ast, diagnostics: &mut Diagnostics) {
+    for item in ast.funcs() {
+        let Item::Function(func) = item else {
+            continue;
+        };
+        let Some(body) = func.spanned_body() else {
+            continue;
+        };
+        for line in body {
+            check_line_arity(ast, &line.inner, &line.span, diagnostics);
+        }
+    }
+}
+
+fn check_line_arity(ast: &Ast, line: &Line, span: &Span, diagnostics: &mut Diagnostics) {
+    match line {
+        Line::Call(name, args, _) if !is_builtin_func(name) => {
+            if let Some(callee) = ast.get_func(name)
+                && !callee.arity.accepts(args.len())
+            {
+                diagnostics.errs.push(
+                    AstErr::ArityMismatch {
+                        name: name.inner.clone(),
+                        expected: callee.arity,
+                        found: args.len(),
+                    }
+                    .at(span.clone()),
+                );
+            }
+        }
+        Line::Call(name, args, _) if store_width(name).is_some() && args.len() != 2 => {
+            diagnostics.errs.push(
+                AstErr::ArityMismatch {
+                    name: name.inner.clone(),
+                    expected: Arity::Exact(2),
+                    found: args.len(),
+                }
+                .at(span.clone()),
+            );
+        }
+        Line::Cond(_, then) => check_line_arity(ast, then, span, diagnostics),
+        _ => {}
+    }
+}
+
+/// Checks that all global initializers are constant and that no constant is assigned to
+pub fn check_globals(ast: &Ast, diagnostics: &mut Diagnostics) {
+    for global in ast.globals() {
+        let Some(value) = &global.value else {
+            continue;
+        };
+        let reason = match eval_const(&value.inner, ast) {
+            Err(reason) => Some(reason),
+            Ok(ConstValue::Array { .. }) if global.kind == GlobalKind::Const => {
+                Some("arrays may only initialize statics".into())
+            }
+            Ok(_) => None,
+        };
+        if let Some(reason) = reason {
+            diagnostics.errs.push(
+                AstErr::NonConstInitializer {
+                    name: global.name.clone(),
+                    reason,
+                }
+                .at(value.span.clone()),
+            );
+        }
+    }
+
+    for item in ast.funcs() {
+        let Item::Function(func) = item else {
+            continue;
+        };
+        let Some(body) = func.spanned_body() else {
+            continue;
+        };
+        for line in body {
+            check_line_assignments(ast, &func.args, &line.inner, &line.span, diagnostics);
+            check_line_arrays(ast, &line.inner, &line.span, diagnostics);
+        }
+    }
+}
+
+/// the initializer and length of every array must be constant
+fn check_line_arrays(ast: &Ast, line: &Line, span: &Span, diagnostics: &mut Diagnostics) {
+    fn check_expr(ast: &Ast, expr: &Expr, span: &Span, diagnostics: &mut Diagnostics) {
+        match expr {
+            Expr::Array(..) => {
+                if let Err(reason) = eval_const(expr, ast) {
+                    diagnostics
+                        .errs
+                        .push(AstErr::NonConstArray { reason }.at(span.clone()));
+                }
+            }
+            Expr::Op(lhs, _, rhs) => {
+                check_expr(ast, lhs, span, diagnostics);
+                check_expr(ast, rhs, span, diagnostics);
+            }
+            Expr::Field(ptr, _) | Expr::Load(ptr, ..) => check_expr(ast, ptr, span, diagnostics),
+            Expr::Val(_) | Expr::SizeOf(_) | Expr::Malformed => {}
+        }
+    }
+
+    match line {
+        Line::Expr(expr) | Line::Decl(_, _, expr) => check_expr(ast, expr, span, diagnostics),
+        Line::Call(_, args, _) => args
+            .iter()
+            .for_each(|arg| check_expr(ast, arg, span, diagnostics)),
+        Line::Cond(cond, then) => {
+            check_expr(ast, cond, span, diagnostics);
+            check_line_arrays(ast, then, span, diagnostics);
+        }
+        Line::Malformed => {}
+    }
+}
+
+/// Checks that every field access and sizeof names a known struct and field
+pub fn check_structs(ast: &Ast, diagnostics: &mut Diagnostics) {
+    for item in ast.funcs() {
+        let Item::Function(func) = item else {
+            continue;
+        };
+        let Some(body) = func.spanned_body() else {
+            continue;
+        };
+        for line in body {
+            check_line_structs(ast, &line.inner, &line.span, diagnostics);
+        }
+    }
+}
+
+fn check_line_structs(ast: &Ast, line: &Line, span: &Span, diagnostics: &mut Diagnostics) {
+    fn check_expr(ast: &Ast, expr: &Expr, span: &Span, diagnostics: &mut Diagnostics) {
+        let err = match expr {
+            Expr::Field(ptr, field) => {
+                check_expr(ast, ptr, span, diagnostics);
+                ast.field_offset(field).err()
+            }
+            Expr::SizeOf(name) if ast.get_struct(name).is_none() => {
+                Some(AstErr::UnknownStruct { name: name.clone() })
+            }
+            Expr::Op(lhs, _, rhs) | Expr::Array(lhs, rhs) => {
+                check_expr(ast, lhs, span, diagnostics);
+                check_expr(ast, rhs, span, diagnostics);
+                None
+            }
+            Expr::Load(ptr, ..) => {
+                check_expr(ast, ptr, span, diagnostics);
+                None
+            }
+            Expr::Val(_) | Expr::SizeOf(_) | Expr::Malformed => None,
+        };
+        if let Some(err) = err {
+            diagnostics.errs.push(err.at(span.clone()));
+        }
+    }
+
+    fn check_lvalue(ast: &Ast, lvalue: &LValue, span: &Span, diagnostics: &mut Diagnostics) {
+        match lvalue {
+            LValue::Field(inner, field) => {
+                check_lvalue(ast, inner, span, diagnostics);
+                if let Err(err) = ast.field_offset(field) {
+                    diagnostics.errs.push(err.at(span.clone()));
+                }
+            }
+            LValue::Deref(inner) => check_lvalue(ast, inner, span, diagnostics),
+            LValue::Variable(_) | LValue::Malformed => {}
+        }
+    }
+
+    match line {
+        Line::Expr(expr) => check_expr(ast, expr, span, diagnostics),
+        Line::Decl(lvalue, _, expr) => {
+            check_lvalue(ast, lvalue, span, diagnostics);
+            check_expr(ast, expr, span, diagnostics);
+        }
+        Line::Call(_, args, ret) => {
+            args.iter()
+                .for_each(|arg| check_expr(ast, arg, span, diagnostics));
+            if let Some(ret) = ret {
+                check_lvalue(ast, ret, span, diagnostics);
+            }
+        }
+        Line::Cond(cond, then) => {
+            check_expr(ast, cond, span, diagnostics);
+            check_line_structs(ast, then, span, diagnostics);
+        }
+        Line::Malformed => {}
+    }
+}
+
+fn check_line_assignments(
+    ast: &Ast,
+    args: &[String],
+    line: &Line,
+    span: &Span,
+    diagnostics: &mut Diagnostics,
+) {
+    let target = match line {
+        Line::Decl(
+            Spanned {
+                inner: LValue::Variable(name),
+                ..
+            },
+            _,
+            _,
+        )
+        | Line::Call(
+            _,
+            _,
+            Some(Spanned {
+                inner: LValue::Variable(name),
+                ..
+            }),
+        ) => name,
+        Line::Cond(_, then) => return check_line_assignments(ast, args, then, span, diagnostics),
+        _ => return,
+    };
+    if !args.contains(target)
+        && ast
+            .get_global(target)
+            .is_some_and(|global| global.kind == GlobalKind::Const)
+    {
+        diagnostics.errs.push(
+            AstErr::AssignToConst {
+                name: target.clone(),
+            }
+            .at(span.clone()),
+        );
+    }
+}
+
+/// A name referenced by a constant expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstRef<'a> {
+    Global(&'a str),
+    /// `sizeof(<struct>)`
+    SizeOf(&'a str),
+}
+
+/// Evaluates a constant expression, which may reference other constants
+pub fn eval_const(expr: &Expr, ast: &Ast) -> Result<ConstValue, String> {
+    fn lookup(name: ConstRef, ast: &Ast, visiting: &mut Vec<String>) -> Result<ConstValue, String> {
+        let name = match name {
+            ConstRef::Global(name) => name,
+            ConstRef::SizeOf(name) => {
+                return ast
+                    .get_struct(name)
+                    .map(|strukt| ConstValue::Int(strukt.size()))
+                    .ok_or_else(|| format!("no struct named {}", name));
+            }
+        };
+        let Some(global) = ast.get_global(name) else {
+            return Err(format!("{} is not a constant", name));
+        };
+        let (GlobalKind::Const, Some(value)) = (global.kind, &global.value) else {
+            return Err(format!("{} is not a constant", name));
+        };
+        if visiting.iter().any(|visited| visited == name) {
+            return Err(format!("{} is defined in terms of itself", name));
+        }
+        visiting.push(name.to_string());
+        let value = eval_const_with(&value.inner, &mut |name| lookup(name, ast, visiting));
+        visiting.pop();
+        value
+    }
+
+    let mut visiting = Vec::new();
+    eval_const_with(expr, &mut |name| lookup(name, ast, &mut visiting))
+}
+
+/// Evaluates a constant expression, resolving referenced constants with lookup
+pub fn eval_const_with(
+    expr: &Expr,
+    lookup: &mut dyn FnMut(ConstRef) -> Result<ConstValue, String>,
+) -> Result<ConstValue, String> {
+    let mut int = |expr: &Expr| match eval_const_with(expr, lookup)? {
+        ConstValue::Int(value) => Ok(value),
+        ConstValue::Str(_) => Err("string literals may not be used in arithmetic".to_string()),
+        ConstValue::Array { .. } => Err("arrays may not be used in arithmetic".to_string()),
+    };
+
+    match expr {
+        Expr::Val(Val::V(value)) => Ok(ConstValue::Int(*value)),
+        Expr::Val(Val::Lit(lit)) => Ok(ConstValue::Str(lit.clone())),
+        Expr::Val(Val::Var(name)) => lookup(ConstRef::Global(name)),
+        Expr::SizeOf(name) => lookup(ConstRef::SizeOf(name)),
+        Expr::Field(..) => Err("fields are loaded at runtime".into()),
+        Expr::Load(..) => Err("addresses are not known at compile time".into()),
+        Expr::Array(init, len) => {
+            let init = int(init)?;
+            match usize::try_from(int(len)?) {
+                Ok(len) if len > 0 => Ok(ConstValue::Array { init, len }),
+                _ => Err("the length of an array must be positive".into()),
+            }
+        }
+        Expr::Op(lhs, op, rhs) => {
+            let lhs = int(lhs)?;
+            // the rhs of `&&` and `||` is not evaluated, if the lhs decides the result
+            match op {
+                Operation::LogicalAnd if lhs == 0 => return Ok(ConstValue::Int(0)),
+                Operation::LogicalOr if lhs != 0 => return Ok(ConstValue::Int(1)),
+                _ => {}
+            }
+            let rhs = int(rhs)?;
+            let value = match op {
+                Operation::Mul => lhs.wrapping_mul(rhs),
+                Operation::Sub => lhs.wrapping_sub(rhs),
+                Operation::Add => lhs.wrapping_add(rhs),
+                Operation::Div | Operation::Mod | Operation::UDiv | Operation::UMod if rhs == 0 => {
+                    return Err("division by zero".into());
+                }
+                // idiv traps on the overflow of i64::MIN / -1, like on division by zero
+                Operation::Div | Operation::Mod if lhs == i64::MIN && rhs == -1 => {
+                    return Err("division overflow".into());
+                }
+                Operation::Div => lhs / rhs,
+                Operation::Mod => lhs % rhs,
+                Operation::UDiv => ((lhs as u64) / (rhs as u64)) as i64,
+                Operation::UMod => ((lhs as u64) % (rhs as u64)) as i64,
+                Operation::Not => (rhs == 0) as i64,
+                Operation::Neg => rhs.wrapping_neg(),
+                Operation::BitNot => !rhs,
+                Operation::Gt => (lhs > rhs) as i64,
+                Operation::Lt => (lhs < rhs) as i64,
+                Operation::Ge => (lhs >= rhs) as i64,
+                Operation::Le => (lhs <= rhs) as i64,
+                Operation::UGt => ((lhs as u64) > (rhs as u64)) as i64,
+                Operation::ULt => ((lhs as u64) < (rhs as u64)) as i64,
+                Operation::UGe => ((lhs as u64) >= (rhs as u64)) as i64,
+                Operation::ULe => ((lhs as u64) <= (rhs as u64)) as i64,
+                Operation::LogicalAnd | Operation::LogicalOr => (rhs != 0) as i64,
+                Operation::EqEq => (lhs == rhs) as i64,
+                Operation::NEq => (lhs != rhs) as i64,
+                Operation::BitAND => lhs & rhs,
+                Operation::BitOR => lhs | rhs,
+                Operation::BitXOR => lhs ^ rhs,
+                // `>>` is logical, `>>>` arithmetic and both use the lower 6 bits of the shift amount, as on x86_64
+                Operation::Shr => ((lhs as u64) >> (rhs & 63)) as i64,
+                Operation::Sar => lhs >> (rhs & 63),
+                Operation::Shl => lhs << (rhs & 63),
+                Operation::Load | Operation::AsRef => {
+                    return Err("addresses are not known at compile time".into());
+                }
+                Operation::Malformed => return Err("malformed expression".into()),
+            };
+            Ok(ConstValue::Int(value))
+        }
+        Expr::Val(Val::Malformed) | Expr::Malformed => Err("malformed expression".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::{
+        ast::{FieldRef, cfg::CfgEnv},
+        get_ast,
+    };
+
+    #[test]
+    fn globals() {
+        let s = "
+            const SIZE = 8 * 4 + 1;
+            const MASK = !0 | SIZE >> 1;
+            const SIGN = -SIZE >>> 1;
+            const FLIP = ~SIZE * -1;
+            const LOGIC = SIZE >= 33 && (0 && 1 / 0 || SIZE <= 0);
+            const NAME = \"name\";
+            public static counter = SIZE;
+            static zeroed;
+            static bad = counter + 1;
+            const CYCLE = CYCLE + 1;
+            const MIN = -9223372036854775807 - 1;
+            const QUOT = MIN / -1;
+            const REM = MIN % -1;
+            extern_static shared;
+            begin_def main arg;
+                SIZE = 2;
+                arg = counter;
+                if arg; NAME = 1;
+            end_def
+            begin_def shadow SIZE;
+                SIZE = 1;
+            end_def
+        ";
+        let (ast, diagnostics) = get_ast(s, &CfgEnv::default());
+        assert!(diagnostics.errs.is_empty());
+
+        let value = |name: &str| {
+            eval_const(
+                &ast.get_global(name).unwrap().value.as_ref().unwrap().inner,
+                &ast,
+            )
+        };
+        assert_eq!(value("SIZE"), Ok(ConstValue::Int(33)));
+        assert_eq!(value("MASK"), Ok(ConstValue::Int(17)));
+        assert_eq!(value("SIGN"), Ok(ConstValue::Int(-17)));
+        assert_eq!(value("FLIP"), Ok(ConstValue::Int(34)));
+        assert_eq!(value("LOGIC"), Ok(ConstValue::Int(0)));
+        assert_eq!(value("NAME"), Ok(ConstValue::Str("name".into())));
+        assert!(ast.get_global("zeroed").unwrap().value.is_none());
+        assert!(ast.get_global("shared").unwrap().link_attr.external);
+
+        let mut diagnostics = Diagnostics::new();
+        check_globals(&ast, &mut diagnostics);
+        let errs = diagnostics
+            .errs
+            .into_iter()
+            .map(|e| e.inner)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errs,
+            vec![
+                AstErr::NonConstInitializer {
+                    name: "bad".into(),
+                    reason: "counter is not a constant".into()
+                },
+                AstErr::NonConstInitializer {
+                    name: "CYCLE".into(),
+                    reason: "CYCLE is defined in terms of itself".into()
+                },
+                AstErr::NonConstInitializer {
+                    name: "QUOT".into(),
+                    reason: "division overflow".into()
+                },
+                AstErr::NonConstInitializer {
+                    name: "REM".into(),
+                    reason: "division overflow".into()
+                },
+                AstErr::AssignToConst {
+                    name: "SIZE".into()
+                },
+                AstErr::AssignToConst {
+                    name: "NAME".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn structs() {
+        let s = "
+            struct Node { value, next };
+            struct Pair { value, other };
+            const SIZE = sizeof(Node) + 1;
+            begin_def main p;
+                p->next->Node::value = sizeof(Pair);
+                x = &p->other;
+                y = p->value;
+                z = p->missing + sizeof(Missing);
+                p->Missing::value = 0;
+            end_def
+        ";
+        let (ast, diagnostics) = get_ast(s, &CfgEnv::default());
+        assert!(diagnostics.errs.is_empty());
+        assert_eq!(
+            eval_const(
+                &ast.get_global("SIZE")
+                    .unwrap()
+                    .value
+                    .as_ref()
+                    .unwrap()
+                    .inner,
+                &ast
+            ),
+            Ok(ConstValue::Int(17))
+        );
+        let field = |strukt: Option<&str>, name: &str| FieldRef {
+            strukt: strukt.map(str::to_string),
+            name: name.into(),
+        };
+        assert_eq!(ast.field_offset(&field(None, "next")), Ok(8));
+        assert_eq!(ast.field_offset(&field(Some("Pair"), "value")), Ok(0));
+
+        let mut diagnostics = Diagnostics::new();
+        check_structs(&ast, &mut diagnostics);
+        let errs = diagnostics
+            .errs
+            .into_iter()
+            .map(|e| e.inner)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errs,
+            vec![
+                AstErr::AmbiguousField {
+                    field: "value".into(),
+                    structs: vec!["Node".into(), "Pair".into()]
+                },
+                AstErr::UnknownField {
+                    field: "missing".into()
+                },
+                AstErr::UnknownStruct {
+                    name: "Missing".into()
+                },
+                AstErr::UnknownStruct {
+                    name: "Missing".into()
+                },
+            ]
+        );
+    }
+}
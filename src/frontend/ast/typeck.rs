@@ -0,0 +1,356 @@
+//! Optional static types.
+//! Only annotated arguments and declarations are typed, every other value is unchecked and compatible with any type,
+//! so untyped code keeps compiling unchanged.
+
+use std::collections::HashMap;
+
+use crate::frontend::{
+    ast::{
+        Ast, Expr, Function, Item, LValue, Line, Operation, Type, Val,
+        error::{AstErr, Diagnostics, Spanned},
+        is_builtin_func, store_width,
+    },
+    lexer::Span,
+};
+
+/// Types of the variables of a single function
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeEnv {
+    vars: HashMap<String, Type>,
+}
+
+impl TypeEnv {
+    /// collects the annotated arguments and declarations of func.
+    /// Declarations conflicting with an earlier annotation are returned as errors
+    pub fn of(func: &Function) -> (Self, Vec<(AstErr<'static>, Span)>) {
+        let mut env = Self::default();
+        let mut errs = Vec::new();
+        for (arg, ty) in func.args.iter().zip(&func.arg_types) {
+            if let Some(ty) = ty {
+                env.vars.insert(arg.clone(), ty.clone());
+            }
+        }
+
+        fn declare(env: &mut TypeEnv, line: &Line) -> Option<AstErr<'static>> {
+            match line {
+                Line::Decl(
+                    Spanned {
+                        inner: LValue::Variable(name),
+                        ..
+                    },
+                    Some(ty),
+                    _,
+                ) => match env.vars.get(name) {
+                    Some(first) if first != ty => Some(AstErr::ConflictingType {
+                        name: name.clone(),
+                        first: first.clone(),
+                        second: ty.clone(),
+                    }),
+                    _ => {
+                        env.vars.insert(name.clone(), ty.clone());
+                        None
+                    }
+                },
+                Line::Cond(_, then) => declare(env, then),
+                _ => None,
+            }
+        }
+
+        for line in func.spanned_body().into_iter().flatten() {
+            if let Some(err) = declare(&mut env, &line.inner) {
+                errs.push((err, line.span.clone()));
+            }
+        }
+        (env, errs)
+    }
+
+    pub fn get(&self, var: &str) -> Option<&Type> {
+        self.vars.get(var)
+    }
+
+    /// the type of expr, None if it is unchecked
+    pub fn type_of(&self, expr: &Expr) -> Result<Option<Type>, AstErr<'static>> {
+        let expect_ptr = |op: &str, ty: Option<Type>| match ty {
+            Some(ty) if !matches!(ty, Type::Ptr(_)) => {
+                Err(AstErr::InvalidOperand { op: op.into(), ty })
+            }
+            ty => Ok(ty),
+        };
+
+        Ok(match expr {
+            Expr::Val(Val::Var(name)) => self.vars.get(name).cloned(),
+            Expr::Val(Val::Lit(_)) => Some(Type::Ptr(Box::new(Type::U8))),
+            // integer literals fit any integer type
+            Expr::Val(Val::V(_) | Val::Malformed) | Expr::Malformed => None,
+            Expr::Array(..) => Some(Type::Ptr(Box::new(Type::I64))),
+            Expr::SizeOf(_) => Some(Type::I64),
+            Expr::Field(ptr, _) => {
+                expect_ptr("->", self.type_of(ptr)?)?;
+                None
+            }
+            Expr::Load(ptr, ..) => {
+                expect_ptr("load", self.type_of(ptr)?)?;
+                Some(Type::I64)
+            }
+            Expr::Op(lhs, op, rhs) => {
+                let rhs = self.type_of(rhs)?;
+                match op {
+                    Operation::Load => expect_ptr("*", rhs)?.and_then(|ty| ty.pointee().cloned()),
+                    Operation::AsRef => rhs.map(|ty| Type::Ptr(Box::new(ty))),
+                    Operation::Not => Some(Type::I64),
+                    Operation::Neg | Operation::BitNot => match rhs {
+                        Some(ty @ Type::Ptr(_)) => {
+                            return Err(AstErr::InvalidOperand {
+                                op: op.to_string(),
+                                ty,
+                            });
+                        }
+                        Some(Type::U64) => Some(Type::U64),
+                        ty => ty.map(|_| Type::I64),
+                    },
+                    _ => binary_type(*op, self.type_of(lhs)?, rhs)?,
+                }
+            }
+        })
+    }
+}
+
+/// the type of `lhs op rhs`. Pointers may only be offset by integers or subtracted from each other
+fn binary_type(
+    op: Operation,
+    lhs: Option<Type>,
+    rhs: Option<Type>,
+) -> Result<Option<Type>, AstErr<'static>> {
+    let is_int = |ty: &Option<Type>| ty.as_ref().is_none_or(Type::is_int);
+    Ok(match (op, lhs, rhs) {
+        (
+            Operation::Gt
+            | Operation::Lt
+            | Operation::Ge
+            | Operation::Le
+            | Operation::UGt
+            | Operation::ULt
+            | Operation::UGe
+            | Operation::ULe
+            | Operation::EqEq
+            | Operation::NEq
+            | Operation::LogicalAnd
+            | Operation::LogicalOr,
+            _,
+            _,
+        ) => Some(Type::I64),
+        (Operation::Add, Some(ptr @ Type::Ptr(_)), other)
+        | (Operation::Add, other, Some(ptr @ Type::Ptr(_)))
+        | (Operation::Sub, Some(ptr @ Type::Ptr(_)), other)
+            if is_int(&other) =>
+        {
+            Some(ptr)
+        }
+        (Operation::Sub, Some(lhs @ Type::Ptr(_)), Some(rhs @ Type::Ptr(_))) => {
+            if lhs != rhs {
+                return Err(AstErr::TypeMismatch {
+                    expected: lhs,
+                    found: rhs,
+                    context: "pointer difference".into(),
+                });
+            }
+            Some(Type::I64)
+        }
+        (op, Some(ty @ Type::Ptr(_)), _) | (op, _, Some(ty @ Type::Ptr(_))) => {
+            return Err(AstErr::InvalidOperand {
+                op: op.to_string(),
+                ty,
+            });
+        }
+        // a single u64 operand makes the result unsigned, even if the other one is unchecked
+        (_, Some(Type::U64), _) | (_, _, Some(Type::U64)) => Some(Type::U64),
+        (_, Some(_), Some(_)) => Some(Type::I64),
+        _ => None,
+    })
+}
+
+/// whether a value of type found may be used where expected is required
+fn compatible(expected: &Type, found: &Type) -> bool {
+    expected == found || (expected.is_int() && found.is_int())
+}
+
+/// Checks all annotated types of every function in ast
+pub fn check_types(ast: &Ast, diagnostics: &mut Diagnostics) {
+    for item in ast.funcs() {
+        let Item::Function(func) = item else {
+            continue;
+        };
+        let Some(body) = func.spanned_body() else {
+            continue;
+        };
+        let (env, errs) = TypeEnv::of(func);
+        for (err, span) in errs {
+            diagnostics.errs.push(err.at(span));
+        }
+        for line in body {
+            if let Err(err) = check_line(ast, func, &env, &line.inner) {
+                diagnostics.errs.push(err.at(line.span.clone()));
+            }
+        }
+    }
+}
+
+fn check_line(
+    ast: &Ast,
+    func: &Function,
+    env: &TypeEnv,
+    line: &Line,
+) -> Result<(), AstErr<'static>> {
+    let expect =
+        |expected: Option<&Type>, found: Option<Type>, context: String| match (expected, found) {
+            (Some(expected), Some(found)) if !compatible(expected, &found) => {
+                Err(AstErr::TypeMismatch {
+                    expected: expected.clone(),
+                    found,
+                    context,
+                })
+            }
+            _ => Ok(()),
+        };
+
+    match line {
+        Line::Expr(expr) => env.type_of(expr).map(|_| ()),
+        Line::Decl(lvalue, ty, expr) => {
+            let found = env.type_of(expr)?;
+            let expected = match ty {
+                Some(ty) => Some(ty.clone()),
+                None => lvalue_type(env, lvalue)?,
+            };
+            expect(
+                expected.as_ref(),
+                found,
+                format!("assignment to {}", lvalue.inner),
+            )
+        }
+        Line::Call(name, args, ret) => {
+            let found = args
+                .iter()
+                .map(|arg| env.type_of(arg))
+                .collect::<Result<Vec<_>, _>>()?;
+            if name.as_str() == "return" {
+                for found in found {
+                    expect(func.ret.as_ref(), found, "return value".into())?;
+                }
+                return Ok(());
+            }
+            if store_width(name).is_some() {
+                if let Some(addr) = found.into_iter().next()
+                    && let Some(ty) = addr
+                    && !matches!(ty, Type::Ptr(_))
+                {
+                    return Err(AstErr::InvalidOperand {
+                        op: name.inner.clone(),
+                        ty,
+                    });
+                }
+                return Ok(());
+            }
+            let Some(callee) = ast.get_func(name).filter(|_| !is_builtin_func(name)) else {
+                return Ok(());
+            };
+            for (i, (expected, found)) in callee.arg_types.iter().zip(found).enumerate() {
+                expect(
+                    expected.as_ref(),
+                    found,
+                    format!("argument {} of {}", i + 1, name.inner),
+                )?;
+            }
+            if let Some(ret) = ret {
+                let expected = lvalue_type(env, ret)?;
+                expect(
+                    expected.as_ref(),
+                    callee.ret.clone(),
+                    format!("assignment to {}", ret.inner),
+                )?;
+            }
+            Ok(())
+        }
+        Line::Cond(cond, then) => {
+            env.type_of(cond)?;
+            check_line(ast, func, env, then)
+        }
+        Line::Malformed => Ok(()),
+    }
+}
+
+/// the type of the value stored to lvalue
+fn lvalue_type(env: &TypeEnv, lvalue: &LValue) -> Result<Option<Type>, AstErr<'static>> {
+    match lvalue {
+        LValue::Variable(name) => Ok(env.get(name).cloned()),
+        LValue::Deref(_) => env.type_of(&lvalue.as_expr()),
+        LValue::Field(..) | LValue::Malformed => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::{ast::cfg::CfgEnv, get_ast};
+
+    #[test]
+    fn types() {
+        let s = "
+            extern_def vec_push v: ptr<ptr<i64>>, value: i64;
+            begin_def first s: ptr<u8> -> u8;
+                return *s;
+            end_def
+            begin_def main n: i64, untyped;
+                name: ptr<u8> = \"name\";
+                v: ptr<i64> = [0; 4];
+                c: u8 = *(name + 1);
+                vec_push &v, c;
+                vec_push &name, 1;
+                untyped = *n;
+                first v : c;
+                d: i64 = (v + 1) - v;
+                e = v * 2;
+                n: u8 = 1;
+                vec_push untyped, untyped;
+            end_def
+        ";
+        let (ast, diagnostics) = get_ast(s, &CfgEnv::default());
+        assert!(diagnostics.errs.is_empty());
+
+        let mut diagnostics = Diagnostics::new();
+        check_types(&ast, &mut diagnostics);
+        let errs = diagnostics
+            .errs
+            .into_iter()
+            .map(|e| e.inner)
+            .collect::<Vec<_>>();
+        let ptr = |ty| Type::Ptr(Box::new(ty));
+        assert_eq!(
+            errs,
+            vec![
+                AstErr::ConflictingType {
+                    name: "n".into(),
+                    first: Type::I64,
+                    second: Type::U8
+                },
+                AstErr::TypeMismatch {
+                    expected: ptr(ptr(Type::I64)),
+                    found: ptr(ptr(Type::U8)),
+                    context: "argument 1 of vec_push".into()
+                },
+                AstErr::InvalidOperand {
+                    op: "*".into(),
+                    ty: Type::I64
+                },
+                AstErr::TypeMismatch {
+                    expected: ptr(Type::U8),
+                    found: ptr(Type::I64),
+                    context: "argument 1 of first".into()
+                },
+                AstErr::InvalidOperand {
+                    op: "*".into(),
+                    ty: ptr(Type::I64)
+                },
+            ]
+        );
+    }
+}
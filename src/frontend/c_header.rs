@@ -0,0 +1,450 @@
+//! A small parser for function prototypes in C headers.
+//! The preprocessor is not run, so only prototypes spelled out in the header itself are found.
+//! Functions passing or returning floating point values are skipped, as these use vector registers.
+
+use crate::frontend::ast::{Extend, Width};
+
+static TYPE_WORDS: &[&str] = &[
+    "void",
+    "char",
+    "short",
+    "int",
+    "long",
+    "float",
+    "double",
+    "signed",
+    "unsigned",
+    "const",
+    "volatile",
+    "restrict",
+    "__restrict",
+    "struct",
+    "union",
+    "enum",
+    "extern",
+    "static",
+    "inline",
+    "__inline",
+    "_Bool",
+    "bool",
+    "register",
+];
+
+static ATTRIBUTE_WORDS: &[&str] = &[
+    "__attribute__",
+    "__attribute",
+    "__asm__",
+    "__asm",
+    "asm",
+    "__declspec",
+];
+
+/// integer types narrower than a qword, the first one contained in a type determines its width
+static NARROW_INTS: &[(&str, Width, bool)] = &[
+    ("_Bool", Width::Byte, true),
+    ("bool", Width::Byte, true),
+    ("char", Width::Byte, false),
+    ("int8_t", Width::Byte, false),
+    ("uint8_t", Width::Byte, true),
+    ("short", Width::Word, false),
+    ("int16_t", Width::Word, false),
+    ("uint16_t", Width::Word, true),
+    ("int", Width::Dword, false),
+    ("signed", Width::Dword, false),
+    ("unsigned", Width::Dword, true),
+    ("enum", Width::Dword, false),
+    ("int32_t", Width::Dword, false),
+    ("uint32_t", Width::Dword, true),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CPrototype {
+    pub name: String,
+    pub params: Vec<String>,
+    pub variadic: bool,
+    /// width and signedness of an integer return value narrower than a qword
+    pub ret: Option<(Width, Extend)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CToken<'a> {
+    Ident(&'a str),
+    Punct(char),
+    Ellipsis,
+    Str,
+}
+
+/// Extracts all function prototypes declared at file scope of a C header
+pub fn parse_prototypes(src: &str) -> Vec<CPrototype> {
+    let src = strip_preprocessor(src);
+    let tokens = tokenize(&src);
+
+    let mut protos = Vec::new();
+    let mut decl: Vec<&CToken> = Vec::new();
+    // true for braces of `extern "C" {`, which do not open a new scope
+    let mut braces: Vec<bool> = Vec::new();
+    let mut skipping = false;
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let tok = &tokens[i];
+        match tok {
+            CToken::Punct('{') => {
+                let transparent = matches!(
+                    (decl.as_slice(), braces.iter().all(|b| *b)),
+                    ([.., CToken::Ident("extern"), CToken::Str], true)
+                );
+                braces.push(transparent);
+                if transparent {
+                    decl.clear();
+                } else {
+                    skipping = true;
+                }
+            }
+            CToken::Punct('}') => {
+                braces.pop();
+                if braces.iter().all(|b| *b) {
+                    // a struct definition or inline function ends the current declaration
+                    skipping = false;
+                    decl.clear();
+                    if matches!(tokens.get(i + 1), Some(CToken::Punct(';'))) {
+                        i += 1;
+                    }
+                }
+            }
+            CToken::Punct(';') if !skipping => {
+                if let Some(proto) = parse_declaration(&decl) {
+                    protos.push(proto);
+                }
+                decl.clear();
+            }
+            _ if !skipping => decl.push(tok),
+            _ => {}
+        }
+        i += 1;
+    }
+    protos
+}
+
+fn parse_declaration(decl: &[&CToken]) -> Option<CPrototype> {
+    let decl = strip_attributes(decl);
+    if matches!(decl.first(), Some(CToken::Ident("typedef"))) {
+        return None;
+    }
+
+    let open = decl.iter().position(|t| **t == CToken::Punct('('))?;
+    // the name must be preceded by a return type and may not be a function pointer
+    let (Some(CToken::Ident(name)), true) = (open.checked_sub(1).map(|i| decl[i]), open >= 2)
+    else {
+        return None;
+    };
+    if TYPE_WORDS.contains(name) || matches!(decl.get(open + 1), Some(CToken::Punct('*'))) {
+        return None;
+    }
+
+    let close = matching_paren(&decl, open)?;
+    let ret = &decl[..open - 1];
+    if is_float(ret) {
+        return None;
+    }
+    let mut params = Vec::new();
+    let mut variadic = false;
+    for (i, param) in split_params(&decl[open + 1..close]).into_iter().enumerate() {
+        if is_float(&param) {
+            return None;
+        }
+        match param.as_slice() {
+            [] | [CToken::Ident("void")] if i == 0 => {}
+            [CToken::Ident(_), ..] | [CToken::Punct(_), ..] => params.push(param_name(&param, i)),
+            [CToken::Ellipsis] => variadic = true,
+            _ => {}
+        }
+    }
+
+    Some(CPrototype {
+        name: name.to_string(),
+        params,
+        variadic,
+        ret: narrow_int(ret),
+    })
+}
+
+/// whether the type is a floating point value, not a pointer to one
+fn is_float(ty: &[&CToken]) -> bool {
+    ty.iter()
+        .any(|t| matches!(t, CToken::Ident("float" | "double")))
+        && !ty
+            .iter()
+            .any(|t| matches!(t, CToken::Punct('*') | CToken::Punct('[')))
+}
+
+/// the width and signedness of an integer type narrower than a qword
+fn narrow_int(ty: &[&CToken]) -> Option<(Width, Extend)> {
+    let has = |word: &str| ty.iter().any(|t| **t == CToken::Ident(word));
+    if has("long") || ty.iter().any(|t| **t == CToken::Punct('*')) {
+        return None;
+    }
+    let (_, width, unsigned) = NARROW_INTS.iter().find(|(word, ..)| has(word))?;
+    let extend = if *unsigned || has("unsigned") {
+        Extend::Zero
+    } else {
+        Extend::Sign
+    };
+    Some((*width, extend))
+}
+
+fn param_name(param: &[&CToken], position: usize) -> String {
+    // array declarators follow the name
+    let declarator = param
+        .iter()
+        .position(|t| **t == CToken::Punct('['))
+        .map_or(param, |end| &param[..end]);
+    match declarator {
+        [_ty, .., CToken::Ident(name)] if !TYPE_WORDS.contains(name) => {
+            name.trim_start_matches('_').to_string()
+        }
+        _ => format!("arg{}", position),
+    }
+}
+
+fn split_params<'b, 'a>(tokens: &[&'b CToken<'a>]) -> Vec<Vec<&'b CToken<'a>>> {
+    let mut params = vec![Vec::new()];
+    let mut depth = 0;
+    for tok in tokens {
+        match tok {
+            CToken::Punct('(') | CToken::Punct('[') => depth += 1,
+            CToken::Punct(')') | CToken::Punct(']') => depth -= 1,
+            CToken::Punct(',') if depth == 0 => {
+                params.push(Vec::new());
+                continue;
+            }
+            _ => {}
+        }
+        params.last_mut().unwrap().push(*tok);
+    }
+    if params.len() == 1 && params[0].is_empty() {
+        params.clear();
+    }
+    params
+}
+
+fn matching_paren(tokens: &[&CToken], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, tok) in tokens.iter().enumerate().skip(open) {
+        match tok {
+            CToken::Punct('(') => depth += 1,
+            CToken::Punct(')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// removes `__attribute__((...))` and similar annotations
+fn strip_attributes<'b, 'a>(decl: &[&'b CToken<'a>]) -> Vec<&'b CToken<'a>> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < decl.len() {
+        if let CToken::Ident(ident) = decl[i]
+            && ATTRIBUTE_WORDS.contains(ident)
+            && matches!(decl.get(i + 1), Some(CToken::Punct('(')))
+            && let Some(close) = matching_paren(decl, i + 1)
+        {
+            i = close + 1;
+            continue;
+        }
+        out.push(decl[i]);
+        i += 1;
+    }
+    out
+}
+
+/// removes comments and preprocessor directives
+fn strip_preprocessor(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    let mut line_start = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                out.push('\n');
+                line_start = true;
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+                out.push(' ');
+                continue;
+            }
+            '#' if line_start => {
+                // directives may be continued with a trailing backslash
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' && last != '\\' {
+                        break;
+                    }
+                    if !c.is_whitespace() || c == '\n' {
+                        last = c;
+                    }
+                }
+                out.push('\n');
+                continue;
+            }
+            '\n' => line_start = true,
+            c if c.is_whitespace() => {}
+            _ => line_start = false,
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn tokenize(src: &str) -> Vec<CToken<'_>> {
+    let mut tokens = Vec::new();
+    let mut idc = src.char_indices().peekable();
+    while let Some((i, c)) = idc.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '"' | '\'' => {
+                let mut escaped = false;
+                for (_, next) in idc.by_ref() {
+                    if next == c && !escaped {
+                        break;
+                    }
+                    escaped = next == '\\' && !escaped;
+                }
+                tokens.push(CToken::Str);
+            }
+            '.' if src[i..].starts_with("...") => {
+                idc.next();
+                idc.next();
+                tokens.push(CToken::Ellipsis);
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, next)) = idc.peek() {
+                    if next.is_ascii_alphanumeric() || *next == '_' {
+                        end = j + next.len_utf8();
+                        idc.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(CToken::Ident(&src[i..end]));
+            }
+            c => tokens.push(CToken::Punct(c)),
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prototypes() {
+        let header = r#"
+            #ifndef FOO_H
+            #define FOO_H \
+                1
+            #include <stddef.h>
+
+            #ifdef __cplusplus
+            extern "C" {
+            #endif
+
+            typedef struct { int x; } point;
+            struct node { struct node *next; };
+            /* comment with a fake(prototype); */
+            extern void *malloc (size_t __size) __attribute__ ((__malloc__));
+            int printf(const char *__restrict __format, ...);
+            long labs(long);
+            int rand(void);
+            void (*signal(int sig, void (*func)(int)))(int);
+            static inline int twice(int x) { return 2 * x; }
+            __attribute__((nonnull(1))) size_t strlen(const char *s);
+            double sqrt(double x);
+            int round_to(float value);
+            double *samples(void);
+            unsigned char next_byte(void);
+            _Bool is_set(int flag);
+
+            #ifdef __cplusplus
+            }
+            #endif
+            #endif
+        "#;
+        let protos = parse_prototypes(header);
+        assert_eq!(
+            protos,
+            vec![
+                CPrototype {
+                    name: "malloc".into(),
+                    params: vec!["size".into()],
+                    variadic: false,
+                    ret: None,
+                },
+                CPrototype {
+                    name: "printf".into(),
+                    params: vec!["format".into()],
+                    variadic: true,
+                    ret: Some((Width::Dword, Extend::Sign)),
+                },
+                CPrototype {
+                    name: "labs".into(),
+                    params: vec!["arg0".into()],
+                    variadic: false,
+                    ret: None,
+                },
+                CPrototype {
+                    name: "rand".into(),
+                    params: vec![],
+                    variadic: false,
+                    ret: Some((Width::Dword, Extend::Sign)),
+                },
+                CPrototype {
+                    name: "strlen".into(),
+                    params: vec!["s".into()],
+                    variadic: false,
+                    ret: None,
+                },
+                CPrototype {
+                    name: "samples".into(),
+                    params: vec![],
+                    variadic: false,
+                    ret: None,
+                },
+                CPrototype {
+                    name: "next_byte".into(),
+                    params: vec![],
+                    variadic: false,
+                    ret: Some((Width::Byte, Extend::Zero)),
+                },
+                CPrototype {
+                    name: "is_set".into(),
+                    params: vec!["flag".into()],
+                    variadic: false,
+                    ret: Some((Width::Byte, Extend::Zero)),
+                },
+            ]
+        );
+    }
+}
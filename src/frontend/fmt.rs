@@ -0,0 +1,726 @@
+//! Formatter for source files.
+//! It re-prints the lossless token stream, so comments and line breaks are kept,
+//! while indentation and the spacing of tokens within a line are normalized.
+
+use crate::frontend::{
+    ast::{
+        Operation,
+        error::{AstErr, Diagnostics},
+        load_kind,
+    },
+    lexer::{LosslessStream, LosslessToken, Token, lexed_apart},
+};
+
+/// Options of the formatter
+#[derive(Debug, Clone)]
+pub struct FmtOpts {
+    /// indentation of a single level
+    pub indent: String,
+    /// remove parentheses, which do not change how an expression is parsed
+    pub drop_parens: bool,
+}
+
+impl Default for FmtOpts {
+    fn default() -> Self {
+        Self {
+            indent: "\t".into(),
+            drop_parens: false,
+        }
+    }
+}
+
+impl FmtOpts {
+    pub fn with_indent(mut self, indent: impl Into<String>) -> Self {
+        self.indent = indent.into();
+        self
+    }
+
+    pub fn with_drop_parens(mut self, drop_parens: bool) -> Self {
+        self.drop_parens = drop_parens;
+        self
+    }
+}
+
+/// Formats s. Sources with malformed tokens are not formatted
+pub fn format<'a>(s: &'a str, opts: &FmtOpts) -> Result<String, Diagnostics<'a>> {
+    let stream = LosslessStream::from_str(s).unwrap();
+    if !stream.errs.is_empty() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.errs.extend(
+            stream
+                .errs
+                .into_iter()
+                .map(|err| AstErr::Lex(err.inner).at(err.span)),
+        );
+        return Err(diagnostics);
+    }
+
+    let mut formatter = Formatter {
+        tokens: &stream.tokens,
+        roles: vec![Role::Plain; stream.tokens.len()],
+        opts,
+    };
+    formatter.annotate();
+    Ok(formatter.print())
+}
+
+/// How a token is spaced from its neighbours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    /// spaced like in the source, unless the tokens require otherwise
+    Plain,
+    /// a prefix operator, which is attached to its operand
+    Prefix,
+    /// a binary operator. Tight operators bind stronger than the loosest operator of their expression
+    Binary { tight: bool },
+    /// a redundant parenthesis, which is not printed
+    Dropped,
+    /// `<` and `>` of a pointer type
+    TypeAngle,
+    /// `:` in front of a type
+    TypeColon,
+    /// `:` in front of the lvalue receiving the result of a call
+    ResultColon,
+    /// `->` in front of a return type
+    RetArrow,
+    /// `->` of a field access
+    FieldArrow,
+    /// `(` of `sizeof(..)` and the `load` builtins
+    CallParen,
+}
+
+struct Formatter<'t, 'a> {
+    tokens: &'t [LosslessToken<'a>],
+    roles: Vec<Role>,
+    opts: &'t FmtOpts,
+}
+
+impl Formatter<'_, '_> {
+    fn token(&self, i: usize) -> &Token<'_> {
+        &self.tokens[i].token.inner
+    }
+
+    /// assigns the roles of all tokens, statement by statement
+    fn annotate(&mut self) {
+        let mut statement = Vec::new();
+        let mut brackets = 0usize;
+        for i in 0..self.tokens.len() {
+            match self.token(i) {
+                Token::Comment | Token::DocComment(_) => continue,
+                Token::OpenBracket => brackets += 1,
+                Token::CloseBracket => brackets = brackets.saturating_sub(1),
+                Token::Semi | Token::EOF | Token::Keyword("end_def") if brackets == 0 => {
+                    self.statement(&std::mem::take(&mut statement));
+                    continue;
+                }
+                _ => {}
+            }
+            statement.push(i);
+        }
+    }
+
+    fn statement(&mut self, st: &[usize]) {
+        let Some(&first) = st.first() else {
+            return;
+        };
+        let eq = self.top_level(st, |token| *token == Token::Eq);
+        match self.token(first) {
+            Token::Keyword("cfg" | "if") => self.expr(&st[1..]),
+            Token::Keyword(
+                "public" | "begin_def" | "extern_def" | "static" | "const" | "extern_static",
+            ) => {
+                let (head, init) = match eq.first() {
+                    Some(&eq) => (&st[..eq], &st[eq + 1..]),
+                    None => (st, &[][..]),
+                };
+                self.signature(head);
+                self.expr(init);
+            }
+            Token::Keyword(_) => {}
+            Token::Ident(_) | Token::Star => match eq.first() {
+                Some(&eq) => {
+                    let lvalue = &st[..eq];
+                    if lvalue.len() > 1 && *self.token(lvalue[1]) == Token::Colon {
+                        self.signature(lvalue);
+                    } else {
+                        self.expr(lvalue);
+                    }
+                    self.expr(&st[eq + 1..]);
+                }
+                None => self.call(st),
+            },
+            _ => {}
+        }
+    }
+
+    /// the positions of the tokens in st matching is_sep outside of parentheses and brackets
+    fn top_level(&self, st: &[usize], is_sep: impl Fn(&Token) -> bool) -> Vec<usize> {
+        let mut depth = 0usize;
+        let mut found = Vec::new();
+        for (pos, &i) in st.iter().enumerate() {
+            match self.token(i) {
+                Token::OpenParen | Token::OpenBracket => depth += 1,
+                Token::CloseParen | Token::CloseBracket => depth = depth.saturating_sub(1),
+                token if depth == 0 && is_sep(token) => found.push(pos),
+                _ => {}
+            }
+        }
+        found
+    }
+
+    /// annotated arguments and declarations, return types and struct fields
+    fn signature(&mut self, st: &[usize]) {
+        for &i in st {
+            self.roles[i] = match self.token(i) {
+                Token::Colon => Role::TypeColon,
+                Token::Arrow => Role::RetArrow,
+                Token::Lt | Token::Gt | Token::Shr | Token::Sar => Role::TypeAngle,
+                _ => continue,
+            };
+        }
+    }
+
+    /// `<func> [ <expr> ( ',' <expr> )* ] [ ':' <lvalue> ]`
+    fn call(&mut self, st: &[usize]) {
+        let mut start = 1;
+        while start + 1 < st.len() && *self.token(st[start]) == Token::PathSep {
+            start += 2;
+        }
+        let args = &st[start.min(st.len())..];
+        let mut from = 0;
+        let seps = self.top_level(args, |token| matches!(token, Token::Comma | Token::Colon));
+        for sep in seps.into_iter().chain([args.len()]) {
+            self.expr(&args[from..sep]);
+            if let Some(&i) = args.get(sep)
+                && *self.token(i) == Token::Colon
+            {
+                self.roles[i] = Role::ResultColon;
+            }
+            from = sep + 1;
+        }
+    }
+
+    /// assigns the roles of an expression. Expressions interrupted by comments are left as they are
+    fn expr(&mut self, code: &[usize]) {
+        let (Some(first), Some(last)) = (code.first(), code.last()) else {
+            return;
+        };
+        if last - first + 1 != code.len() {
+            return;
+        }
+        let mut parser = ExprParser::new(self.tokens, code);
+        let Some(mut node) = parser.parse() else {
+            return;
+        };
+        for (i, role) in parser.roles {
+            self.roles[i] = role;
+        }
+
+        if self.opts.drop_parens {
+            node.drop_parens(Context::Top);
+            let kept = code
+                .iter()
+                .copied()
+                .filter(|&i| !node.is_dropped(i))
+                .collect::<Vec<_>>();
+            // dropping is decided locally, so make sure the whole expression still parses the same
+            let reparsed = ExprParser::new(self.tokens, &kept).parse();
+            if reparsed.is_none_or(|reparsed| reparsed.shape() != node.shape()) {
+                node.keep_parens();
+            }
+        }
+        node.annotate(&mut self.roles, None);
+    }
+
+    fn print(&self) -> String {
+        let mut out = String::new();
+        let mut depth = 0usize;
+        // the last statement is not terminated yet, so a new line continues it
+        let mut open_statement = false;
+        let mut prev = None;
+        let mut pending_newlines = 0;
+
+        for (i, token) in self.tokens.iter().enumerate() {
+            let newlines = pending_newlines + token.leading.matches('\n').count();
+            if self.roles[i] == Role::Dropped {
+                pending_newlines = newlines;
+                continue;
+            }
+            pending_newlines = 0;
+            let inner = &token.token.inner;
+            if *inner == Token::EOF {
+                break;
+            }
+
+            if let Some(prev) = prev {
+                if newlines > 0 {
+                    out.push_str(if newlines > 1 { "\n\n" } else { "\n" });
+                    let mut level = depth;
+                    if *inner == Token::Keyword("end_def") {
+                        level = level.saturating_sub(1);
+                    } else if open_statement && *inner != Token::CloseBrace {
+                        level += 1;
+                    }
+                    out.push_str(&self.opts.indent.repeat(level));
+                } else if self.spaced(prev, i) {
+                    out.push(' ');
+                }
+            }
+
+            match inner {
+                Token::Comment | Token::DocComment(_) if !token.text.starts_with("#[") => {
+                    out.push_str(token.text.trim_end());
+                }
+                _ => out.push_str(token.text),
+            }
+
+            match inner {
+                Token::Comment | Token::DocComment(_) => {}
+                Token::Keyword("begin_def") => {
+                    depth += 1;
+                    open_statement = true;
+                }
+                Token::Keyword("end_def") => {
+                    depth = depth.saturating_sub(1);
+                    open_statement = false;
+                }
+                Token::Semi => open_statement = false,
+                _ => open_statement = true,
+            }
+            prev = Some(i);
+        }
+
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+
+    /// whether a space separates the tokens a and b on the same line
+    fn spaced(&self, a: usize, b: usize) -> bool {
+        let (ta, tb) = (self.token(a), self.token(b));
+        let (ra, rb) = (self.roles[a], self.roles[b]);
+        let spaced = match (ta, tb) {
+            (Token::Comment | Token::DocComment(_), _)
+            | (_, Token::Comment | Token::DocComment(_)) => true,
+            (_, Token::Semi | Token::Comma) => false,
+            (Token::Comma | Token::Semi | Token::Eq | Token::Keyword(_), _)
+            | (_, Token::Eq | Token::Keyword(_)) => ra != Role::CallParen && rb != Role::CallParen,
+            _ if ra == Role::RetArrow || rb == Role::RetArrow => true,
+            _ if ra == Role::ResultColon || rb == Role::ResultColon => true,
+            _ if rb == Role::TypeColon => false,
+            _ if ra == Role::TypeColon => true,
+            (Token::PathSep, _) | (_, Token::PathSep) => false,
+            _ if ra == Role::FieldArrow || rb == Role::FieldArrow => false,
+            _ if rb == Role::CallParen => false,
+            (Token::OpenParen | Token::OpenBracket, _)
+            | (_, Token::CloseParen | Token::CloseBracket) => false,
+            (Token::OpenBrace, _) | (_, Token::OpenBrace | Token::CloseBrace) => true,
+            _ if ra == Role::TypeAngle || rb == Role::TypeAngle => false,
+            _ if ra == Role::Prefix => false,
+            (_, _) if let Role::Binary { tight } = ra => !tight,
+            (_, _) if let Role::Binary { tight } = rb => !tight,
+            _ if rb == Role::Prefix => true,
+            (
+                Token::Ident(_) | Token::Number(_) | Token::RadixNumber(_) | Token::Lit(_),
+                Token::Ident(_) | Token::Number(_) | Token::RadixNumber(_) | Token::Lit(_),
+            ) => true,
+            _ => !self.tokens[b].leading.is_empty(),
+        };
+        spaced || !lexed_apart(self.tokens[a].text, self.tokens[b].text)
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    /// a value, together with the expressions inside it, i.e. of arrays and loads
+    Atom(Vec<Node>),
+    Prefix(Operation, Box<Node>),
+    Field(Box<Node>),
+    /// lhs, operator, position of the operator token, rhs
+    Binary(Box<Node>, Operation, usize, Box<Node>),
+    Paren {
+        open: usize,
+        close: usize,
+        inner: Box<Node>,
+        dropped: bool,
+    },
+}
+
+/// Position of an expression within its parent
+#[derive(Debug, Clone, Copy)]
+enum Context {
+    Top,
+    Left(Operation),
+    Right(Operation),
+    Prefix(Operation),
+    Field,
+}
+
+impl Node {
+    /// the node, skipping dropped parentheses
+    fn visible(&self) -> &Self {
+        match self {
+            Self::Paren {
+                inner,
+                dropped: true,
+                ..
+            } => inner.visible(),
+            node => node,
+        }
+    }
+
+    /// operators of the binary expressions along the left or right edge of the node
+    fn spine(&self, left: bool) -> Vec<Operation> {
+        let mut ops = Vec::new();
+        let mut node = self.visible();
+        while let Self::Binary(lhs, op, _, rhs) = node {
+            ops.push(*op);
+            node = if left { lhs } else { rhs }.visible();
+        }
+        ops
+    }
+
+    fn drop_parens(&mut self, ctx: Context) {
+        match self {
+            Self::Atom(children) => children
+                .iter_mut()
+                .for_each(|child| child.drop_parens(Context::Top)),
+            Self::Prefix(op, rhs) => rhs.drop_parens(Context::Prefix(*op)),
+            Self::Field(lhs) => lhs.drop_parens(Context::Field),
+            Self::Binary(lhs, op, _, rhs) => {
+                lhs.drop_parens(Context::Left(*op));
+                rhs.drop_parens(Context::Right(*op));
+            }
+            Self::Paren { inner, .. } => {
+                inner.drop_parens(Context::Top);
+                let redundant = match (inner.visible(), ctx) {
+                    (_, Context::Top) => true,
+                    // `-(1)` is a negation, but `-1` a literal
+                    (Self::Atom(_), Context::Prefix(Operation::Neg)) => false,
+                    (Self::Atom(_) | Self::Field(_), _) => true,
+                    (Self::Prefix(..), ctx) => !matches!(ctx, Context::Field),
+                    (Self::Binary(..), Context::Left(parent)) => {
+                        // the operators on the right edge must not take the parent operator as operand
+                        let (_, r) = parent.infix_power();
+                        inner.spine(false).iter().all(|op| r < op.infix_power().0)
+                    }
+                    (Self::Binary(..), Context::Right(parent)) => {
+                        let (l, _) = parent.infix_power();
+                        inner.spine(true).iter().all(|op| op.infix_power().1 >= l)
+                    }
+                    _ => false,
+                };
+                if let Self::Paren { dropped, .. } = self {
+                    *dropped = redundant;
+                }
+            }
+        }
+    }
+
+    fn keep_parens(&mut self) {
+        match self {
+            Self::Atom(children) => children.iter_mut().for_each(Self::keep_parens),
+            Self::Prefix(_, node) | Self::Field(node) => node.keep_parens(),
+            Self::Binary(lhs, _, _, rhs) => {
+                lhs.keep_parens();
+                rhs.keep_parens();
+            }
+            Self::Paren { inner, dropped, .. } => {
+                *dropped = false;
+                inner.keep_parens();
+            }
+        }
+    }
+
+    fn is_dropped(&self, i: usize) -> bool {
+        match self {
+            Self::Atom(children) => children.iter().any(|child| child.is_dropped(i)),
+            Self::Prefix(_, node) | Self::Field(node) => node.is_dropped(i),
+            Self::Binary(lhs, _, _, rhs) => lhs.is_dropped(i) || rhs.is_dropped(i),
+            Self::Paren {
+                open,
+                close,
+                inner,
+                dropped,
+            } => (*dropped && (*open == i || *close == i)) || inner.is_dropped(i),
+        }
+    }
+
+    /// the structure of the node, ignoring parentheses
+    fn shape(&self) -> String {
+        match self {
+            Self::Atom(children) => format!(
+                "[{}]",
+                children
+                    .iter()
+                    .map(Self::shape)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Self::Prefix(_, node) => format!("p{}", node.shape()),
+            Self::Field(node) => format!("f{}", node.shape()),
+            Self::Binary(lhs, _, i, rhs) => format!("({} {} {})", lhs.shape(), i, rhs.shape()),
+            Self::Paren { inner, .. } => inner.shape(),
+        }
+    }
+
+    /// spaces the operators of a chain of binary expressions.
+    /// Arithmetic binding stronger than the loosest operator of the chain is written without spaces, e.g. `a + b*2`
+    fn annotate(&self, roles: &mut [Role], chain: Option<f32>) {
+        match self {
+            Self::Atom(children) => children
+                .iter()
+                .for_each(|child| child.annotate(roles, None)),
+            Self::Prefix(_, node) | Self::Field(node) => node.annotate(roles, None),
+            Self::Binary(lhs, op, i, rhs) => {
+                let loosest = chain.unwrap_or_else(|| self.loosest());
+                let arithmetic = matches!(
+                    op,
+                    Operation::Add
+                        | Operation::Sub
+                        | Operation::Mul
+                        | Operation::Div
+                        | Operation::Mod
+                        | Operation::Shl
+                        | Operation::Shr
+                        | Operation::Sar
+                );
+                roles[*i] = Role::Binary {
+                    tight: arithmetic && op.infix_power().0 > loosest,
+                };
+                for child in [lhs, rhs] {
+                    let chain = matches!(child.visible(), Self::Binary(..)).then_some(loosest);
+                    child.annotate(roles, chain);
+                }
+            }
+            Self::Paren {
+                open,
+                close,
+                inner,
+                dropped,
+            } => {
+                if *dropped {
+                    roles[*open] = Role::Dropped;
+                    roles[*close] = Role::Dropped;
+                    inner.annotate(roles, chain);
+                } else {
+                    inner.annotate(roles, None);
+                }
+            }
+        }
+    }
+
+    /// the binding power of the loosest operator of the chain of binary expressions starting at self
+    fn loosest(&self) -> f32 {
+        match self.visible() {
+            Self::Binary(lhs, op, _, rhs) => {
+                op.infix_power().0.min(lhs.loosest()).min(rhs.loosest())
+            }
+            _ => f32::INFINITY,
+        }
+    }
+}
+
+/// Parses an expression like [crate::frontend::ast::parser] does, recording the roles of its tokens
+struct ExprParser<'t, 'a> {
+    tokens: &'t [LosslessToken<'a>],
+    code: &'t [usize],
+    pos: usize,
+    roles: Vec<(usize, Role)>,
+}
+
+impl<'t, 'a> ExprParser<'t, 'a> {
+    fn new(tokens: &'t [LosslessToken<'a>], code: &'t [usize]) -> Self {
+        Self {
+            tokens,
+            code,
+            pos: 0,
+            roles: Vec::new(),
+        }
+    }
+
+    /// the whole expression, None if code is not a single expression
+    fn parse(&mut self) -> Option<Node> {
+        let node = self.expr(0.)?;
+        (self.pos == self.code.len()).then_some(node)
+    }
+
+    fn peekn(&self, n: usize) -> Option<&'t Token<'a>> {
+        self.code
+            .get(self.pos + n)
+            .map(|&i| &self.tokens[i].token.inner)
+    }
+
+    fn expect(&mut self, token: Token) -> Option<usize> {
+        let i = *self.code.get(self.pos)?;
+        (self.tokens[i].token.inner == token).then(|| {
+            self.pos += 1;
+            i
+        })
+    }
+
+    fn expr(&mut self, min_bp: f32) -> Option<Node> {
+        let at = *self.code.get(self.pos)?;
+        let mut lhs = match self.peekn(0)? {
+            Token::Keyword("sizeof") => {
+                self.pos += 1;
+                let open = self.expect(Token::OpenParen)?;
+                self.roles.push((open, Role::CallParen));
+                self.ident()?;
+                self.expect(Token::CloseParen)?;
+                Node::Atom(Vec::new())
+            }
+            Token::Ident(name)
+                if self.peekn(1) == Some(&Token::OpenParen) && load_kind(name).is_some() =>
+            {
+                self.pos += 1;
+                let open = self.expect(Token::OpenParen)?;
+                self.roles.push((open, Role::CallParen));
+                let ptr = self.expr(0.)?;
+                self.expect(Token::CloseParen)?;
+                Node::Atom(vec![ptr])
+            }
+            Token::Sub
+                if matches!(
+                    self.peekn(1),
+                    Some(Token::Number(_) | Token::RadixNumber(_))
+                ) =>
+            {
+                self.roles.push((at, Role::Prefix));
+                self.pos += 2;
+                Node::Atom(Vec::new())
+            }
+            Token::Ident(_) | Token::Lit(_) | Token::Number(_) | Token::RadixNumber(_) => {
+                self.pos += 1;
+                Node::Atom(Vec::new())
+            }
+            Token::OpenBracket => {
+                self.pos += 1;
+                let init = self.expr(0.)?;
+                self.expect(Token::Semi)?;
+                let len = self.expr(0.)?;
+                self.expect(Token::CloseBracket)?;
+                Node::Atom(vec![init, len])
+            }
+            Token::OpenParen => {
+                self.pos += 1;
+                let inner = self.expr(0.)?;
+                let close = self.expect(Token::CloseParen)?;
+                Node::Paren {
+                    open: at,
+                    close,
+                    inner: Box::new(inner),
+                    dropped: false,
+                }
+            }
+            _ => {
+                let op = Operation::try_from_token_as_single(&self.tokens[at].token)?;
+                self.roles.push((at, Role::Prefix));
+                self.pos += 1;
+                Node::Prefix(op, Box::new(self.expr(op.infix_power().0)?))
+            }
+        };
+
+        while let Some(&i) = self.code.get(self.pos)
+            && self.tokens[i].token.inner == Token::Arrow
+        {
+            self.roles.push((i, Role::FieldArrow));
+            self.pos += 1;
+            self.ident()?;
+            if self.peekn(0) == Some(&Token::PathSep) {
+                self.pos += 1;
+                self.ident()?;
+            }
+            lhs = Node::Field(Box::new(lhs));
+        }
+
+        while let Some(&i) = self.code.get(self.pos)
+            && let Some(op) = Operation::try_from_token(&self.tokens[i].token)
+        {
+            let (l, r) = op.infix_power();
+            if r < min_bp {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.expr(l)?;
+            lhs = Node::Binary(Box::new(lhs), op, i, Box::new(rhs));
+        }
+        Some(lhs)
+    }
+
+    fn ident(&mut self) -> Option<()> {
+        matches!(self.peekn(0)?, Token::Ident(_)).then(|| self.pos += 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+    use crate::frontend::{ast::cfg::CfgEnv, get_ast};
+
+    #[test]
+    fn format() {
+        let s = "
+# comment
+ begin_def main a:ptr<ptr<u8>> ,b->u8;
+    x=1+ 2*3;#[ block ]#
+  *a = - b ;   # trailing
+
+
+        if x>=2&&b; y = ((x + 1)) * (a->next) - (-1);
+   print_str *a , sizeof( Node ) : x;
+ end_def
+";
+        let expected = "\
+# comment
+begin_def main a: ptr<ptr<u8>>, b -> u8;
+\tx = 1 + 2*3; #[ block ]#
+\t*a = -b; # trailing
+
+\tif x >= 2 && b; y = ((x + 1))*(a->next) - (-1);
+\tprint_str *a, sizeof(Node) : x;
+end_def
+";
+        assert_eq!(super::format(s, &FmtOpts::default()).unwrap(), expected);
+        assert_eq!(
+            super::format(s, &FmtOpts::default().with_drop_parens(true))
+                .unwrap()
+                .lines()
+                .nth(5),
+            Some("\tif x >= 2 && b; y = (x + 1)*a->next - -1;")
+        );
+    }
+
+    /// formatting keeps the ast unchanged and formatting twice is the same as formatting once
+    #[test]
+    fn lib() {
+        let lib = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("lib/std");
+        let mut dirs = vec![lib];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                if path.extension().is_none_or(|ext| ext != "lang") {
+                    continue;
+                }
+                let s = fs::read_to_string(&path).unwrap();
+                let opts = FmtOpts::default().with_drop_parens(true);
+                let formatted = super::format(&s, &opts).unwrap();
+                let env = CfgEnv::default().populate(&["test".into()]);
+                assert_eq!(
+                    get_ast(&s, &env).0.to_string(),
+                    get_ast(&formatted, &env).0.to_string(),
+                    "{}",
+                    path.display()
+                );
+                assert_eq!(super::format(&formatted, &opts).unwrap(), formatted);
+            }
+        }
+    }
+}
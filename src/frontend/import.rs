@@ -0,0 +1,451 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::frontend::{
+    ast::{
+        Abi, Arity, Ast, ConstValue, Expr, Function, Global, GlobalKind, Import, Item, Line,
+        LinkAttr, Struct, Val,
+        cfg::CfgEnv,
+        error::{AstErr, Diagnostics, Spanned},
+        is_builtin_func,
+        sema::eval_const,
+    },
+    c_header::parse_prototypes,
+    get_ast,
+    lexer::Span,
+};
+
+/// Resolves the imports of a single file
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    dir: PathBuf,
+    include_dirs: Vec<PathBuf>,
+    lib_roots: Vec<PathBuf>,
+    extension: String,
+}
+
+impl Resolver {
+    pub fn new(importer: &Path) -> Self {
+        Self {
+            dir: importer.parent().unwrap_or(Path::new(".")).to_path_buf(),
+            include_dirs: Vec::new(),
+            lib_roots: Vec::new(),
+            extension: "lang".into(),
+        }
+    }
+
+    /// directories searched for C headers
+    pub fn with_include_dirs(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.include_dirs.extend(dirs);
+        self
+    }
+
+    /// directories searched for modules, e.g. `use std::mem;` resolves to `<root>/std/mem.<ext>`
+    pub fn with_lib_roots(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.lib_roots.extend(dirs);
+        self
+    }
+
+    pub fn with_extension(mut self, extension: &str) -> Self {
+        self.extension = extension.into();
+        self
+    }
+
+    /// searches the import relative to the importing file, then in the include directories for C headers or library roots for modules
+    pub fn resolve(&self, import: &Import) -> Option<PathBuf> {
+        let (path, search_dirs) = match import {
+            Import::File(path) if is_c_header(path) => (PathBuf::from(path), &self.include_dirs),
+            Import::File(path) => (PathBuf::from(path), &self.lib_roots),
+            Import::Module(segments) => (
+                segments
+                    .iter()
+                    .collect::<PathBuf>()
+                    .with_extension(&self.extension),
+                &self.lib_roots,
+            ),
+        };
+        std::iter::once(&self.dir)
+            .chain(search_dirs.iter())
+            .map(|dir| dir.join(&path))
+            .find(|candidate| candidate.is_file())
+            .map(|found| fs::canonicalize(&found).unwrap_or(found))
+    }
+
+    /// the module path of a file. Files in a library root are qualified by their path relative to it,
+    /// e.g. `<root>/std/collections/vec.lang` is `std::collections::vec`, all other files by their name only
+    pub fn module_path(&self, file: &Path) -> Vec<String> {
+        let file = fs::canonicalize(file).unwrap_or(file.to_path_buf());
+        self.lib_roots
+            .iter()
+            .find_map(|root| {
+                let root = fs::canonicalize(root).unwrap_or(root.clone());
+                file.strip_prefix(root).ok().map(Path::to_path_buf)
+            })
+            .unwrap_or_else(|| file.file_name().map(PathBuf::from).unwrap_or_default())
+            .with_extension("")
+            .iter()
+            .map(|segment| segment.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// declares all public functions of imported modules and all referenced functions of imported C headers
+    pub fn resolve_imports(&self, ast: &mut Ast, cfg_env: &CfgEnv, diagnostics: &mut Diagnostics) {
+        let referenced = referenced_idents(ast);
+        let imports = ast.imports().cloned().collect::<Vec<_>>();
+        let mut modules = Vec::new();
+
+        for import in imports {
+            let Some(source) = self
+                .resolve(&import.inner)
+                .and_then(|resolved| fs::read_to_string(resolved).ok())
+            else {
+                diagnostics.errs.push(
+                    AstErr::UnresolvedImport {
+                        path: import.inner.to_string(),
+                    }
+                    .at(import.span),
+                );
+                continue;
+            };
+
+            match &import.inner {
+                Import::File(path) if is_c_header(path) => {}
+                Import::File(path) => {
+                    let module = get_ast(&source, cfg_env).0;
+                    declare_module_exports(ast, &module, &import.span);
+                    let path = Path::new(path).with_extension("");
+                    modules.push((
+                        path.iter()
+                            .map(|segment| segment.to_string_lossy().into_owned())
+                            .collect(),
+                        module,
+                    ));
+                    continue;
+                }
+                Import::Module(path) => {
+                    let module = get_ast(&source, cfg_env).0;
+                    declare_module_exports(ast, &module, &import.span);
+                    modules.push((path.clone(), module));
+                    continue;
+                }
+            }
+
+            for proto in parse_prototypes(&source) {
+                if !referenced.contains(&proto.name) {
+                    continue;
+                }
+                let arity = if proto.variadic {
+                    Arity::AtLeast(proto.params.len())
+                } else {
+                    Arity::Exact(proto.params.len())
+                };
+                ast.declare(Function {
+                    name: proto.name,
+                    body: None,
+                    arg_types: vec![None; proto.params.len()],
+                    arg_spans: vec![import.span.clone(); proto.params.len()],
+                    args: proto.params,
+                    ret: None,
+                    arity,
+                    link_attr: LinkAttr::default().into_external().with_abi(Abi::C {
+                        variadic: proto.variadic,
+                        ret: proto.ret,
+                    }),
+                    doc: None,
+                    cfg: None,
+                    span: import.span.clone(),
+                    name_span: import.span.clone(),
+                });
+            }
+        }
+
+        resolve_paths(ast, &modules, diagnostics);
+    }
+}
+
+fn is_c_header(path: &str) -> bool {
+    path.ends_with(".h")
+}
+
+/// declares all public functions, globals and structs defined in module, spanning the import at span.
+/// Constants are copied with their value, as they are inlined at each use
+fn declare_module_exports(ast: &mut Ast, module: &Ast, span: &Span) {
+    for item in module.funcs() {
+        match item {
+            Item::Function(func) if func.link_attr.is_public && !func.link_attr.external => {
+                ast.declare(Function {
+                    name: func.name.clone(),
+                    body: None,
+                    args: func.args.clone(),
+                    arg_spans: vec![span.clone(); func.args.len()],
+                    arg_types: func.arg_types.clone(),
+                    ret: func.ret.clone(),
+                    arity: Arity::Exact(func.args.len()),
+                    link_attr: LinkAttr::default()
+                        .into_external()
+                        .with_abi(func.link_attr.abi),
+                    doc: func.doc.clone(),
+                    cfg: None,
+                    span: span.clone(),
+                    name_span: span.clone(),
+                });
+            }
+            Item::Global(global) if global.link_attr.is_public && !global.link_attr.external => {
+                let (link_attr, value) = match global.kind {
+                    GlobalKind::Static => (LinkAttr::default().into_external(), None),
+                    GlobalKind::Const => {
+                        let Some(value) = global
+                            .value
+                            .as_ref()
+                            .and_then(|value| eval_const(&value.inner, module).ok())
+                        else {
+                            continue;
+                        };
+                        let value = match value {
+                            ConstValue::Int(value) => Val::V(value),
+                            ConstValue::Str(lit) => Val::Lit(lit),
+                            // arrays may only initialize statics
+                            ConstValue::Array { .. } => continue,
+                        };
+                        (
+                            LinkAttr::default(),
+                            Some(Spanned {
+                                inner: Expr::Val(value),
+                                span: span.clone(),
+                            }),
+                        )
+                    }
+                };
+                ast.declare_global(Global {
+                    name: global.name.clone(),
+                    kind: global.kind,
+                    value,
+                    link_attr,
+                    span: span.clone(),
+                    name_span: span.clone(),
+                });
+            }
+            Item::Struct(strukt) if strukt.link_attr.is_public => {
+                ast.declare_struct(Struct {
+                    name: strukt.name.clone(),
+                    fields: strukt.fields.clone(),
+                    field_spans: vec![span.clone(); strukt.fields.len()],
+                    link_attr: LinkAttr::default(),
+                    span: span.clone(),
+                    name_span: span.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// replaces module qualified calls with the called public function and rejects calls to private functions of imported modules
+fn resolve_paths(ast: &mut Ast, modules: &[(Vec<String>, Ast)], diagnostics: &mut Diagnostics) {
+    fn resolve_call(
+        name: &mut String,
+        declared: &HashSet<String>,
+        modules: &[(Vec<String>, Ast)],
+    ) -> Result<(), AstErr<'static>> {
+        let Some((qualifier, func_name)) = name.rsplit_once("::") else {
+            if declared.contains(name) || is_builtin_func(name) {
+                return Ok(());
+            }
+            return match modules
+                .iter()
+                .find(|(_, module)| module.get_func(name).is_some())
+            {
+                Some((path, _)) => Err(AstErr::PrivateFunction {
+                    name: name.clone(),
+                    module: path.join("::"),
+                }),
+                None => Ok(()),
+            };
+        };
+
+        let qualifier = qualifier.split("::").map(String::from).collect::<Vec<_>>();
+        let resolved = modules
+            .iter()
+            .filter(|(path, _)| path.ends_with(&qualifier))
+            .find_map(|(path, module)| module.get_func(func_name).map(|func| (path, func)));
+
+        match resolved {
+            Some((_, func)) if func.link_attr.is_public && !func.link_attr.external => {
+                *name = func_name.to_string();
+                Ok(())
+            }
+            Some((path, func)) if !func.link_attr.is_public => Err(AstErr::PrivateFunction {
+                name: func_name.to_string(),
+                module: path.join("::"),
+            }),
+            _ => Err(AstErr::UnresolvedPath { path: name.clone() }),
+        }
+    }
+
+    fn visit_line(
+        line: &mut Line,
+        declared: &HashSet<String>,
+        modules: &[(Vec<String>, Ast)],
+    ) -> Result<(), AstErr<'static>> {
+        match line {
+            Line::Call(name, _, _) => resolve_call(name, declared, modules),
+            Line::Cond(_, then) => visit_line(then, declared, modules),
+            _ => Ok(()),
+        }
+    }
+
+    let declared = ast
+        .funcs()
+        .filter_map(|item| item.name().map(String::from))
+        .collect::<HashSet<_>>();
+
+    for item in ast.funcs_mut() {
+        let Item::Function(func) = item else {
+            continue;
+        };
+        for Spanned { inner, span } in func.body.iter_mut().flatten() {
+            if let Err(e) = visit_line(inner, &declared, modules) {
+                diagnostics.errs.push(e.at(span.clone()));
+            }
+        }
+    }
+}
+
+/// all names of called functions and referenced variables
+fn referenced_idents(ast: &Ast) -> HashSet<String> {
+    fn visit_expr(expr: &Expr, idents: &mut HashSet<String>) {
+        match expr {
+            Expr::Val(Val::Var(var)) => _ = idents.insert(var.clone()),
+            Expr::Op(lhs, _, rhs) => {
+                visit_expr(lhs, idents);
+                visit_expr(rhs, idents);
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_line(line: &Line, idents: &mut HashSet<String>) {
+        match line {
+            Line::Expr(expr) | Line::Decl(_, _, expr) => visit_expr(expr, idents),
+            Line::Call(name, args, _) => {
+                idents.insert(name.inner.clone());
+                args.iter().for_each(|arg| visit_expr(arg, idents));
+            }
+            Line::Cond(cond, then) => {
+                visit_expr(cond, idents);
+                visit_line(then, idents);
+            }
+            Line::Malformed => {}
+        }
+    }
+
+    let mut idents = HashSet::new();
+    for item in ast.funcs() {
+        if let Item::Function(func) = item
+            && let Some(body) = func.body()
+        {
+            body.for_each(|line| visit_line(line, &mut idents));
+        }
+    }
+    idents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_imports() {
+        let lib_root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("lib");
+        let importer = lib_root.join("../examples/main.lang");
+        let resolver = Resolver::new(&importer).with_lib_roots([lib_root.clone()]);
+
+        let s = "
+            use std::mem;
+            import \"std/collections/vec.lang\";
+            use std::does_not_exist;
+            begin_def main;
+                call_malloc 8, 2;
+            end_def
+        ";
+        let (mut ast, _) = get_ast(s, &CfgEnv::default());
+        assert_eq!(
+            ast.imports()
+                .map(|i| i.inner.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "std::mem",
+                "\"std/collections/vec.lang\"",
+                "std::does_not_exist"
+            ]
+        );
+        assert_eq!(
+            resolver.resolve(&Import::Module(vec!["std".into(), "mem".into()])),
+            fs::canonicalize(lib_root.join("std/mem.lang")).ok()
+        );
+
+        let mut diagnostics = Diagnostics::new();
+        resolver.resolve_imports(&mut ast, &CfgEnv::default(), &mut diagnostics);
+        assert_eq!(diagnostics.errs.len(), 1);
+
+        let malloc = ast.get_func("call_malloc").unwrap();
+        assert!(malloc.link_attr.external);
+        assert_eq!(malloc.arity, Arity::Exact(1));
+        assert!(ast.get_func("free").is_none());
+    }
+
+    #[test]
+    fn qualified_calls() {
+        let lib_root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("lib");
+        let resolver =
+            Resolver::new(&lib_root.join("main.lang")).with_lib_roots([lib_root.clone()]);
+        assert_eq!(
+            resolver.module_path(&lib_root.join("std/collections/vec.lang")),
+            vec!["std", "collections", "vec"]
+        );
+
+        let s = "
+            use std::collections::vec;
+            begin_def main;
+                vec::vec_len 0;
+                collections::vec::vec_cap 0;
+                vec::__bound_violation 1, 2, 3;
+                mem::call_free 0;
+            end_def
+        ";
+        let (mut ast, _) = get_ast(s, &CfgEnv::default());
+        let mut diagnostics = Diagnostics::new();
+        resolver.resolve_imports(&mut ast, &CfgEnv::default(), &mut diagnostics);
+
+        let errs = diagnostics
+            .errs
+            .into_iter()
+            .map(|e| e.inner)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errs,
+            vec![
+                AstErr::PrivateFunction {
+                    name: "__bound_violation".into(),
+                    module: "std::collections::vec".into()
+                },
+                AstErr::UnresolvedPath {
+                    path: "mem::call_free".into()
+                }
+            ]
+        );
+        let calls = ast
+            .get_func("main")
+            .and_then(|main| main.body())
+            .unwrap()
+            .filter_map(|line| match line {
+                Line::Call(name, _, _) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(calls[..2], ["vec_len", "vec_cap"]);
+    }
+}
@@ -1,4 +1,9 @@
-use std::{fmt::Display, ops::Deref};
+use std::{
+    borrow::Cow,
+    fmt::{Debug, Display},
+    iter::Peekable,
+    ops::Deref,
+};
 
 use crate::frontend::ast::error::Spanned;
 
@@ -9,28 +14,17 @@ static KEYWORDS: &[&str] = &[
     "extern_def",
     "public",
     "link_attr",
+    "lint_attr",
     "cfg",
+    "import",
+    "use",
+    "static",
+    "const",
+    "extern_static",
+    "struct",
+    "sizeof",
 ];
 
-impl<'a> Spanned<Token<'a>> {
-    fn new(token: Token<'a>, span: Span) -> Self {
-        Self { inner: token, span }
-    }
-}
-
-impl<'a> Deref for Spanned<Token<'a>> {
-    type Target = Token<'a>;
-    fn deref(&self) -> &Self::Target {
-        self.as_ref()
-    }
-}
-
-impl<'a> AsRef<Token<'a>> for Spanned<Token<'a>> {
-    fn as_ref(&self) -> &Token<'a> {
-        &self.inner
-    }
-}
-
 impl<'a> Display for Spanned<Token<'a>> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "{}", self.as_ref())
@@ -44,6 +38,11 @@ pub struct Span {
 }
 
 impl Span {
+    /// whether offset lies within the span. The end is inclusive, so that a cursor right after a token still hits it
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset <= self.end
+    }
+
     pub fn merge(mut self, other: Span) -> Self {
         self.end = other.end.max(self.end);
         self.start = other.start.midpoint(self.start);
@@ -55,11 +54,17 @@ impl Span {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token<'a> {
     Ident(&'a str),
-    Number(i64),
-    Lit(&'a str),
+    /// decimal or char literal. Literals too large for a u64 saturate, the parser reports literals outside of the i64 range
+    Number(u64),
+    /// hex, octal or binary literal, whose bits are reinterpreted as an i64. Literals too large for a u128 saturate,
+    /// the parser reports literals outside of the u64 range
+    RadixNumber(u128),
+    /// string literal with decoded escapes
+    Lit(LitBytes<'a>),
     Keyword(&'a str),
     Eq,
     Not,
+    Tilde,
     NEq,
     EqEq,
     Or,
@@ -71,55 +76,109 @@ pub enum Token<'a> {
     Mod,
     OpenParen,
     CloseParen,
+    OpenBracket,
+    CloseBracket,
+    OpenBrace,
+    CloseBrace,
+    Arrow,
     Semi,
     Gt,
     Lt,
+    Ge,
+    Le,
+    AndAnd,
+    OrOr,
     Hat,
     Shr,
+    Sar,
     Shl,
     Comma,
     Comment,
+    /// text of a `##` doc comment
+    DocComment(&'a str),
     Colon,
+    PathSep,
     EOF,
 }
 
 impl<'a> Token<'a> {
-    fn parse(s: &'a str) -> Result<(Self, usize), LexErr> {
+    /// lexes the next token of s. Malformed tokens are replaced and their errors pushed to errs
+    fn parse(s: &'a str, errs: &mut Vec<LexErr>) -> Result<(Self, usize), LexErr> {
         if s.is_empty() {
             return Ok((Self::EOF, 0));
         }
         let mut n_parsed = 0;
         let token = 'outer: {
-            let mut idc = s.char_indices();
-            while let Some((i, c)) = idc.next() {
+            for (i, c) in s.char_indices() {
                 n_parsed += c.len_utf8();
                 match c {
+                    '#' if s[i + 1..].starts_with('[') => {
+                        break 'outer Self::parse_block_comment(&s[i..], &mut n_parsed, errs);
+                    }
                     '#' => {
-                        for (_, c) in idc.by_ref() {
-                            n_parsed += c.len_utf8();
-                            if c == '\n' {
-                                break;
-                            }
+                        let end = s[i..].find('\n').map_or(s.len(), |end| i + end);
+                        n_parsed += end - i - 1;
+                        match s[i + 1..end].strip_prefix('#') {
+                            Some(doc) => break 'outer Token::DocComment(doc.trim_end()),
+                            None => break 'outer Token::Comment,
                         }
-                        break 'outer Token::Comment;
                     }
-                    '\'' | '\"' => break 'outer Self::parse_quoted(&s[i..], &mut n_parsed),
+                    '\"' => break 'outer Self::parse_quoted(&s[i..], &mut n_parsed, errs),
+                    '\'' => break 'outer Self::parse_char(&s[i..], &mut n_parsed, errs),
                     '+' => break 'outer Token::Add,
-                    '-' => break 'outer Token::Sub,
+                    '-' => {
+                        if s[i + 1..].starts_with('>') {
+                            n_parsed += 1;
+                            break 'outer Token::Arrow;
+                        } else {
+                            break 'outer Token::Sub;
+                        }
+                    }
                     '*' => break 'outer Token::Star,
                     '/' => break 'outer Token::Div,
                     '%' => break 'outer Token::Mod,
                     '(' => break 'outer Token::OpenParen,
                     ')' => break 'outer Token::CloseParen,
+                    '[' => break 'outer Token::OpenBracket,
+                    ']' => break 'outer Token::CloseBracket,
+                    '{' => break 'outer Token::OpenBrace,
+                    '}' => break 'outer Token::CloseBrace,
                     ';' => break 'outer Token::Semi,
-                    ':' => break 'outer Token::Colon,
-                    '|' => break 'outer Token::Or,
-                    '&' => break 'outer Token::Ampercent,
+                    ':' => {
+                        if s.chars().nth(i + 1).is_some_and(|c| c == ':') {
+                            n_parsed += 1;
+                            break 'outer Token::PathSep;
+                        } else {
+                            break 'outer Token::Colon;
+                        }
+                    }
+                    '|' => {
+                        if s[i + 1..].starts_with('|') {
+                            n_parsed += 1;
+                            break 'outer Token::OrOr;
+                        } else {
+                            break 'outer Token::Or;
+                        }
+                    }
+                    '&' => {
+                        if s[i + 1..].starts_with('&') {
+                            n_parsed += 1;
+                            break 'outer Token::AndAnd;
+                        } else {
+                            break 'outer Token::Ampercent;
+                        }
+                    }
                     ',' => break 'outer Token::Comma,
                     '>' => {
-                        if s.chars().nth(i + 1).is_some_and(|c| c == '>') {
+                        if s[i + 1..].starts_with(">>") {
+                            n_parsed += 2;
+                            break 'outer Token::Sar;
+                        } else if s.chars().nth(i + 1).is_some_and(|c| c == '>') {
                             n_parsed += 1;
                             break 'outer Token::Shr;
+                        } else if s[i + 1..].starts_with('=') {
+                            n_parsed += 1;
+                            break 'outer Token::Ge;
                         } else {
                             break 'outer Token::Gt;
                         }
@@ -128,11 +187,15 @@ impl<'a> Token<'a> {
                         if s.chars().nth(i + 1).is_some_and(|c| c == '<') {
                             n_parsed += 1;
                             break 'outer Token::Shl;
+                        } else if s[i + 1..].starts_with('=') {
+                            n_parsed += 1;
+                            break 'outer Token::Le;
                         } else {
                             break 'outer Token::Lt;
                         }
                     }
                     '^' => break 'outer Token::Hat,
+                    '~' => break 'outer Token::Tilde,
                     '=' => {
                         if s.chars().nth(i + 1).is_some_and(|c| c == '=') {
                             n_parsed += 1;
@@ -150,7 +213,7 @@ impl<'a> Token<'a> {
                         }
                     }
                     w if w.is_whitespace() => continue,
-                    _ => break 'outer Self::parse_single(&s[i..], &mut n_parsed),
+                    _ => break 'outer Self::parse_single(&s[i..], &mut n_parsed, errs),
                 }
             }
             panic!("malformed input");
@@ -158,36 +221,139 @@ impl<'a> Token<'a> {
         Ok((token, n_parsed))
     }
 
-    fn parse_quoted(s: &'a str, counter: &mut usize) -> Self {
-        let quotation_open = s.chars().next().unwrap();
-        let inner_name_end = s[quotation_open.len_utf8()..].find(quotation_open).expect("Quotations can currently not be nested and must be closed using the same qutation mark");
-        let str_lit = &s[quotation_open.len_utf8()..=inner_name_end];
-        *counter += inner_name_end + quotation_open.len_utf8();
-        Self::Lit(str_lit)
+    /// lexes a block comment `#[ ... ]#`, which may contain nested block comments
+    fn parse_block_comment(s: &'a str, counter: &mut usize, errs: &mut Vec<LexErr>) -> Self {
+        // the delimiters are ASCII, so scanning bytes never matches within a multi-byte character
+        let bytes = s.as_bytes();
+        let mut depth = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i..].starts_with(b"#[") {
+                depth += 1;
+                i += 2;
+            } else if bytes[i..].starts_with(b"]#") {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    *counter += i - 1;
+                    return Self::Comment;
+                }
+            } else {
+                i += 1;
+            }
+        }
+        errs.push(LexErr::UnclosedComment);
+        *counter += s.len() - 1;
+        Self::Comment
     }
 
-    fn parse_single(s: &'a str, counter: &mut usize) -> Self {
-        let is_number = s.chars().next().is_some_and(|c| c.is_ascii_digit());
-        let mut last_char_len = 0;
-        for (i, c) in s.char_indices() {
-            if !((is_number && c.is_ascii_digit())
-                || (!is_number && (c.is_alphanumeric() || c == '_')))
-            {
-                *counter += i - last_char_len;
+    /// lexes a string literal and decodes its escapes. Literals without escapes borrow from s
+    fn parse_quoted(s: &'a str, counter: &mut usize, errs: &mut Vec<LexErr>) -> Self {
+        let mut decoded: Option<Vec<u8>> = None;
+        let mut chars = s.char_indices().skip(1).peekable();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    *counter += i;
+                    return Self::Lit(LitBytes(match decoded {
+                        Some(decoded) => Cow::Owned(decoded),
+                        None => Cow::Borrowed(&s.as_bytes()[1..i]),
+                    }));
+                }
+                '\\' => {
+                    let decoded = decoded.get_or_insert_with(|| s.as_bytes()[1..i].to_vec());
+                    match unescape(&mut chars) {
+                        Some(Ok(Escape::Char(c))) => {
+                            decoded.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes())
+                        }
+                        Some(Ok(Escape::Byte(byte))) => decoded.push(byte),
+                        Some(Err(err)) => errs.push(err),
+                        None => {}
+                    }
+                }
+                c => {
+                    if let Some(decoded) = &mut decoded {
+                        decoded.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    }
+                }
+            }
+        }
+        errs.push(LexErr::UnclosedStr);
+        *counter += s.len() - 1;
+        Self::Lit(LitBytes(Cow::Owned(
+            decoded.unwrap_or_else(|| s.as_bytes()[1..].to_vec()),
+        )))
+    }
 
-                if is_number {
-                    return Self::Number(s[..i].parse::<i64>().unwrap());
-                } else {
-                    return Self::Ident(&s[..i]).map_keyword();
+    /// lexes a char literal like `'a'` or `'\n'` to its byte value
+    fn parse_char(s: &'a str, counter: &mut usize, errs: &mut Vec<LexErr>) -> Self {
+        let mut bytes = Vec::new();
+        let mut malformed = false;
+        let mut chars = s.char_indices().skip(1).peekable();
+        let end = loop {
+            let c = match chars.next() {
+                Some((i, '\'')) => break i + 1,
+                None | Some((_, '\n')) => {
+                    errs.push(LexErr::UnclosedChar);
+                    // only the opening quote is consumed
+                    return Self::Number(0);
                 }
+                Some((_, '\\')) => match unescape(&mut chars) {
+                    Some(Ok(Escape::Char(c))) => c,
+                    Some(Ok(Escape::Byte(byte))) => {
+                        bytes.push(byte);
+                        continue;
+                    }
+                    Some(Err(err)) => {
+                        errs.push(err);
+                        malformed = true;
+                        continue;
+                    }
+                    None => continue,
+                },
+                Some((_, c)) => c,
+            };
+            if c.is_ascii() {
+                bytes.push(c as u8);
+            } else {
+                errs.push(LexErr::InvalidChar(c));
+                malformed = true;
             }
-            last_char_len = c.len_utf8();
+        };
+        *counter += end - 1;
+        match bytes[..] {
+            [byte] if !malformed => Self::Number(byte as u64),
+            _ if malformed => Self::Number(0),
+            _ => {
+                errs.push(LexErr::CharLength(bytes.len()));
+                Self::Number(0)
+            }
+        }
+    }
+
+    fn parse_single(s: &'a str, counter: &mut usize, errs: &mut Vec<LexErr>) -> Self {
+        let is_number = s.chars().next().is_some_and(|c| c.is_ascii_digit());
+        // numbers consume trailing letters, so that `0x1f` and malformed literals like `12ab` are a single token
+        let len = s
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(s.len());
+        if len == 0 {
+            // a character starting no token, which is already consumed. It is skipped like a comment
+            errs.push(LexErr::UnexpectedChar(s.chars().next().unwrap()));
+            return Self::Comment;
         }
-        *counter += s.len();
+        *counter += len - s.chars().next().map_or(0, char::len_utf8);
         if is_number {
-            Self::Number(s.parse::<i64>().unwrap())
+            match parse_number(&s[..len]) {
+                Ok((num, 10)) => Self::Number(u64::try_from(num).unwrap_or(u64::MAX)),
+                Ok((num, _)) => Self::RadixNumber(num),
+                Err(err) => {
+                    errs.push(err);
+                    Self::Number(0)
+                }
+            }
         } else {
-            Self::Ident(s).map_keyword()
+            Self::Ident(&s[..len]).map_keyword()
         }
     }
 
@@ -199,11 +365,35 @@ impl<'a> Token<'a> {
     }
 }
 
+/// The bytes of a string literal, which need not be UTF-8 due to `\x` escapes
+#[derive(Clone, PartialEq, Eq)]
+pub struct LitBytes<'a>(pub Cow<'a, [u8]>);
+
+impl Deref for LitBytes<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a str> for LitBytes<'a> {
+    fn from(s: &'a str) -> Self {
+        Self(Cow::Borrowed(s.as_bytes()))
+    }
+}
+
+impl Debug for LitBytes<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\"", self.0.escape_ascii())
+    }
+}
+
 impl Display for Token<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Ident(_ident) => write!(f, "Ident"),
-            Self::Number(_num) => write!(f, "Number"),
+            Self::Number(_) | Self::RadixNumber(_) => write!(f, "Number"),
             Self::Lit(_lit) => write!(f, "String Literal"),
             _ => write!(f, "{:?}", self),
         }
@@ -215,6 +405,12 @@ pub struct TokenStream<'a> {
     inner: Vec<Spanned<Token<'a>>>,
     cursor: usize,
     pub last_span: Span,
+    /// errors of malformed tokens, which were replaced
+    pub errs: Vec<Spanned<LexErr>>,
+    /// doc comments are kept apart from the tokens, so that the parser does not see them
+    docs: Vec<Spanned<&'a str>>,
+    /// plain line comments directly above a token, the doc of items without `##` comments
+    comments: Vec<Spanned<&'a str>>,
 }
 
 impl<'a> TokenStream<'a> {
@@ -223,6 +419,9 @@ impl<'a> TokenStream<'a> {
             inner: Vec::new(),
             cursor: 0,
             last_span: Span { start: 0, end: 0 },
+            errs: Vec::new(),
+            docs: Vec::new(),
+            comments: Vec::new(),
         }
     }
 
@@ -253,6 +452,35 @@ impl<'a> TokenStream<'a> {
             .unwrap_or_else(|| self.inner.last().unwrap())
     }
 
+    /// the span from offset start to the end of the last consumed token
+    pub fn span_from(&self, start: usize) -> Span {
+        Span {
+            start,
+            end: self.last_span.end.max(start),
+        }
+    }
+
+    /// the doc comments between the token preceding offset start and start, one line each.
+    /// Without `##` comments, the plain `#` comments directly above start are used
+    pub fn doc_before(&self, start: usize) -> Option<String> {
+        let first = self.inner.partition_point(|token| token.span.end <= start);
+        let prev_end = first
+            .checked_sub(1)
+            .map_or(0, |prev| self.inner[prev].span.end);
+        let lines = |comments: &[Spanned<&'a str>]| {
+            comments
+                .iter()
+                .filter(|doc| doc.span.start >= prev_end && doc.span.end <= start)
+                .map(|doc| doc.inner.strip_prefix(' ').unwrap_or(doc.inner))
+                .collect::<Vec<_>>()
+        };
+        let mut docs = lines(&self.docs);
+        if docs.is_empty() {
+            docs = lines(&self.comments);
+        }
+        (!docs.is_empty()).then(|| docs.join("\n"))
+    }
+
     fn push(&mut self, token: Spanned<Token<'a>>) {
         self.inner.push(token);
     }
@@ -260,39 +488,109 @@ impl<'a> TokenStream<'a> {
     #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &'a str) -> Result<Self, LexErr> {
         let mut stream = Self::new();
+        let lossless = LosslessStream::from_str(s)?;
+        stream.errs = lossless.errs;
+        // the line comments since the last blank line, which document the next token
+        let mut run = Vec::new();
+        for (i, token) in lossless.tokens.into_iter().enumerate() {
+            let newlines = token.leading.matches('\n').count();
+            if newlines > 1 {
+                run.clear();
+            }
+            match token.token.inner {
+                // trailing comments after code on the same line, block comments
+                // and skipped unexpected characters break the run
+                Token::Comment
+                    if (newlines == 0 && i > 0)
+                        || token.text.starts_with("#[")
+                        || !token.text.starts_with('#') =>
+                {
+                    run.clear()
+                }
+                Token::Comment => run.push(Spanned {
+                    inner: token.text[1..].trim_end(),
+                    span: token.token.span,
+                }),
+                Token::DocComment(doc) => stream.docs.push(Spanned {
+                    inner: doc,
+                    span: token.token.span,
+                }),
+                _ => {
+                    stream.comments.append(&mut run);
+                    stream.push(token.token);
+                }
+            }
+        }
+        Ok(stream)
+    }
+}
+
+/// A token of the lossless stream together with the whitespace in front of it
+#[derive(Debug, Clone)]
+pub struct LosslessToken<'a> {
+    pub leading: &'a str,
+    pub token: Spanned<Token<'a>>,
+    /// the source of the token
+    pub text: &'a str,
+}
+
+/// All tokens of a source including comments, from which the source can be restored exactly
+#[derive(Debug)]
+pub struct LosslessStream<'a> {
+    /// the tokens, the last one is EOF and holds the trailing whitespace
+    pub tokens: Vec<LosslessToken<'a>>,
+    /// errors of malformed tokens, which were replaced
+    pub errs: Vec<Spanned<LexErr>>,
+}
+
+impl<'a> LosslessStream<'a> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &'a str) -> Result<Self, LexErr> {
+        let mut stream = Self {
+            tokens: Vec::new(),
+            errs: Vec::new(),
+        };
         let mut total_parsed = 0;
-        while total_parsed < s.len() {
+        loop {
             let remainder = &s[total_parsed..];
             let trimmed = remainder.trim_start();
+            let leading = &remainder[..remainder.len() - trimmed.len()];
+            let start = total_parsed + leading.len();
             if trimmed.is_empty() {
-                break;
-            }
-            let whitespace_len = remainder.len() - trimmed.len();
-
-            let (token, parsed) = Token::parse(trimmed)?;
-            if token != Token::Comment {
-                stream.push(Spanned::new(
-                    token,
-                    Span {
-                        start: total_parsed + whitespace_len,
-                        end: total_parsed + whitespace_len + parsed,
-                    },
-                ));
+                stream.tokens.push(LosslessToken {
+                    leading,
+                    token: Spanned::new(Token::EOF, Span { start, end: start }),
+                    text: "",
+                });
+                return Ok(stream);
             }
 
-            total_parsed += parsed + whitespace_len;
+            let mut errs = Vec::new();
+            let (token, parsed) = Token::parse(trimmed, &mut errs)?;
+            let span = Span {
+                start,
+                end: start + parsed,
+            };
+            stream.errs.extend(errs.into_iter().map(|err| Spanned {
+                inner: err,
+                span: span.clone(),
+            }));
+            stream.tokens.push(LosslessToken {
+                leading,
+                token: Spanned::new(token, span),
+                text: &trimmed[..parsed],
+            });
+            total_parsed = start + parsed;
         }
-        stream.push(Spanned::new(
-            Token::EOF,
-            Span {
-                start: s.len(),
-                end: s.len(),
-            },
-        ));
-        Ok(stream)
     }
 }
 
+/// whether a and b are lexed as two tokens when written without whitespace in between
+pub fn lexed_apart(a: &str, b: &str) -> bool {
+    let joined = format!("{}{}", a, b);
+    Token::parse(&joined, &mut Vec::new()).is_ok_and(|(_, parsed)| parsed == a.len())
+}
+
 impl Display for TokenStream<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "tokens: {:#?}\ncursor: {}", self.inner, self.cursor)
@@ -301,7 +599,144 @@ impl Display for TokenStream<'_> {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum LexErr {
-    General,
+    /// a number prefix like `0x` without digits
+    MissingDigits,
+    InvalidDigit {
+        digit: char,
+        radix: u32,
+    },
+    UnclosedChar,
+    /// a char literal containing a number of bytes other than one
+    CharLength(usize),
+    /// a character outside of ASCII in a char literal
+    InvalidChar(char),
+    UnknownEscape(char),
+    InvalidEscape {
+        escape: String,
+        reason: &'static str,
+    },
+    UnclosedStr,
+    UnclosedComment,
+    /// a character which starts no token, e.g. `@`
+    UnexpectedChar(char),
+}
+
+impl LexErr {
+    /// the stable code identifying the kind of error
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingDigits => "E0020",
+            Self::InvalidDigit { .. } => "E0021",
+            Self::UnclosedChar => "E0022",
+            Self::CharLength(_) => "E0023",
+            Self::InvalidChar(_) => "E0024",
+            Self::UnknownEscape(_) => "E0025",
+            Self::InvalidEscape { .. } => "E0026",
+            Self::UnclosedStr => "E0027",
+            Self::UnclosedComment => "E0028",
+            Self::UnexpectedChar(_) => "E0035",
+        }
+    }
+}
+
+impl Display for LexErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingDigits => write!(f, "number prefix without digits"),
+            Self::InvalidDigit { digit, radix } => {
+                write!(f, "invalid digit {:?} in base {} literal", digit, radix)
+            }
+            Self::UnclosedChar => write!(f, "unclosed char literal"),
+            Self::CharLength(len) => write!(
+                f,
+                "char literals contain a single character, found {} characters",
+                len
+            ),
+            Self::InvalidChar(c) => write!(f, "{:?} is not an ASCII character", c),
+            Self::UnknownEscape(c) => write!(f, "unknown escape sequence \\{}", c),
+            Self::InvalidEscape { escape, reason } => {
+                write!(f, "invalid escape sequence {}: {}", escape, reason)
+            }
+            Self::UnclosedStr => write!(f, "unclosed string literal"),
+            Self::UnclosedComment => write!(f, "unclosed block comment"),
+            Self::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c),
+        }
+    }
+}
+
+/// A decoded escape sequence
+enum Escape {
+    Char(char),
+    /// `\xNN`, which may be any byte
+    Byte(u8),
+}
+
+/// decodes the escape sequence following a `\`. None if the input ends
+fn unescape(
+    chars: &mut Peekable<impl Iterator<Item = (usize, char)>>,
+) -> Option<Result<Escape, LexErr>> {
+    let (_, c) = chars.next()?;
+    let invalid = |escape: String, reason| LexErr::InvalidEscape { escape, reason };
+    Some(Ok(Escape::Char(match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        '\\' | '\'' | '"' => c,
+        'x' => {
+            let mut hex = String::new();
+            while hex.len() < 2
+                && let Some((_, digit)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit())
+            {
+                hex.push(digit);
+            }
+            let escape = format!("\\x{}", hex);
+            if hex.len() != 2 {
+                return Some(Err(invalid(escape, "expected two hex digits")));
+            }
+            return Some(Ok(Escape::Byte(u8::from_str_radix(&hex, 16).unwrap())));
+        }
+        'u' => {
+            if chars.next_if(|(_, c)| *c == '{').is_none() {
+                return Some(Err(invalid("\\u".into(), "expected `{`")));
+            }
+            let mut hex = String::new();
+            while let Some((_, digit)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+                hex.push(digit);
+            }
+            let escape = format!("\\u{{{}}}", hex);
+            if chars.next_if(|(_, c)| *c == '}').is_none() {
+                return Some(Err(invalid(escape, "expected hex digits and `}`")));
+            }
+            match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                Some(c) if hex.len() <= 6 => c,
+                _ => return Some(Err(invalid(escape, "not a unicode character"))),
+            }
+        }
+        c => return Some(Err(LexErr::UnknownEscape(c))),
+    })))
+}
+
+/// parses a decimal, `0x` hexadecimal, `0o` octal or `0b` binary number, which may contain `_` separators,
+/// into its value and radix. Numbers too large for a u128 saturate
+fn parse_number(text: &str) -> Result<(u128, u32), LexErr> {
+    let (radix, digits) = match text.get(..2) {
+        Some("0x" | "0X") => (16, &text[2..]),
+        Some("0o" | "0O") => (8, &text[2..]),
+        Some("0b" | "0B") => (2, &text[2..]),
+        _ => (10, text),
+    };
+    let digits = digits.replace('_', "");
+    if digits.is_empty() {
+        return Err(LexErr::MissingDigits);
+    }
+    if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+        return Err(LexErr::InvalidDigit { digit, radix });
+    }
+    Ok((
+        u128::from_str_radix(&digits, radix).unwrap_or(u128::MAX),
+        radix,
+    ))
 }
 
 #[cfg(test)]
@@ -385,6 +820,194 @@ mod tests {
         )
     }
 
+    #[test]
+    fn numbers() {
+        let text = "0x1F 0b1010_0101 0o17 1_000_000 0xffff_ffff_ffff_ffff_f 12ab 0x 'ab' '\\q' 'x";
+        let stream = TokenStream::from_str(text).unwrap();
+        assert_eq!(
+            stream
+                .inner
+                .iter()
+                .map(|item| item.as_ref().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                Token::RadixNumber(31),
+                Token::RadixNumber(165),
+                Token::RadixNumber(15),
+                Token::Number(1_000_000),
+                Token::RadixNumber(0xf_ffff_ffff_ffff_ffff),
+                Token::Number(0),
+                Token::Number(0),
+                Token::Number(0),
+                Token::Number(0),
+                Token::Number(0),
+                Token::Ident("x"),
+                Token::EOF
+            ]
+        );
+        assert_eq!(
+            stream
+                .errs
+                .into_iter()
+                .map(|err| err.inner)
+                .collect::<Vec<_>>(),
+            vec![
+                LexErr::InvalidDigit {
+                    digit: 'a',
+                    radix: 10
+                },
+                LexErr::MissingDigits,
+                LexErr::CharLength(2),
+                LexErr::UnknownEscape('q'),
+                LexErr::UnclosedChar,
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let text = r#""a\tb\"\x41\u{e9}" "\q\x80\xff\u{110000}\x4" "open"#;
+        let stream = TokenStream::from_str(text).unwrap();
+        assert_eq!(
+            stream
+                .inner
+                .iter()
+                .map(|item| item.as_ref().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                Token::Lit("a\tb\"Aé".into()),
+                Token::Lit(LitBytes(Cow::Borrowed(b"\x80\xff"))),
+                Token::Lit("open".into()),
+                Token::EOF
+            ]
+        );
+        assert_eq!(
+            stream
+                .errs
+                .into_iter()
+                .map(|err| err.inner)
+                .collect::<Vec<_>>(),
+            vec![
+                LexErr::UnknownEscape('q'),
+                LexErr::InvalidEscape {
+                    escape: "\\u{110000}".into(),
+                    reason: "not a unicode character"
+                },
+                LexErr::InvalidEscape {
+                    escape: "\\x4".into(),
+                    reason: "expected two hex digits"
+                },
+                LexErr::UnclosedStr,
+            ]
+        );
+    }
+
+    #[test]
+    fn operators() {
+        let text = "a >= b <= c && d || e >>> f >> g";
+        assert_eq!(
+            TokenStream::from_str(text)
+                .unwrap()
+                .inner
+                .into_iter()
+                .filter_map(|item| match item.as_ref() {
+                    Token::Ident(_) => None,
+                    token => Some(token.clone()),
+                })
+                .collect::<Vec<_>>(),
+            vec![
+                Token::Ge,
+                Token::Le,
+                Token::AndAnd,
+                Token::OrOr,
+                Token::Sar,
+                Token::Shr,
+                Token::EOF
+            ]
+        )
+    }
+
+    #[test]
+    fn unexpected_char() {
+        let text = "x = 1 @ 2;\n€\ny = a.b;";
+        let stream = TokenStream::from_str(text).unwrap();
+        assert_eq!(
+            stream
+                .inner
+                .iter()
+                .map(|item| item.as_ref().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                Token::Ident("x"),
+                Token::Eq,
+                Token::Number(1),
+                Token::Number(2),
+                Token::Semi,
+                Token::Ident("y"),
+                Token::Eq,
+                Token::Ident("a"),
+                Token::Ident("b"),
+                Token::Semi,
+                Token::EOF
+            ]
+        );
+        let errs = stream
+            .errs
+            .iter()
+            .map(|err| err.inner.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errs,
+            vec![
+                LexErr::UnexpectedChar('@'),
+                LexErr::UnexpectedChar('€'),
+                LexErr::UnexpectedChar('.')
+            ]
+        );
+        assert_eq!(stream.errs[0].span.start, text.find('@').unwrap());
+        assert_eq!(stream.errs[2].span.start, text.find('.').unwrap());
+    }
+
+    #[test]
+    fn comments() {
+        let text = "a #[ b #[ café — c ]# d ]# e # fé\n## doc\n##\ng #[ h";
+        let stream = TokenStream::from_str(text).unwrap();
+        assert_eq!(
+            stream
+                .inner
+                .iter()
+                .map(|item| item.as_ref().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                Token::Ident("a"),
+                Token::Ident("e"),
+                Token::Ident("g"),
+                Token::EOF
+            ]
+        );
+        let lossless = LosslessStream::from_str(text).unwrap();
+        assert_eq!(
+            lossless
+                .tokens
+                .iter()
+                .map(|token| format!("{}{}", token.leading, token.text))
+                .collect::<String>(),
+            text
+        );
+        assert_eq!(stream.inner[1].span.start, text.find(" e ").unwrap() + 1);
+        let g = stream.inner[2].span.start;
+        assert_eq!(stream.doc_before(g), Some("doc\n".into()));
+        assert_eq!(stream.doc_before(stream.inner[1].span.start), None);
+        assert_eq!(
+            stream
+                .errs
+                .into_iter()
+                .map(|err| err.inner)
+                .collect::<Vec<_>>(),
+            vec![LexErr::UnclosedComment]
+        );
+    }
+
     #[test]
     fn str_lit() {
         let txt1 = "\"hello world\"";
@@ -395,10 +1018,10 @@ mod tests {
                 .into_iter()
                 .map(|item| item.as_ref().clone())
                 .collect::<Vec<_>>(),
-            vec![Token::Lit("hello world"), Token::EOF]
+            vec![Token::Lit("hello world".into()), Token::EOF]
         );
 
-        let txt2 = "\'hello world\'";
+        let txt2 = "'h' '\\n' '\"' \"'\"";
         assert_eq!(
             TokenStream::from_str(txt2)
                 .unwrap()
@@ -406,7 +1029,13 @@ mod tests {
                 .into_iter()
                 .map(|item| item.as_ref().clone())
                 .collect::<Vec<_>>(),
-            vec![Token::Lit("hello world"), Token::EOF]
+            vec![
+                Token::Number(104),
+                Token::Number(10),
+                Token::Number(34),
+                Token::Lit("'".into()),
+                Token::EOF
+            ]
         );
 
         let txt3 = "\"hello world\";";
@@ -417,7 +1046,7 @@ mod tests {
                 .into_iter()
                 .map(|item| item.as_ref().clone())
                 .collect::<Vec<_>>(),
-            vec![Token::Lit("hello world"), Token::Semi, Token::EOF]
+            vec![Token::Lit("hello world".into()), Token::Semi, Token::EOF]
         );
 
         let txt4 = "print_str \"hello world\";";
@@ -430,7 +1059,7 @@ mod tests {
                 .collect::<Vec<_>>(),
             vec![
                 Token::Ident("print_str"),
-                Token::Lit("hello world"),
+                Token::Lit("hello world".into()),
                 Token::Semi,
                 Token::EOF
             ]
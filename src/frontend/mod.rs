@@ -2,19 +2,42 @@ use ast::Ast;
 use lexer::{LexErr, TokenStream};
 
 use crate::{
-    frontend::ast::{cfg::CfgEnv, error::Diagnostics},
+    frontend::{
+        ast::{cfg::CfgEnv, error::Diagnostics, sema, typeck},
+        import::Resolver,
+    },
     print_if,
 };
 
 pub mod ast;
+pub mod c_header;
+pub mod fmt;
+pub mod import;
 mod lexer;
 
+pub use lexer::Span;
+
 pub fn get_ast<'a>(s: &'a str, cfg_env: &CfgEnv) -> (Ast, Diagnostics<'a>) {
     let mut token_stream = TokenStream::from_str(s).map_err(FrontendErr::Lex).unwrap();
     print_if!(2, "stream: {}", token_stream);
     Ast::from_stream(&mut token_stream, cfg_env)
 }
 
+/// Parses s, resolves its imports using resolver and runs all semantic checks
+pub fn get_checked_ast<'a>(
+    s: &'a str,
+    cfg_env: &CfgEnv,
+    resolver: &Resolver,
+) -> (Ast, Diagnostics<'a>) {
+    let (mut ast, mut diagnostics) = get_ast(s, cfg_env);
+    resolver.resolve_imports(&mut ast, cfg_env, &mut diagnostics);
+    sema::check_arity(&ast, &mut diagnostics);
+    sema::check_globals(&ast, &mut diagnostics);
+    sema::check_structs(&ast, &mut diagnostics);
+    typeck::check_types(&ast, &mut diagnostics);
+    (ast, diagnostics)
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum FrontendErr {
     Lex(LexErr),
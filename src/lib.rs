@@ -1,7 +1,9 @@
 use std::sync::atomic::AtomicU8;
 
 pub mod backend;
+pub mod error_codes;
 pub mod frontend;
+pub mod lsp;
 pub static VERBOSITY: AtomicU8 = AtomicU8::new(0);
 
 #[macro_export]
@@ -0,0 +1,281 @@
+use serde_json::{Value, json};
+
+use crate::{
+    backend::doc::FuncDoc,
+    frontend::{
+        Span,
+        ast::{
+            Arity, Ast, Expr, FieldRef, Function, GlobalKind, Item, LValue, Line, Val,
+            error::{AstErr, AstWarn, Diagnostics, Spanned},
+            is_builtin_func,
+            node::Node,
+        },
+    },
+};
+
+/// Converts between byte offsets and LSP positions, which count UTF-16 code units
+pub struct LineIndex<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, starts }
+    }
+
+    pub fn position(&self, offset: usize) -> Value {
+        let offset = offset.min(self.text.len());
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let character = self.text[self.starts[line]..offset].encode_utf16().count();
+        json!({ "line": line, "character": character })
+    }
+
+    pub fn range(&self, span: &Span) -> Value {
+        json!({ "start": self.position(span.start), "end": self.position(span.end) })
+    }
+
+    /// the byte offset of an LSP position, clamped to the end of its line
+    pub fn offset(&self, position: &Value) -> usize {
+        let line = position["line"].as_u64().unwrap_or(0) as usize;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+        let Some(&start) = self.starts.get(line) else {
+            return self.text.len();
+        };
+        let end = self
+            .starts
+            .get(line + 1)
+            .map_or(self.text.len(), |next| next - 1);
+        let mut units = 0;
+        for (i, c) in self.text[start..end].char_indices() {
+            if units >= character {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        end
+    }
+}
+
+/// What the name under the cursor refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// a local variable, or a function or global if there is no such variable
+    Name(String),
+    /// a function, global or struct
+    Item(String),
+    Field(FieldRef),
+}
+
+/// the name at offset, together with the function it is used in
+pub fn target_at(ast: &Ast, offset: usize) -> Option<(Target, Option<&Function>)> {
+    let nodes = ast.nodes_at(offset);
+    let func = match nodes.first() {
+        Some(Node::Item(Item::Function(func))) => Some(func),
+        _ => None,
+    };
+    let target = match nodes.last()? {
+        Node::ItemName(item) => Target::Item(item.name()?.to_string()),
+        Node::Arg(func, i) => Target::Name(func.args[*i].clone()),
+        Node::Callee(name) if !is_builtin_func(name) => Target::Item(name.inner.clone()),
+        Node::Expr(expr) => match &expr.inner {
+            Expr::Val(Val::Var(name)) => Target::Name(name.clone()),
+            Expr::SizeOf(name) => Target::Item(name.clone()),
+            Expr::Field(_, field) => Target::Field(field.clone()),
+            _ => return None,
+        },
+        Node::LValue(lvalue) => match &lvalue.inner {
+            LValue::Variable(name) => Target::Name(name.clone()),
+            LValue::Field(_, field) => Target::Field(field.clone()),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some((target, func))
+}
+
+/// the argument or first assignment declaring the local variable name in func
+pub fn local_definition(func: &Function, name: &str) -> Option<Span> {
+    if let Some(i) = func.args.iter().position(|arg| arg == name) {
+        return Some(func.arg_spans[i].clone());
+    }
+    func.spanned_body()?
+        .find_map(|line| assigned_variable(line, name))
+}
+
+fn assigned_variable(line: &Spanned<Line>, name: &str) -> Option<Span> {
+    match &line.inner {
+        Line::Decl(lvalue, ..) | Line::Call(_, _, Some(lvalue)) if matches!(&lvalue.inner, LValue::Variable(var) if var == name) => {
+            Some(lvalue.span.clone())
+        }
+        Line::Cond(_, then) => assigned_variable(then, name),
+        _ => None,
+    }
+}
+
+/// the local variables of func, arguments first, with their annotated type
+pub fn locals(func: &Function) -> Vec<(String, Option<String>)> {
+    fn collect(line: &Line, vars: &mut Vec<(String, Option<String>)>) {
+        let (name, ty) = match line {
+            Line::Decl(lvalue, ty, _) => match &lvalue.inner {
+                LValue::Variable(name) => (name, ty.as_ref().map(ToString::to_string)),
+                _ => return,
+            },
+            Line::Call(_, _, Some(lvalue)) => match &lvalue.inner {
+                LValue::Variable(name) => (name, None),
+                _ => return,
+            },
+            Line::Cond(_, then) => return collect(then, vars),
+            _ => return,
+        };
+        if let Some((_, known)) = vars.iter_mut().find(|(var, _)| var == name) {
+            // a variable keeps the type it was declared with first
+            if known.is_none() {
+                *known = ty;
+            }
+        } else {
+            vars.push((name.clone(), ty));
+        }
+    }
+
+    let mut vars = func
+        .args
+        .iter()
+        .zip(&func.arg_types)
+        .map(|(arg, ty)| (arg.clone(), ty.as_ref().map(ToString::to_string)))
+        .collect::<Vec<_>>();
+    for line in func.body().into_iter().flatten() {
+        collect(line, &mut vars);
+    }
+    vars
+}
+
+/// whether item is defined in the file with source text, rather than declared by `extern_def` or an import
+pub fn is_definition(item: &Item, text: &str) -> bool {
+    let declared_here = item
+        .name_span()
+        .is_some_and(|span| text.get(span.start..span.end) == item.name());
+    declared_here
+        && match item {
+            Item::Function(func) => func.body.is_some(),
+            Item::Global(global) => !global.link_attr.external,
+            Item::Struct(_) => true,
+            Item::Malformed => false,
+        }
+}
+
+/// markdown describing a function. The arity of a defined function is its number of arguments
+pub fn function_hover(func: &Function) -> String {
+    let arity = match func.body {
+        Some(_) => Arity::Exact(func.args.len()),
+        None => func.arity,
+    };
+    let mut hover = format!(
+        "```\n{}\n```\n\ntakes {} argument(s)",
+        FuncDoc::of(func).signature,
+        arity
+    );
+    if let Some(doc) = &func.doc {
+        hover.push_str("\n\n");
+        hover.push_str(doc);
+    }
+    hover
+}
+
+pub fn item_hover(item: &Item) -> String {
+    match item {
+        Item::Function(func) => function_hover(func),
+        item => format!("```\n{}\n```", item),
+    }
+}
+
+pub fn local_hover(name: &str, ty: Option<&str>) -> String {
+    match ty {
+        Some(ty) => format!("```\n{}: {}\n```", name, ty),
+        None => format!("```\n{}\n```", name),
+    }
+}
+
+/// completion items for the items of ast and the locals of func
+pub fn completions(ast: &Ast, func: Option<&Function>) -> Vec<Value> {
+    // see CompletionItemKind of the LSP specification
+    const FUNCTION: u8 = 3;
+    const VARIABLE: u8 = 6;
+    const CONSTANT: u8 = 21;
+    const STRUCT: u8 = 22;
+
+    let locals = func.map(locals).unwrap_or_default();
+    let locals = locals.into_iter().map(
+        |(name, ty)| json!({ "label": name, "kind": VARIABLE, "detail": ty.unwrap_or_default() }),
+    );
+    let items = ast.funcs().filter_map(|item| {
+        let (kind, detail) = match item {
+            Item::Function(func) => (FUNCTION, FuncDoc::of(func).signature),
+            Item::Global(global) => {
+                let kind = match global.kind {
+                    GlobalKind::Static => VARIABLE,
+                    GlobalKind::Const => CONSTANT,
+                };
+                (kind, global.to_string())
+            }
+            Item::Struct(strukt) => (STRUCT, strukt.to_string()),
+            Item::Malformed => return None,
+        };
+        Some(json!({ "label": item.name()?, "kind": kind, "detail": detail }))
+    });
+    locals.chain(items).collect()
+}
+
+/// diagnostics of a document, with code excluded by `cfg` marked as unnecessary
+pub fn diagnostics(diagnostics: &Diagnostics, ast: &Ast, lines: &LineIndex) -> Vec<Value> {
+    // see DiagnosticSeverity and DiagnosticTag of the LSP specification
+    const ERROR: u8 = 1;
+    const WARNING: u8 = 2;
+    const HINT: u8 = 4;
+    const UNNECESSARY: u8 = 1;
+
+    let errs = diagnostics.errs.iter().map(|err| {
+        json!({
+            "range": lines.range(&primary_span(&err.inner, &err.span)),
+            "severity": ERROR,
+            "code": err.inner.code(),
+            "source": "mini_compiler",
+            "message": err.inner.to_string(),
+        })
+    });
+    let warns = diagnostics.warns.iter().map(|warn| (WARNING, warn));
+    let denied = diagnostics.denied.iter().map(|warn| (ERROR, warn));
+    let warns = warns.chain(denied).map(|(severity, warn)| {
+        let AstWarn::DeadCodeError { err, .. } = &warn.inner;
+        json!({
+            "range": lines.range(&primary_span(err, &warn.span)),
+            "severity": severity,
+            "code": warn.inner.code(),
+            "source": "mini_compiler",
+            "message": warn.inner.to_string(),
+        })
+    });
+    let inactive = ast.inactive().map(|span| {
+        json!({
+            "range": lines.range(span),
+            "severity": HINT,
+            "source": "mini_compiler",
+            "message": "code is inactive due to cfg",
+            "tags": [UNNECESSARY],
+        })
+    });
+    errs.chain(warns).chain(inactive).collect()
+}
+
+/// the offending token of err, or the span of the whole block
+fn primary_span(err: &AstErr, block: &Span) -> Span {
+    match err {
+        AstErr::UnexpectedToken { found: token, .. } | AstErr::UnclosedBlock { at: token, .. } => {
+            token.span.clone()
+        }
+        _ => block.clone(),
+    }
+}
@@ -0,0 +1,534 @@
+//! A language server speaking JSON-RPC over stdio.
+//! Documents are synced in full and reparsed on every request.
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Value, json};
+
+use crate::{
+    frontend::{
+        Span,
+        ast::{Ast, Item, cfg::CfgEnv, lint::LintLevels},
+        get_ast, get_checked_ast,
+        import::Resolver,
+    },
+    lsp::analysis::{
+        LineIndex, Target, completions, function_hover, is_definition, item_hover,
+        local_definition, local_hover, locals, target_at,
+    },
+};
+
+mod analysis;
+
+/// JSON-RPC error code of unknown methods
+const METHOD_NOT_FOUND: i64 = -32601;
+
+pub struct Server {
+    /// text of the open documents
+    documents: HashMap<PathBuf, String>,
+    cfg_env: CfgEnv,
+    lint_levels: LintLevels,
+    include_dirs: Vec<PathBuf>,
+    lib_roots: Vec<PathBuf>,
+    extension: String,
+    shutdown: bool,
+}
+
+impl Server {
+    pub fn new(cfg_env: CfgEnv) -> Self {
+        Self {
+            documents: HashMap::new(),
+            cfg_env,
+            lint_levels: LintLevels::default(),
+            include_dirs: Vec::new(),
+            lib_roots: Vec::new(),
+            extension: "lang".into(),
+            shutdown: false,
+        }
+    }
+
+    /// directories searched for C headers
+    pub fn with_include_dirs(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.include_dirs.extend(dirs);
+        self
+    }
+
+    /// directories searched for modules and definitions of external functions
+    pub fn with_lib_roots(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.lib_roots.extend(dirs);
+        self
+    }
+
+    pub fn with_lint_levels(mut self, lint_levels: LintLevels) -> Self {
+        self.lint_levels = lint_levels;
+        self
+    }
+
+    pub fn with_extension(mut self, extension: &str) -> Self {
+        self.extension = extension.into();
+        self
+    }
+
+    /// serves the messages of input until the client exits.
+    /// Returns whether the client requested a shutdown before exiting
+    pub fn run(mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<bool> {
+        while let Some(msg) = read_message(&mut input)? {
+            if msg["method"] == "exit" {
+                break;
+            }
+            for reply in self.handle(&msg) {
+                write_message(&mut output, &reply)?;
+            }
+        }
+        Ok(self.shutdown)
+    }
+
+    /// the responses and notifications caused by msg
+    fn handle(&mut self, msg: &Value) -> Vec<Value> {
+        // responses of the client carry no method
+        let Some(method) = msg["method"].as_str() else {
+            return Vec::new();
+        };
+        let params = &msg["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "mini_compiler", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "shutdown" => {
+                self.shutdown = true;
+                Value::Null
+            }
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri_to_path(uri), text.into());
+                return vec![self.publish_diagnostics(uri)];
+            }
+            "textDocument/didChange" => {
+                // full sync, the last change holds the whole document
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri_to_path(uri), text.into());
+                }
+                return vec![self.publish_diagnostics(uri)];
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri_to_path(uri));
+                return vec![notification(
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": uri, "diagnostics": [] }),
+                )];
+            }
+            "textDocument/definition" => self.definition(uri, &params["position"]),
+            "textDocument/hover" => self.hover(uri, &params["position"]),
+            "textDocument/completion" => self.completion(uri, &params["position"]),
+            _ if msg.get("id").is_some() => {
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": msg["id"],
+                    "error": {
+                        "code": METHOD_NOT_FOUND,
+                        "message": format!("unsupported method {}", method),
+                    },
+                })];
+            }
+            // other notifications are ignored
+            _ => return Vec::new(),
+        };
+        vec![json!({ "jsonrpc": "2.0", "id": msg["id"], "result": result })]
+    }
+
+    fn resolver(&self, path: &Path) -> Resolver {
+        Resolver::new(path)
+            .with_include_dirs(self.include_dirs.iter().cloned())
+            .with_lib_roots(self.lib_roots.iter().cloned())
+            .with_extension(&self.extension)
+    }
+
+    /// the text of the file at path, preferring the open document
+    fn source(&self, path: &Path) -> Option<String> {
+        match self.documents.get(path) {
+            Some(text) => Some(text.clone()),
+            None => fs::read_to_string(path).ok(),
+        }
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let path = uri_to_path(uri);
+        let text = self.documents.get(&path).map_or("", String::as_str);
+        let (ast, mut diagnostics) = get_checked_ast(text, &self.cfg_env, &self.resolver(&path));
+        diagnostics.apply_lints(&ast, &self.lint_levels);
+        let lines = LineIndex::new(text);
+        notification(
+            "textDocument/publishDiagnostics",
+            json!({
+                "uri": uri,
+                "diagnostics": analysis::diagnostics(&diagnostics, &ast, &lines),
+            }),
+        )
+    }
+
+    /// calls f with the first item named name defined outside of the file at path,
+    /// searching its imports, its directory and the library roots
+    fn find_definition<T>(
+        &self,
+        path: &Path,
+        ast: &Ast,
+        name: &str,
+        f: impl Fn(&Path, &str, &Item) -> Option<T>,
+    ) -> Option<T> {
+        let resolver = self.resolver(path);
+        let imported = ast
+            .imports()
+            .filter_map(|import| resolver.resolve(&import.inner))
+            .collect::<Vec<_>>();
+        let siblings = path
+            .parent()
+            .map(|dir| source_files(dir, &self.extension, false))
+            .unwrap_or_default();
+        let library = self
+            .lib_roots
+            .iter()
+            .flat_map(|root| source_files(root, &self.extension, true));
+
+        let this = fs::canonicalize(path).ok();
+        imported
+            .into_iter()
+            .chain(siblings)
+            .chain(library)
+            .filter(|candidate| {
+                candidate
+                    .extension()
+                    .is_some_and(|ext| *ext == *self.extension)
+                    && fs::canonicalize(candidate).ok() != this
+            })
+            .find_map(|candidate| {
+                let text = self.source(&candidate)?;
+                let (module, _) = get_ast(&text, &self.cfg_env);
+                let item = module
+                    .funcs()
+                    .find(|item| item.name() == Some(name) && is_definition(item, &text))?;
+                f(&candidate, &text, item)
+            })
+    }
+
+    fn definition(&self, uri: &str, position: &Value) -> Value {
+        let path = uri_to_path(uri);
+        let Some(text) = self.documents.get(&path) else {
+            return Value::Null;
+        };
+        let (ast, _) = get_checked_ast(text, &self.cfg_env, &self.resolver(&path));
+        let lines = LineIndex::new(text);
+        let Some((target, func)) = target_at(&ast, lines.offset(position)) else {
+            return Value::Null;
+        };
+        let location = |uri: &str, text: &str, span: &Span| json!({ "uri": uri, "range": LineIndex::new(text).range(span) });
+
+        let (name, field) = match &target {
+            Target::Name(name) => {
+                if let Some(span) = func.and_then(|func| local_definition(func, name)) {
+                    return location(uri, text, &span);
+                }
+                (name, None)
+            }
+            Target::Item(name) => (name, None),
+            Target::Field(field) => {
+                // unqualified fields are looked up in the unique struct declaring them
+                let strukt = field.strukt.clone().or_else(|| {
+                    ast.structs()
+                        .find(|strukt| strukt.fields.contains(&field.name))
+                        .map(|strukt| strukt.name.clone())
+                });
+                let Some(strukt) = strukt else {
+                    return Value::Null;
+                };
+                (&strukt.clone(), Some(field.name.clone()))
+            }
+        };
+        // the span of the item, or of the field of a struct
+        let span_of = |item: &Item| match (item, &field) {
+            (Item::Struct(strukt), Some(field)) => strukt
+                .fields
+                .iter()
+                .position(|f| f == field)
+                .map(|i| strukt.field_spans[i].clone()),
+            (_, Some(_)) => None,
+            (item, None) => item.name_span(),
+        };
+
+        if let Some(item) = ast.funcs().find(|item| item.name() == Some(name))
+            && is_definition(item, text)
+        {
+            return span_of(item).map_or(Value::Null, |span| location(uri, text, &span));
+        }
+        self.find_definition(&path, &ast, name, |path, text, item| {
+            Some(location(&path_to_uri(path), text, &span_of(item)?))
+        })
+        .unwrap_or(Value::Null)
+    }
+
+    fn hover(&self, uri: &str, position: &Value) -> Value {
+        let path = uri_to_path(uri);
+        let Some(text) = self.documents.get(&path) else {
+            return Value::Null;
+        };
+        let (ast, _) = get_checked_ast(text, &self.cfg_env, &self.resolver(&path));
+        let lines = LineIndex::new(text);
+        let Some((target, func)) = target_at(&ast, lines.offset(position)) else {
+            return Value::Null;
+        };
+        let name = match &target {
+            Target::Name(name) => {
+                if let Some((_, ty)) = func
+                    .map(locals)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|(var, _)| var == name)
+                {
+                    return markdown(local_hover(name, ty.as_deref()));
+                }
+                name
+            }
+            Target::Item(name) => name,
+            Target::Field(field) => match &field.strukt {
+                Some(strukt) => strukt,
+                None => return Value::Null,
+            },
+        };
+        match ast.funcs().find(|item| item.name() == Some(name)) {
+            // the definition of an external function documents it
+            Some(Item::Function(func)) if func.body.is_none() => markdown(
+                self.find_definition(&path, &ast, name, |_, _, item| match item {
+                    Item::Function(def) => Some(function_hover(def)),
+                    _ => None,
+                })
+                .unwrap_or_else(|| function_hover(func)),
+            ),
+            Some(item) => markdown(item_hover(item)),
+            None => Value::Null,
+        }
+    }
+
+    fn completion(&self, uri: &str, position: &Value) -> Value {
+        let path = uri_to_path(uri);
+        let Some(text) = self.documents.get(&path) else {
+            return Value::Null;
+        };
+        let (ast, _) = get_checked_ast(text, &self.cfg_env, &self.resolver(&path));
+        let offset = LineIndex::new(text).offset(position);
+        let func = ast.funcs().find_map(|item| match item {
+            Item::Function(func) if func.span.contains(offset) => Some(func),
+            _ => None,
+        });
+        json!({ "isIncomplete": false, "items": completions(&ast, func) })
+    }
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn markdown(value: String) -> Value {
+    json!({ "contents": { "kind": "markdown", "value": value } })
+}
+
+/// reads a message framed by a Content-Length header. Returns None at the end of input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut len = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            len = value.trim().parse::<usize>().ok();
+        }
+    }
+    let Some(len) = len else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing Content-Length header",
+        ));
+    };
+    let mut body = vec![0; len];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message(output: &mut impl Write, msg: &Value) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// the files with extension in dir, sorted
+fn source_files(dir: &Path, extension: &str, recursive: bool) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+    {
+        if path.is_dir() && recursive {
+            files.extend(source_files(&path, extension, true));
+        } else if path.extension().is_some_and(|ext| *ext == *extension) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri).as_bytes();
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut i = 0;
+    while i < path.len() {
+        let escaped = (path[i] == b'%')
+            .then(|| std::str::from_utf8(path.get(i + 1..i + 3)?).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(path[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            write!(uri, "%{:02X}", byte).unwrap();
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn frame(msg: Value) -> String {
+        let body = msg.to_string();
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    #[test]
+    fn session() {
+        let dir = std::env::temp_dir().join(format!("mini_compiler_lsp_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("util.lang"),
+            "## Adds one.\npublic begin_def inc x;\n\treturn x + 1;\nend_def\n",
+        )
+        .unwrap();
+        let main = "extern_def inc;\n\
+                    cfg false;\nbegin_def unused;\nend_def\n\
+                    begin_def main;\n\tcount = 1;\n\tinc count : count;\n\t1 +;\nend_def\n";
+        let uri = path_to_uri(&dir.join("main.lang"));
+        let at = |needle: &str| {
+            let offset = main.find(needle).unwrap();
+            LineIndex::new(main).position(offset)
+        };
+        let request = |id: u64, method: &str, position: Value| {
+            frame(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": { "textDocument": { "uri": uri }, "position": position },
+            }))
+        };
+
+        let input = [
+            frame(json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} })),
+            frame(json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": { "textDocument": { "uri": uri, "text": main } },
+            })),
+            request(1, "textDocument/definition", at("inc count")),
+            request(2, "textDocument/hover", at("inc count")),
+            request(3, "textDocument/completion", at("1 +")),
+            request(4, "textDocument/definition", at("count;")),
+            request(5, "shutdown", Value::Null),
+            frame(json!({ "jsonrpc": "2.0", "method": "exit" })),
+        ]
+        .concat();
+        let mut output = Vec::new();
+        let clean = Server::new(CfgEnv::default())
+            .run(Cursor::new(input), &mut output)
+            .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(clean);
+
+        let mut output = Cursor::new(output);
+        let mut replies = HashMap::new();
+        let mut diagnostics = Vec::new();
+        while let Some(msg) = read_message(&mut output).unwrap() {
+            match msg["id"].as_u64() {
+                Some(id) => {
+                    replies.insert(id, msg["result"].clone());
+                }
+                None => diagnostics = msg["params"]["diagnostics"].as_array().unwrap().clone(),
+            }
+        }
+
+        assert_eq!(replies[&0]["capabilities"]["hoverProvider"], true);
+        assert!(diagnostics.iter().any(|d| d["severity"] == 1));
+        let inactive = diagnostics
+            .iter()
+            .find(|d| d["tags"] == json!([1]))
+            .unwrap();
+        assert_eq!(inactive["range"]["start"]["line"], 1);
+
+        let definition = &replies[&1];
+        assert!(definition["uri"].as_str().unwrap().ends_with("/util.lang"));
+        assert_eq!(definition["range"]["start"]["line"], 1);
+
+        let hover = replies[&2]["contents"]["value"].as_str().unwrap();
+        assert!(hover.contains("Adds one.") && hover.contains("takes 1 argument"));
+
+        let labels = replies[&3]["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert!(labels.contains(&"count") && labels.contains(&"inc") && labels.contains(&"main"));
+
+        assert_eq!(replies[&4]["uri"], uri);
+        assert_eq!(replies[&4]["range"]["start"], at("count = 1"));
+    }
+}
@@ -1,30 +1,52 @@
 use std::{
     collections::{HashMap, HashSet},
     fs::{self, File, create_dir_all},
-    io::{self, Read},
+    io::{self, BufWriter, Read},
     path::{Path, PathBuf},
     process::Command,
     sync::atomic::Ordering,
 };
 
-use clap::Parser;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, parser::ValueSource};
 use mini_compiler::{
-    VERBOSITY, backend,
-    frontend::{ast::cfg::CfgEnv, get_ast},
+    VERBOSITY,
+    backend::{
+        self, BackendErr,
+        doc::{DocFormat, Docs, ModuleDoc},
+        header::{CExport, write_c_header},
+        mangle::demangle_all,
+    },
+    error_codes::explain,
+    frontend::{
+        ast::{
+            Item,
+            cfg::CfgEnv,
+            error::MessageFormat,
+            lint::{Level, LintLevels},
+        },
+        fmt::{FmtOpts, format as format_source},
+        get_ast, get_checked_ast,
+        import::Resolver,
+    },
+    lsp::Server,
     print_if,
 };
 
 #[derive(clap::Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct ParserImpl {
+    #[command(subcommand)]
+    command: Option<Subcommand>,
+
     #[arg(required = true)]
     inputs: Vec<PathBuf>,
 
     #[arg(short, long, default_value = "lang")]
     extension: String,
 
-    #[arg(short, long, default_value = "a.out")]
-    output: String,
+    /// Name of the produced artifact. Defaults to a.out, libout.a or libout.so depending on the crate type
+    #[arg(short, long)]
+    output: Option<String>,
 
     #[arg(short, long, default_value = "./target")]
     target: String,
@@ -35,7 +57,7 @@ struct ParserImpl {
     #[arg(long, default_value_t = false)]
     clean: bool,
 
-    #[arg(long, default_value_t = false)]
+    #[arg(long, default_value_t = false, conflicts_with = "crate_type")]
     test: bool,
 
     #[arg(long = "cfg", value_name = "SPEC")]
@@ -43,13 +65,218 @@ struct ParserImpl {
 
     #[arg(long, default_value_t = false)]
     no_std: bool,
+
+    /// Emit position independent code and link a PIE executable
+    #[arg(long, default_value_t = false)]
+    pic: bool,
+
+    /// Kind of artifact to produce. Libraries are accompanied by a C header declaring their public functions
+    #[arg(long, value_enum, default_value_t = CrateType::Bin)]
+    crate_type: CrateType,
+
+    /// Additional directories searched for imported headers. /usr/include is always searched last
+    #[arg(short = 'I', long = "include-dir", value_name = "DIR")]
+    include_dirs: Vec<PathBuf>,
+
+    /// Outputs to produce. Compilation stops after the last requested stage
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [Emit::Link])]
+    emit: Vec<Emit>,
+
+    /// Annotate the emitted assembly and linker errors with the module paths of mangled symbols
+    #[arg(long, default_value_t = false)]
+    demangle: bool,
+
+    /// Print the explanation of an error code, e.g. E0003, and exit
+    #[arg(long, value_name = "CODE", exclusive = true)]
+    explain: Option<String>,
+
+    /// How errors and warnings are printed
+    #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+
+    #[command(flatten)]
+    lints: LintArgs,
+}
+
+// Levels of lints. `warnings` names all lints that would warn otherwise
+#[derive(clap::Args, Debug)]
+struct LintArgs {
+    /// Silence the warnings of a lint
+    #[arg(short = 'A', long = "allow", value_name = "LINT")]
+    allow: Vec<String>,
+
+    /// Report a lint as warning
+    #[arg(short = 'W', long = "warn", value_name = "LINT")]
+    warn: Vec<String>,
+
+    /// Report a lint as error, which fails the compilation
+    #[arg(short = 'D', long = "deny", value_name = "LINT")]
+    deny: Vec<String>,
+
+    /// Report all warnings as errors, like `-D warnings`
+    #[arg(long, default_value_t = false)]
+    deny_warnings: bool,
+}
+
+impl LintArgs {
+    /// the lint levels, where the last flag on the command line wins. Exits if a lint is unknown
+    fn levels(&self, matches: &ArgMatches) -> LintLevels {
+        let named = [
+            ("allow", &self.allow, Level::Allow),
+            ("warn", &self.warn, Level::Warn),
+            ("deny", &self.deny, Level::Deny),
+        ];
+        let mut flags = named
+            .into_iter()
+            .flat_map(|(id, names, level)| {
+                let indices = matches.indices_of(id).into_iter().flatten();
+                indices
+                    .zip(names)
+                    .map(move |(index, name)| (index, name.as_str(), level))
+            })
+            .collect::<Vec<_>>();
+        if matches.value_source("deny_warnings") == Some(ValueSource::CommandLine)
+            && let Some(index) = matches.index_of("deny_warnings")
+        {
+            flags.push((index, "warnings", Level::Deny));
+        }
+        flags.sort_by_key(|(index, ..)| *index);
+
+        let mut levels = LintLevels::new();
+        for (_, name, level) in flags {
+            levels = levels.with_named_level(name, level).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+        }
+        levels
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Subcommand {
+    /// Generate documentation of the public functions of the given files and directories
+    Doc(DocArgs),
+    /// Format the given files and directories in place
+    Fmt(FmtArgs),
+    /// Run a language server on stdin and stdout
+    Lsp(LspArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct FmtArgs {
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    #[arg(short, long, default_value = "lang")]
+    extension: String,
+
+    /// Do not write the files, but fail if any of them is not formatted
+    #[arg(long, default_value_t = false)]
+    check: bool,
+
+    /// Remove parentheses, which do not change how an expression is parsed
+    #[arg(long, default_value_t = false)]
+    drop_parens: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct DocArgs {
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    #[arg(short, long, default_value = "lang")]
+    extension: String,
+
+    #[arg(long, value_enum, default_value_t = DocFormat::Html)]
+    format: DocFormat,
+
+    /// Directory the pages are written to
+    #[arg(short, long, default_value = "./target/doc")]
+    out: PathBuf,
+
+    #[arg(long = "cfg", value_name = "SPEC")]
+    cfgs: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct LspArgs {
+    #[arg(short, long, default_value = "lang")]
+    extension: String,
+
+    #[arg(long = "cfg", value_name = "SPEC")]
+    cfgs: Vec<String>,
+
+    /// Additional directories searched for imported headers. /usr/include is always searched last
+    #[arg(short = 'I', long = "include-dir", value_name = "DIR")]
+    include_dirs: Vec<PathBuf>,
+
+    #[command(flatten)]
+    lints: LintArgs,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Emit {
+    /// assembly of each compiled file
+    Asm,
+    /// object file of each compiled file
+    Obj,
+    /// the final artifact
+    Link,
+    /// a C header with prototypes of the public functions of each compiled file
+    Header,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CrateType {
+    /// linked executable
+    Bin,
+    /// static archive (.a)
+    Staticlib,
+    /// shared library (.so)
+    Cdylib,
+}
+
+impl CrateType {
+    fn default_output(&self) -> &'static str {
+        match self {
+            Self::Bin => "a.out",
+            Self::Staticlib => "libout.a",
+            Self::Cdylib => "libout.so",
+        }
+    }
 }
 
 fn main() {
     let repo_root = env!("CARGO_MANIFEST_DIR");
-    let args = ParserImpl::parse();
+    let matches = ParserImpl::command().get_matches();
+    let args = ParserImpl::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
     VERBOSITY.store(args.verbosity, Ordering::Relaxed);
 
+    if let Some(code) = &args.explain {
+        match explain(code) {
+            Some(explanation) => print!("{}", explanation),
+            None => {
+                eprintln!("{} is not a known error code", code);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    match args.command {
+        Some(Subcommand::Doc(doc_args)) => return doc(doc_args, repo_root),
+        Some(Subcommand::Fmt(fmt_args)) => return fmt(fmt_args),
+        Some(Subcommand::Lsp(lsp_args)) => {
+            return lsp(
+                lsp_args,
+                matches.subcommand_matches("lsp").unwrap(),
+                repo_root,
+            );
+        }
+        None => {}
+    }
+
     let mut files: HashMap<String, HashSet<PathBuf>> = HashMap::new();
 
     for path in &args.inputs {
@@ -75,12 +302,19 @@ fn main() {
         return;
     }
 
+    let lint_levels = args.lints.levels(&matches);
     let mut cfg_env = CfgEnv::default().populate(&args.cfgs);
     if args.test {
         cfg_env = cfg_env.populate(&["test".into()]);
     }
 
-    let env_hash = fxhash::hash64(&cfg_env.as_list());
+    // shared objects can only be built from position independent code
+    let pic = args.pic || args.crate_type == CrateType::Cdylib;
+    let codegen_opts = backend::CodegenOpts::default()
+        .with_pic(pic)
+        .with_demangle(args.demangle);
+
+    let env_hash = fxhash::hash64(&(cfg_env.as_list(), pic, args.demangle));
 
     let target_dir = PathBuf::from(args.target);
     if let Err(e) = fs::create_dir_all(&target_dir) {
@@ -98,14 +332,49 @@ fn main() {
         recursive_collect(&test_lib, &mut files);
     }
 
-    if !args.no_std {
-        let std_lib: PathBuf = format!("{}/lib/std", repo_root).into();
-        recursive_collect(&std_lib, &mut files);
-    }
+    let include_dirs = args
+        .include_dirs
+        .iter()
+        .cloned()
+        .chain([PathBuf::from("/usr/include")])
+        .collect::<Vec<_>>();
+    let lib_root: PathBuf = format!("{}/lib", repo_root).into();
+    let resolver_for = |file: &Path| {
+        Resolver::new(file)
+            .with_include_dirs(include_dirs.iter().cloned())
+            .with_lib_roots([lib_root.clone()])
+            .with_extension(&args.extension)
+    };
 
-    print_if!(1, "compiling {} files...", files.len());
+    let std_index = if args.no_std {
+        HashMap::new()
+    } else {
+        index_library(&lib_root.join("std"), &args.extension, &cfg_env)
+    };
+    collect_dependencies(
+        &mut files,
+        &std_index,
+        &args.extension,
+        &cfg_env,
+        resolver_for,
+    );
+
+    print_if!(
+        1,
+        "compiling {} files...",
+        files.values().map(|f| f.len()).sum::<usize>()
+    );
 
     let mut total_errs = 0;
+    let mut exports = Vec::new();
+
+    let emit_asm = args.emit.iter().any(|e| *e != Emit::Header);
+    let emit_obj = args
+        .emit
+        .iter()
+        .any(|e| matches!(e, Emit::Obj | Emit::Link));
+    let emit_header = args.emit.contains(&Emit::Header);
+    let wants_exports = args.crate_type != CrateType::Bin || emit_header;
 
     for (ext, files) in &files {
         if !["asm", "o", &args.extension].contains(&ext.as_ref()) {
@@ -130,9 +399,13 @@ fn main() {
 
             create_dir_all(obj_path.parent().unwrap()).unwrap();
 
-            let needs_recompile = args.clean
-                || needs_recompile(file, &obj_path).unwrap()
-                || stale_meta(&obj_path, env_hash).unwrap();
+            let needs_recompile = emit_asm
+                && (args.clean
+                    || needs_recompile(file, &obj_path).unwrap()
+                    || stale_meta(&obj_path, env_hash).unwrap());
+
+            let mut file_exports = Vec::new();
+            let resolver = resolver_for(file);
 
             if needs_recompile && ext != "asm" {
                 print_if!(
@@ -141,40 +414,79 @@ fn main() {
                     file.file_name().unwrap().display(),
                     file.display()
                 );
-                let mut s = String::new();
-                File::open(file).unwrap().read_to_string(&mut s).unwrap();
+                let s = read_source(file);
 
-                let (ast, diagnostics) = get_ast(&s, &cfg_env);
+                let (ast, mut diagnostics) = get_checked_ast(&s, &cfg_env, &resolver);
                 print_if!(2, "AST for {}: {}", f_name, ast);
 
-                diagnostics.report(file.to_str().unwrap(), &s);
+                diagnostics.apply_lints(&ast, &lint_levels);
+                diagnostics.emit(file.to_str().unwrap(), &s, args.message_format);
 
-                if !diagnostics.errs.is_empty() {
-                    total_errs += diagnostics.errs.len();
+                if diagnostics.error_count() > 0 {
+                    total_errs += diagnostics.error_count();
                     continue;
                 }
 
-                let code = backend::generate(&ast).unwrap();
+                file_exports.extend(CExport::collect(&ast));
+
+                let code = match backend::generate(&ast) {
+                    Ok(code) => code,
+                    Err(e) => {
+                        e.emit(args.message_format);
+                        total_errs += 1;
+                        continue;
+                    }
+                };
                 print_if!(2, "IR for {}: {:#?}", f_name, code);
 
                 rm_stale_env_meta(&obj_path).unwrap();
-                backend::asm_gen(code, asm_path).unwrap();
+                let opts = codegen_opts.clone().with_module(resolver.module_path(file));
+                if let Err(e) = backend::asm_gen(code, asm_path, &opts) {
+                    e.emit(args.message_format);
+                    total_errs += 1;
+                    continue;
+                }
 
                 let env_meta_path = obj_path
                     .with_extension(format!("{:x}", env_hash))
                     .with_added_extension("meta");
 
                 File::create(env_meta_path).unwrap();
+            } else if wants_exports && ext == &args.extension {
+                // the object is up to date, but we still need its exports for the header
+                let s = read_source(file);
+                let (ast, mut diagnostics) = get_checked_ast(&s, &cfg_env, &resolver);
+                if !emit_asm {
+                    diagnostics.apply_lints(&ast, &lint_levels);
+                    diagnostics.emit(file.to_str().unwrap(), &s, args.message_format);
+                    total_errs += diagnostics.error_count();
+                }
+                file_exports.extend(CExport::collect(&ast));
+            }
+
+            if emit_header && ext == &args.extension {
+                let header_path = target_dir.join(safe_parent).join(format!("{}.h", f_name));
+                let mut out = BufWriter::new(File::create(&header_path).unwrap());
+                write_c_header(&file_exports, &format!("{}.h", f_name), &mut out).unwrap();
+                print_if!(1, "Wrote C header to {}", header_path.display());
             }
 
-            if needs_recompile {
+            if args.crate_type != CrateType::Bin {
+                exports.append(&mut file_exports);
+            }
+
+            if needs_recompile && emit_obj {
                 print_if!(
                     1,
                     "Assembling {} to {}",
                     asm_path.display(),
                     obj_path.display()
                 );
-                assemble(asm_path, &obj_path);
+                if let Err(e) = assemble(asm_path, &obj_path) {
+                    e.emit(args.message_format);
+                    total_errs += 1;
+                    continue;
+                }
             }
             obj_files.push(obj_path);
         }
@@ -186,10 +498,27 @@ fn main() {
             "\n\x1b[1;31mCompilation failed due to {} errors\x1b[0m\n",
             total_errs
         );
+        print_if!(
+            0,
+            "For more information about an error, try `mini_compiler --explain <CODE>`"
+        );
+        std::process::exit(1);
+    }
+
+    if !args.emit.contains(&Emit::Link) {
+        print_if!(
+            0,
+            "\x1b[1;32mFinished\x1b[0m Outputs in {}",
+            target_dir.display()
+        );
         return;
     }
 
-    let final_binary = target_dir.join(&args.output);
+    let final_binary = target_dir.join(
+        args.output
+            .as_deref()
+            .unwrap_or(args.crate_type.default_output()),
+    );
     print_if!(
         1,
         "Linking {} objects into {}",
@@ -197,12 +526,35 @@ fn main() {
         final_binary.display()
     );
 
-    let mut gcc_args = Vec::new();
-    if args.test {
-        gcc_args.push("-Wl,--wrap=main".into());
+    let linked = match args.crate_type {
+        CrateType::Bin => {
+            let mut gcc_args = vec![if pic { "-pie" } else { "-no-pie" }.to_string()];
+            if args.test {
+                gcc_args.push("-Wl,--wrap=main".into());
+            }
+            link_with_gcc(&obj_files, &final_binary, &gcc_args, args.demangle)
+        }
+        CrateType::Staticlib => archive(&obj_files, &final_binary),
+        CrateType::Cdylib => link_with_gcc(
+            &obj_files,
+            &final_binary,
+            &["-shared".into()],
+            args.demangle,
+        ),
+    };
+    if let Err(e) = linked {
+        e.emit(args.message_format);
+        std::process::exit(1);
     }
 
-    link_with_gcc(&obj_files, &final_binary, &gcc_args);
+    if args.crate_type != CrateType::Bin {
+        exports.sort_by(|a, b| a.name.cmp(&b.name));
+        let header_path = final_binary.with_extension("h");
+        let guard = header_path.file_name().unwrap().to_string_lossy();
+        let mut out = BufWriter::new(File::create(&header_path).unwrap());
+        write_c_header(&exports, &guard, &mut out).unwrap();
+        print_if!(1, "Wrote C header to {}", header_path.display());
+    }
 
     print_if!(
         0,
@@ -211,6 +563,225 @@ fn main() {
     );
 }
 
+/// Formats all source files in args.inputs, exits with 1 if a file could not be formatted
+/// or is not formatted in check mode
+fn fmt(args: FmtArgs) {
+    let mut files: HashMap<String, HashSet<PathBuf>> = HashMap::new();
+    for path in &args.inputs {
+        if path.is_dir() {
+            recursive_collect(path, &mut files);
+        } else if path.is_file() {
+            files
+                .entry(args.extension.clone())
+                .or_default()
+                .insert(fs::canonicalize(path).unwrap());
+        } else {
+            panic!("Input file not found: {}", path.display());
+        }
+    }
+
+    let opts = FmtOpts::default().with_drop_parens(args.drop_parens);
+    let mut failed = false;
+    let mut files = files
+        .remove(&args.extension)
+        .unwrap_or_default()
+        .into_iter()
+        .collect::<Vec<_>>();
+    files.sort();
+    for file in files {
+        let s = read_source(&file);
+        let formatted = match format_source(&s, &opts) {
+            Ok(formatted) => formatted,
+            Err(diagnostics) => {
+                diagnostics.report(file.to_str().unwrap(), &s);
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == s {
+            continue;
+        }
+        if args.check {
+            let line = s
+                .lines()
+                .zip(formatted.lines())
+                .position(|(old, new)| old != new)
+                .unwrap_or_else(|| s.lines().count().min(formatted.lines().count()));
+            print_if!(0, "Diff in {}:{}", file.display(), line + 1);
+            failed = true;
+        } else {
+            fs::write(&file, formatted).unwrap();
+            print_if!(1, "Formatted {}", file.display());
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Writes the documentation of all source files in args.inputs
+fn doc(args: DocArgs, repo_root: &str) {
+    let mut files: HashMap<String, HashSet<PathBuf>> = HashMap::new();
+    for path in &args.inputs {
+        if path.is_dir() {
+            recursive_collect(path, &mut files);
+        } else if path.is_file() {
+            files
+                .entry(args.extension.clone())
+                .or_default()
+                .insert(fs::canonicalize(path).unwrap());
+        } else {
+            panic!("Input file not found: {}", path.display());
+        }
+    }
+
+    let cfg_env = CfgEnv::default().populate(&args.cfgs);
+    let lib_root: PathBuf = format!("{}/lib", repo_root).into();
+    let mut total_errs = 0;
+    let mut modules = Vec::new();
+    for file in files.remove(&args.extension).unwrap_or_default() {
+        let s = read_source(&file);
+        let (ast, mut diagnostics) = get_ast(&s, &cfg_env);
+        diagnostics.apply_lints(&ast, &LintLevels::new());
+        diagnostics.report(file.to_str().unwrap(), &s);
+        total_errs += diagnostics.error_count();
+        let module = Resolver::new(&file)
+            .with_lib_roots([lib_root.clone()])
+            .module_path(&file);
+        modules.push(ModuleDoc::collect(module, &ast));
+    }
+
+    if total_errs > 0 {
+        print_if!(
+            0,
+            "\n\x1b[1;31mDocumentation failed due to {} errors\x1b[0m\n",
+            total_errs
+        );
+        return;
+    }
+
+    create_dir_all(&args.out).unwrap();
+    for (name, page) in Docs::new(modules).pages(args.format) {
+        fs::write(args.out.join(name), page).unwrap();
+    }
+    print_if!(
+        0,
+        "\x1b[1;32mFinished\x1b[0m Documentation in {}",
+        args.out
+            .join(format!("index.{}", args.format.extension()))
+            .display()
+    );
+}
+
+/// Serves the language server protocol until the client exits
+fn lsp(args: LspArgs, matches: &ArgMatches, repo_root: &str) {
+    // stdout carries the protocol
+    VERBOSITY.store(0, Ordering::Relaxed);
+    let server = Server::new(CfgEnv::default().populate(&args.cfgs))
+        .with_include_dirs(
+            args.include_dirs
+                .into_iter()
+                .chain([PathBuf::from("/usr/include")]),
+        )
+        .with_lib_roots([format!("{}/lib", repo_root).into()])
+        .with_lint_levels(args.lints.levels(matches))
+        .with_extension(&args.extension);
+    let clean_exit = server.run(io::stdin().lock(), io::stdout().lock()).unwrap();
+    if !clean_exit {
+        std::process::exit(1);
+    }
+}
+
+/// Adds all files transitively required by the source files in files,
+/// i.e. imported modules and the library files defining declared external functions
+fn collect_dependencies(
+    files: &mut HashMap<String, HashSet<PathBuf>>,
+    library: &HashMap<String, PathBuf>,
+    extension: &str,
+    cfg_env: &CfgEnv,
+    resolver_for: impl Fn(&Path) -> Resolver,
+) {
+    let mut queue = files
+        .get(extension)
+        .map(|sources| sources.iter().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    while let Some(file) = queue.pop() {
+        let s = read_source(&file);
+        let (ast, _) = get_ast(&s, cfg_env);
+        let resolver = resolver_for(&file);
+
+        let imported = ast
+            .imports()
+            .filter_map(|import| resolver.resolve(&import.inner));
+        let defining = ast
+            .funcs()
+            .filter_map(|item| match (item.name(), item.link_attr()) {
+                (Some(name), Some(link_attr)) if link_attr.external => library.get(name).cloned(),
+                _ => None,
+            });
+
+        for dep in imported.chain(defining).collect::<Vec<_>>() {
+            let Some(ext) = dep.extension().and_then(|e| e.to_str()) else {
+                continue;
+            };
+            if !["asm", "o", extension].contains(&ext) {
+                continue;
+            }
+            if files
+                .entry(ext.to_string())
+                .or_default()
+                .insert(dep.clone())
+                && ext == extension
+            {
+                queue.push(dep);
+            }
+        }
+    }
+}
+
+/// Maps every symbol exported by the files in dir to its defining file
+fn index_library(dir: &Path, extension: &str, cfg_env: &CfgEnv) -> HashMap<String, PathBuf> {
+    let mut files = HashMap::new();
+    recursive_collect(dir, &mut files);
+
+    let mut index = HashMap::new();
+    for (ext, paths) in files {
+        for path in paths {
+            let s = read_source(&path);
+            if ext == extension {
+                let (ast, _) = get_ast(&s, cfg_env);
+                // structs have no symbol, they are copied into every importing module
+                let exported = ast.funcs().filter(|item| {
+                    !matches!(item, Item::Struct(_))
+                        && item
+                            .link_attr()
+                            .is_some_and(|attr| attr.is_public && !attr.external)
+                });
+                for name in exported.filter_map(Item::name) {
+                    index.insert(name.to_string(), path.clone());
+                }
+            } else if ext == "asm" {
+                for globals in s.lines().filter_map(|l| l.trim().strip_prefix("global ")) {
+                    for symbol in globals.split(',') {
+                        // strip symbol types, i.e. `global x:data 8`
+                        let symbol = symbol.split(':').next().unwrap_or(symbol);
+                        index.insert(symbol.trim().to_string(), path.clone());
+                    }
+                }
+            }
+        }
+    }
+    index
+}
+
+fn read_source(file: &Path) -> String {
+    let mut s = String::new();
+    File::open(file).unwrap().read_to_string(&mut s).unwrap();
+    s
+}
+
 fn rm_stale_env_meta(target_obj: &Path) -> Result<(), io::Error> {
     let stem = target_obj.with_extension("");
     let parent_dir = target_obj.parent().unwrap();
@@ -256,7 +827,7 @@ fn needs_recompile(source: &Path, target_obj: &Path) -> Result<bool, io::Error>
     Ok(obj_mtime < source_mtime)
 }
 
-fn assemble(asm_path: &Path, obj_path: &Path) {
+fn assemble(asm_path: &Path, obj_path: &Path) -> Result<(), BackendErr> {
     let status = Command::new("nasm")
         .args([
             "-f",
@@ -267,11 +838,19 @@ fn assemble(asm_path: &Path, obj_path: &Path) {
         ])
         .status()
         .expect("failed to run nasm");
-    assert!(status.success(), "nasm failed for {}", asm_path.display());
+    status
+        .success()
+        .then_some(())
+        .ok_or_else(|| BackendErr::Assemble(asm_path.to_path_buf()))
 }
 
-fn link_with_gcc(obj_files: &[PathBuf], out_path: &Path, extra_args: &[String]) {
-    let mut args = vec!["-no-pie".to_string()];
+fn link_with_gcc(
+    obj_files: &[PathBuf],
+    out_path: &Path,
+    extra_args: &[String],
+    demangle: bool,
+) -> Result<(), BackendErr> {
+    let mut args = Vec::new();
 
     for obj in obj_files {
         args.push(obj.display().to_string());
@@ -282,11 +861,39 @@ fn link_with_gcc(obj_files: &[PathBuf], out_path: &Path, extra_args: &[String])
 
     args.extend_from_slice(extra_args);
 
-    let status = Command::new("gcc")
-        .args(&args)
+    let mut gcc = Command::new("gcc");
+    gcc.args(&args);
+    let status = if demangle {
+        // linker errors name the mangled symbols of private functions
+        let output = gcc.output().expect("failed to run gcc");
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+        eprint!("{}", demangle_all(&String::from_utf8_lossy(&output.stderr)));
+        output.status
+    } else {
+        gcc.status().expect("failed to run gcc")
+    };
+    status
+        .success()
+        .then_some(())
+        .ok_or_else(|| BackendErr::Link(out_path.to_path_buf()))
+}
+
+fn archive(obj_files: &[PathBuf], out_path: &Path) -> Result<(), BackendErr> {
+    // ar appends to existing archives, so stale members have to be removed first
+    if out_path.exists() {
+        fs::remove_file(out_path).unwrap();
+    }
+
+    let status = Command::new("ar")
+        .arg("rcs")
+        .arg(out_path)
+        .args(obj_files)
         .status()
-        .expect("failed to run gcc");
-    assert!(status.success(), "gcc linking failed");
+        .expect("failed to run ar");
+    status
+        .success()
+        .then_some(())
+        .ok_or_else(|| BackendErr::Archive(out_path.to_path_buf()))
 }
 
 fn recursive_collect(dir: &Path, files: &mut HashMap<String, HashSet<PathBuf>>) {